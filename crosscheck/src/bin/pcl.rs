@@ -0,0 +1,498 @@
+//! `pcl`: runs the ops of the lab on an image or on vectors with the OpenCL, wgpu or CPU
+//! backend, instead of editing the constants of the examples:
+//!
+//! ```text
+//! pcl rotate --input data/cat.png --output rotated.png --degrees 45 --backend opencl
+//! pcl histogram --input data/cat.png --bins 16 --json
+//! pcl bench convolve --input data/cat.png --radius 7 --iterations 20
+//! pcl job --input jobs/blur_rotate.toml
+//! ```
+//!
+//! Exits with 0 on success, 1 when the backend is unavailable or the op fails and 2 on a
+//! usage error.
+
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crosscheck::{Backend, Backends, GrayImage, JobResult, Op};
+use lab_common::job::{rebin, Job};
+use rust_wgpu::pipeline::HIST_BINS;
+use serde_json::json;
+
+const USAGE: &str = "\
+usage: pcl <command> [options]
+
+commands:
+  convolve    --input PATH [--output PATH] [--radius N] [--sigma S]
+  rotate      --input PATH [--output PATH] [--degrees D]
+  histogram   --input PATH [--bins N]
+  vecadd      [--len N]
+  job         --input JOB   a .toml or .json job, on the backend it names
+  devices
+  bench       <convolve|rotate|histogram|vecadd> [its options] [--iterations N]
+
+options:
+  --backend <opencl|wgpu|cpu>   the backend to run on, wgpu by default
+  --device NAME                 a part of the OpenCL device or wgpu adapter name
+  --json                        print the result as JSON
+  --help                        print this help";
+
+/// Environment variable wgpu picks its adapter by.
+const WGPU_ADAPTER_ENV: &str = "WGPU_ADAPTER_NAME";
+
+/// Why `pcl` failed, which decides the exit code.
+enum Failure {
+    /// Bad arguments, exit code 2.
+    Usage(String),
+    /// An unavailable backend or a failed op, exit code 1.
+    Failed(String),
+}
+
+#[derive(Clone)]
+struct Options {
+    command: String,
+    /// The op `bench` runs.
+    op: Option<String>,
+    backend: Backend,
+    /// `--backend` was given; a job that names another backend is then a usage error.
+    explicit_backend: bool,
+    device: Option<String>,
+    json: bool,
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    radius: i32,
+    sigma: f32,
+    degrees: f32,
+    bins: usize,
+    len: usize,
+    iterations: usize,
+}
+
+impl Options {
+    /// The op to run, the command itself except for `bench`.
+    fn op(&self) -> &str {
+        self.op.as_deref().unwrap_or(&self.command)
+    }
+}
+
+/// The options of each command besides `--backend`, `--device` and `--json`, `None` for an
+/// unknown command.
+fn command_options(command: &str) -> Option<&'static [&'static str]> {
+    Some(match command {
+        "convolve" => &["--input", "--output", "--radius", "--sigma"],
+        "rotate" => &["--input", "--output", "--degrees"],
+        "histogram" => &["--input", "--bins"],
+        "vecadd" => &["--len"],
+        "job" => &["--input"],
+        "devices" => &[],
+        _ => return None,
+    })
+}
+
+fn value<T: FromStr>(flag: &str, value: Option<&String>) -> Result<T, Failure>
+where
+    T::Err: Display,
+{
+    let value = value.ok_or_else(|| Failure::Usage(format!("{flag} needs a value")))?;
+    value
+        .parse()
+        .map_err(|err| Failure::Usage(format!("{flag} {value}: {err}")))
+}
+
+fn parse(args: &[String]) -> Result<Options, Failure> {
+    let usage = |message: String| Failure::Usage(message);
+    let mut args = args.iter();
+    let command = args
+        .next()
+        .ok_or_else(|| usage("missing command".to_string()))?;
+    let op = match command.as_str() {
+        "bench" => Some(
+            args.next()
+                .filter(|op| !matches!(op.as_str(), "devices" | "job"))
+                .filter(|op| command_options(op).is_some())
+                .ok_or_else(|| usage("bench needs an op, e.g. `pcl bench rotate`".to_string()))?
+                .clone(),
+        ),
+        _ => None,
+    };
+    let allowed = command_options(op.as_deref().unwrap_or(command))
+        .ok_or_else(|| usage(format!("unknown command `{command}`")))?;
+    let bench = op.is_some();
+
+    let mut options = Options {
+        command: command.clone(),
+        op,
+        backend: Backend::Wgpu,
+        explicit_backend: false,
+        device: None,
+        json: false,
+        input: None,
+        output: None,
+        radius: 2,
+        sigma: 1.0,
+        degrees: 30.0,
+        bins: HIST_BINS,
+        len: 1 << 20,
+        iterations: 10,
+    };
+    while let Some(flag) = args.next() {
+        let flag = flag.as_str();
+        match flag {
+            "--json" => {
+                options.json = true;
+                continue;
+            }
+            "--backend" | "--device" => {}
+            "--iterations" if bench => {}
+            // a benchmark saves nothing
+            "--output" if bench => return Err(usage("bench takes no --output".to_string())),
+            _ if allowed.contains(&flag) => {}
+            _ => return Err(usage(format!("unexpected option `{flag}` for {command}"))),
+        }
+
+        let next = args.next();
+        match flag {
+            "--backend" => {
+                let name: String = value(flag, next)?;
+                options.backend = Backend::from_name(&name).ok_or_else(|| {
+                    usage(format!(
+                        "unknown backend `{name}`, expected opencl, wgpu or cpu"
+                    ))
+                })?;
+                options.explicit_backend = true;
+            }
+            "--device" => options.device = Some(value(flag, next)?),
+            "--input" => options.input = Some(value(flag, next)?),
+            "--output" => options.output = Some(value(flag, next)?),
+            "--radius" => options.radius = value(flag, next)?,
+            "--sigma" => options.sigma = value(flag, next)?,
+            "--degrees" => options.degrees = value(flag, next)?,
+            "--bins" => options.bins = value(flag, next)?,
+            "--len" => options.len = value(flag, next)?,
+            "--iterations" => options.iterations = value(flag, next)?,
+            _ => unreachable!("{flag} is allowed but not parsed"),
+        }
+    }
+
+    let op = options.op();
+    if allowed.contains(&"--input") && options.input.is_none() {
+        return Err(usage(format!("{op} needs --input")));
+    }
+    if options.radius < 0 || !(options.sigma.is_finite() && options.sigma > 0.0) {
+        return Err(usage(
+            "--radius must not be negative, --sigma must be positive".to_string(),
+        ));
+    }
+    if options.bins == 0 || !HIST_BINS.is_multiple_of(options.bins) {
+        return Err(usage(format!("--bins must divide {HIST_BINS}")));
+    }
+    if options.len == 0 || options.iterations == 0 {
+        return Err(usage("--len and --iterations must not be zero".to_string()));
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    match parse(&args).and_then(|options| run(&options)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Usage(message)) => {
+            eprintln!("pcl: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(Failure::Failed(message)) => {
+            eprintln!("pcl: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), Failure> {
+    if options.command == "devices" {
+        devices(options);
+        return Ok(());
+    }
+
+    // a job runs on the backend it names, which an explicit `--backend` must match
+    let job = match options.command.as_str() {
+        "job" => {
+            let path = options.input.as_ref().expect("job needs --input");
+            Some(Job::load(path).map_err(|err| Failure::Failed(err.to_string()))?)
+        }
+        _ => None,
+    };
+    let backend = match &job {
+        Some(job) if options.explicit_backend => {
+            options
+                .backend
+                .check_job(job)
+                .map_err(|err| Failure::Usage(err.to_string()))?;
+            options.backend
+        }
+        Some(job) => job.backend.map_or(options.backend, Backend::from),
+        None => options.backend,
+    };
+    let options = &Options {
+        backend,
+        ..options.clone()
+    };
+
+    // the backends pick their device by these variables
+    let backend = options.backend;
+    if let Some(device) = &options.device {
+        match backend {
+            Backend::OpenCl => std::env::set_var(lab_opencl::device::DEVICE_ENV, device),
+            Backend::Wgpu => std::env::set_var(WGPU_ADAPTER_ENV, device),
+            Backend::Cpu => {}
+        }
+    }
+    let backends = Backends::init_for(&[backend]);
+    let device = backends
+        .describe(backend)
+        .ok_or_else(|| Failure::Failed(format!("the {} backend is unavailable", backend.name())))?;
+    // wgpu falls back to the default adapter when no adapter has the name
+    if let Some(wanted) = options.device.as_ref().filter(|_| backend != Backend::Cpu) {
+        if !device.to_lowercase().contains(&wanted.to_lowercase()) {
+            return Err(Failure::Failed(format!(
+                "no {} device matches `{wanted}`, got {device}",
+                backend.name()
+            )));
+        }
+    }
+
+    if let Some(job) = &job {
+        let start = Instant::now();
+        let result = backends
+            .run_job(backend, job)
+            .map_err(|err| Failure::Failed(err.to_string()))?;
+        let outcome = Outcome::Job(result, job.output.clone());
+        report(options, &device, start.elapsed(), &outcome);
+        return Ok(());
+    }
+    let input = load_input(options)?;
+    if options.op.is_some() {
+        return bench(options, &backends, &input, &device);
+    }
+    let start = Instant::now();
+    let outcome = execute(options, &backends, &input)?;
+    let elapsed = start.elapsed();
+    if let (Some(path), Outcome::Image(image)) = (&options.output, &outcome) {
+        image
+            .save(path)
+            .map_err(|err| Failure::Failed(format!("{}: {err}", path.display())))?;
+    }
+    report(options, &device, elapsed, &outcome);
+    Ok(())
+}
+
+enum Input {
+    Image(GrayImage),
+    Vectors(Vec<f32>, Vec<f32>),
+}
+
+/// The result of one run of an op.
+enum Outcome {
+    Image(GrayImage),
+    Histogram(Vec<u32>),
+    Vector {
+        len: usize,
+        max_abs: f32,
+    },
+    /// A job and where it saved its image, if anywhere.
+    Job(JobResult, Option<PathBuf>),
+}
+
+fn load_input(options: &Options) -> Result<Input, Failure> {
+    let Some(path) = &options.input else {
+        let a = (0..options.len).map(|i| i as f32 * 0.5).collect();
+        let b = (0..options.len).map(|i| (i as f32).sin()).collect();
+        return Ok(Input::Vectors(a, b));
+    };
+    GrayImage::open(path)
+        .map(Input::Image)
+        .map_err(|err| Failure::Failed(format!("{}: {err}", path.display())))
+}
+
+fn execute(options: &Options, backends: &Backends, input: &Input) -> Result<Outcome, Failure> {
+    let (op, backend) = (options.op(), options.backend);
+    let failed = || {
+        Failure::Failed(format!(
+            "{op} failed on the {} backend, see the log (RUST_LOG=error)",
+            backend.name()
+        ))
+    };
+    match (op, input) {
+        ("convolve" | "rotate", Input::Image(image)) => {
+            let image_op = match op {
+                "convolve" => Op::Convolution {
+                    radius: options.radius,
+                    sigma: options.sigma,
+                },
+                _ => Op::Rotation {
+                    theta: options.degrees.to_radians(),
+                },
+            };
+            let data = backends.run(backend, image_op, image).ok_or_else(failed)?;
+            Ok(Outcome::Image(GrayImage {
+                cols: image.cols,
+                rows: image.rows,
+                data,
+            }))
+        }
+        ("histogram", Input::Image(image)) => {
+            let counts = backends.histogram(backend, image).ok_or_else(failed)?;
+            Ok(Outcome::Histogram(rebin(&counts, options.bins)))
+        }
+        ("vecadd", Input::Vectors(a, b)) => {
+            let out = backends.vector_add(backend, a, b).ok_or_else(failed)?;
+            let max_abs = out
+                .iter()
+                .zip(a.iter().zip(b))
+                .map(|(out, (a, b))| (out - (a + b)).abs())
+                .fold(0.0, f32::max);
+            Ok(Outcome::Vector {
+                len: out.len(),
+                max_abs,
+            })
+        }
+        _ => unreachable!("{op} on the wrong input"),
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e3
+}
+
+fn report(options: &Options, device: &str, elapsed: Duration, outcome: &Outcome) {
+    let backend = options.backend.name();
+    let mut report = json!({
+        "command": options.command,
+        "backend": backend,
+        "device": device,
+        "elapsed_ms": millis(elapsed),
+    });
+    match outcome {
+        Outcome::Image(image) => {
+            report["cols"] = json!(image.cols);
+            report["rows"] = json!(image.rows);
+            report["output"] = json!(options.output);
+        }
+        Outcome::Histogram(counts) => report["bins"] = json!(counts),
+        Outcome::Vector { len, max_abs } => {
+            report["len"] = json!(len);
+            report["max_abs"] = json!(max_abs);
+        }
+        Outcome::Job(result, output) => {
+            report["cols"] = json!(result.image.cols);
+            report["rows"] = json!(result.image.rows);
+            report["output"] = json!(output);
+            report["histograms"] = json!(result.histograms);
+        }
+    }
+    if options.json {
+        println!("{report}");
+        return;
+    }
+
+    println!(
+        "{} on {backend} ({device}) in {:.2} ms",
+        options.command,
+        millis(elapsed)
+    );
+    match outcome {
+        Outcome::Image(image) => match &options.output {
+            Some(path) => println!("{}x{}, saved {}", image.cols, image.rows, path.display()),
+            None => println!("{}x{}, not saved (no --output)", image.cols, image.rows),
+        },
+        Outcome::Histogram(counts) => println!("bins: {counts:?}"),
+        Outcome::Vector { len, max_abs } => println!("{len} elements, max abs error {max_abs}"),
+        Outcome::Job(result, output) => {
+            let (cols, rows) = (result.image.cols, result.image.rows);
+            match output {
+                Some(path) => println!("{cols}x{rows}, saved {}", path.display()),
+                None => println!("{cols}x{rows}, not saved (no output in the job)"),
+            }
+            for counts in &result.histograms {
+                println!("bins: {counts:?}");
+            }
+        }
+    }
+}
+
+/// Time `iterations` runs of the op after a first one, which builds the kernels or shaders.
+///
+/// Each run is a whole op call, uploads and downloads included.
+fn bench(
+    options: &Options,
+    backends: &Backends,
+    input: &Input,
+    device: &str,
+) -> Result<(), Failure> {
+    execute(options, backends, input)?;
+    let mut times = Vec::with_capacity(options.iterations);
+    for _ in 0..options.iterations {
+        let start = Instant::now();
+        execute(options, backends, input)?;
+        times.push(start.elapsed());
+    }
+    times.sort();
+    let mean = times.iter().sum::<Duration>() / times.len() as u32;
+    let (min, median, max) = (times[0], times[times.len() / 2], times[times.len() - 1]);
+
+    let (op, backend) = (options.op(), options.backend.name());
+    if options.json {
+        let report = json!({
+            "command": "bench",
+            "op": op,
+            "backend": backend,
+            "device": device,
+            "iterations": options.iterations,
+            "min_ms": millis(min),
+            "median_ms": millis(median),
+            "mean_ms": millis(mean),
+            "max_ms": millis(max),
+        });
+        println!("{report}");
+    } else {
+        println!(
+            "{op} on {backend} ({device}), {} runs: min {:.2} ms, median {:.2} ms, mean {:.2} ms, \
+             max {:.2} ms",
+            options.iterations,
+            millis(min),
+            millis(median),
+            millis(mean),
+            millis(max)
+        );
+    }
+    Ok(())
+}
+
+/// Report the capabilities of the OpenCL devices and wgpu adapters.
+fn devices(options: &Options) {
+    let opencl = lab_opencl::capabilities::report().unwrap_or_else(|err| {
+        log::warn!("no OpenCL platforms - {err}");
+        Vec::new()
+    });
+    let wgpu = rust_wgpu::capabilities::report();
+
+    if options.json {
+        println!("{}", json!({ "opencl": opencl, "wgpu": wgpu }));
+        return;
+    }
+    for device in &opencl {
+        println!("opencl  {device}\n");
+    }
+    for adapter in &wgpu {
+        println!("wgpu    {adapter}\n");
+    }
+}
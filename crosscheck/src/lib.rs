@@ -0,0 +1,555 @@
+//! Runs the same op on the OpenCL, wgpu and CPU backends and measures how far their outputs
+//! drift apart.
+//!
+//! The backends disagree on conventions: OpenCL works on single channel images, clamps to
+//! the edge and samples texel centers, while wgpu works on RGBA images, pads with zeros and
+//! samples texel corners. The ops therefore run on a gray image (replicated into the RGBA
+//! channels for wgpu), and the CPU backend follows the wgpu shaders.
+
+use std::fmt;
+use std::path::Path;
+
+use lab_common::job::{self, rebin, Interp, Job, JobError};
+use ocl::Queue;
+use rust_wgpu::pipeline::HIST_BINS;
+use rust_wgpu::WgpuState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Cpu,
+    Wgpu,
+    OpenCl,
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Cpu => "cpu",
+            Backend::Wgpu => "wgpu",
+            Backend::OpenCl => "opencl",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Backend::Cpu, Backend::Wgpu, Backend::OpenCl]
+            .into_iter()
+            .find(|backend| backend.name() == name)
+    }
+
+    /// Check that `job` can run on the backend, i.e. it names no other one, see
+    /// [`Job::check_backend`]. The CPU backend only runs the jobs that name none.
+    pub fn check_job(self, job: &Job) -> Result<(), JobError> {
+        match self {
+            Backend::Wgpu => job.check_backend(job::Backend::Wgpu),
+            Backend::OpenCl => job.check_backend(job::Backend::Opencl),
+            Backend::Cpu => match job.backend {
+                Some(other) => Err(JobError::Invalid {
+                    op: None,
+                    message: format!("the job is for the {} backend, not cpu", other.name()),
+                }),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+impl From<job::Backend> for Backend {
+    fn from(backend: job::Backend) -> Self {
+        match backend {
+            job::Backend::Opencl => Backend::OpenCl,
+            job::Backend::Wgpu => Backend::Wgpu,
+        }
+    }
+}
+
+/// An op with its parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// Gaussian blur.
+    Convolution { radius: i32, sigma: f32 },
+    /// Rotation by `theta` radians around the image center.
+    Rotation { theta: f32 },
+}
+
+impl Op {
+    /// The op named `name` with default parameters, `None` for an unknown name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "convolution" => Some(Op::Convolution {
+                radius: 2,
+                sigma: 1.0,
+            }),
+            "rotation" => Some(Op::Rotation {
+                theta: 30f32.to_radians(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A single channel image, row-major with values in [0, 1].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrayImage {
+    pub cols: u32,
+    pub rows: u32,
+    pub data: Vec<f32>,
+}
+
+impl GrayImage {
+    pub fn open(path: impl AsRef<Path>) -> image::ImageResult<Self> {
+        let image = image::open(path)?.to_luma32f();
+        Ok(Self {
+            cols: image.width(),
+            rows: image.height(),
+            data: image.into_raw(),
+        })
+    }
+
+    /// Save as an 8-bit gray image, e.g. a PNG.
+    pub fn save(&self, path: impl AsRef<Path>) -> image::ImageResult<()> {
+        let pixels: Vec<u8> = self.data.iter().map(|v| (v * 255.0) as u8).collect();
+        image::save_buffer(path, &pixels, self.cols, self.rows, image::ColorType::L8)
+    }
+}
+
+/// The results of a job, see [`Backends::run_job`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobResult {
+    /// The image after the last op.
+    pub image: GrayImage,
+    /// The counts of the histogram ops, in order.
+    pub histograms: Vec<Vec<u32>>,
+}
+
+/// The available devices; a backend without one is skipped.
+pub struct Backends {
+    wgpu: Option<WgpuState>,
+    opencl: Option<Queue>,
+}
+
+impl Backends {
+    /// Initialize every backend that has an adapter or device.
+    pub fn init() -> Self {
+        Self::init_for(&[Backend::Wgpu, Backend::OpenCl])
+    }
+
+    /// Initialize `backends` only; the CPU needs no initialization.
+    pub fn init_for(backends: &[Backend]) -> Self {
+        let wgpu = if backends.contains(&Backend::Wgpu) {
+            pollster::block_on(WgpuState::try_init())
+        } else {
+            None
+        };
+        let opencl = if backends.contains(&Backend::OpenCl) {
+            lab_opencl::device::default_queue(None)
+                .map_err(|err| log::warn!("OpenCL backend unavailable - {err}"))
+                .ok()
+        } else {
+            None
+        };
+        Self { wgpu, opencl }
+    }
+
+    /// The adapter or device of `backend`, `None` when it is unavailable.
+    pub fn describe(&self, backend: Backend) -> Option<String> {
+        match backend {
+            Backend::Cpu => Some("host".to_string()),
+            Backend::Wgpu => self.wgpu.as_ref().map(WgpuState::describe),
+            Backend::OpenCl => self.opencl.as_ref().map(lab_opencl::device::describe),
+        }
+    }
+
+    pub fn available(&self) -> Vec<Backend> {
+        let mut backends = vec![Backend::Cpu];
+        if self.wgpu.is_some() {
+            backends.push(Backend::Wgpu);
+        }
+        if self.opencl.is_some() {
+            backends.push(Backend::OpenCl);
+        }
+        backends
+    }
+
+    /// Run `op` on `backend`, `None` when the backend is unavailable or fails.
+    pub fn run(&self, backend: Backend, op: Op, image: &GrayImage) -> Option<Vec<f32>> {
+        let (cols, rows) = (image.cols, image.rows);
+        match backend {
+            Backend::Cpu => {
+                let (cols, rows) = (cols as usize, rows as usize);
+                Some(match op {
+                    Op::Convolution { radius, sigma } => {
+                        let filter = rust_wgpu::generate_gaussian_kernel(radius, sigma);
+                        rust_wgpu::reference::convolution(&image.data, cols, rows, 1, &filter)
+                    }
+                    Op::Rotation { theta } => {
+                        rust_wgpu::reference::rotation(&image.data, cols, rows, 1, theta)
+                    }
+                })
+            }
+            Backend::Wgpu => {
+                let state = self.wgpu.as_ref()?;
+                let rgba = to_rgba(&image.data);
+                let out = pollster::block_on(async {
+                    match op {
+                        Op::Convolution { radius, sigma } => {
+                            let filter = rust_wgpu::generate_gaussian_kernel(radius, sigma);
+                            rust_wgpu::ops::convolution(state, &rgba, cols, rows, &filter).await
+                        }
+                        Op::Rotation { theta } => {
+                            rust_wgpu::ops::rotation(state, &rgba, cols, rows, theta).await
+                        }
+                    }
+                })?;
+                Some(
+                    out.iter()
+                        .step_by(rust_wgpu::ops::CHANNELS)
+                        .copied()
+                        .collect(),
+                )
+            }
+            Backend::OpenCl => {
+                let queue = self.opencl.as_ref()?;
+                let out = match op {
+                    Op::Convolution { radius, sigma } => {
+                        let filter = lab_opencl::utils::generate_gaussian_kernel(radius, sigma);
+                        lab_opencl::ops::convolution(queue, &image.data, cols, rows, &filter)
+                    }
+                    Op::Rotation { theta } => {
+                        lab_opencl::ops::rotation(queue, &image.data, cols, rows, theta)
+                    }
+                };
+                out.map_err(|err| log::error!("OpenCL {op:?} failed - {err}"))
+                    .ok()
+            }
+        }
+    }
+
+    /// Histogram of `image` in [`HIST_BINS`] bins over [0, 1], `None` when the backend is
+    /// unavailable or fails.
+    pub fn histogram(&self, backend: Backend, image: &GrayImage) -> Option<Vec<u32>> {
+        match backend {
+            Backend::Cpu => Some(rust_wgpu::reference::histogram(
+                &to_rgba(&image.data),
+                rust_wgpu::ops::CHANNELS,
+                HIST_BINS,
+            )),
+            Backend::Wgpu => {
+                let state = self.wgpu.as_ref()?;
+                wgpu_histogram(state, image)
+                    .map_err(|err| log::error!("wgpu histogram failed - {err}"))
+                    .ok()
+            }
+            Backend::OpenCl => {
+                let queue = self.opencl.as_ref()?;
+                let counts = opencl_histogram(queue, image)
+                    .map_err(|err| log::error!("OpenCL histogram failed - {err}"))
+                    .ok()?;
+                Some(counts.into_iter().map(|count| count as u32).collect())
+            }
+        }
+    }
+
+    /// `a + b`, `None` when the backend is unavailable or fails.
+    pub fn vector_add(&self, backend: Backend, a: &[f32], b: &[f32]) -> Option<Vec<f32>> {
+        match backend {
+            Backend::Cpu => Some(rust_wgpu::reference::vector_add(a, b)),
+            Backend::Wgpu => {
+                let state = self.wgpu.as_ref()?;
+                pollster::block_on(rust_wgpu::ops::vector_add(state, a, b))
+            }
+            Backend::OpenCl => {
+                let queue = self.opencl.as_ref()?;
+                lab_opencl::ops::vector_add(queue, a, b)
+                    .map_err(|err| log::error!("OpenCL vector add failed - {err}"))
+                    .ok()
+            }
+        }
+    }
+
+    /// Run `job` on `backend` and save its output if it has one; a job that names another
+    /// backend is an error, see [`Backend::check_job`].
+    ///
+    /// The wgpu backend saves an RGBA image and the others a gray one; the result is gray.
+    pub fn run_job(&self, backend: Backend, job: &Job) -> Result<JobResult, JobError> {
+        backend.check_job(job)?;
+        let unavailable = || JobError::Invalid {
+            op: None,
+            message: format!("the {} backend is unavailable", backend.name()),
+        };
+        match backend {
+            Backend::Cpu => cpu_job(job),
+            Backend::Wgpu => {
+                let state = self.wgpu.as_ref().ok_or_else(unavailable)?;
+                let output = pollster::block_on(rust_wgpu::job::run(job, state))?;
+                let data = output
+                    .image
+                    .iter()
+                    .step_by(rust_wgpu::ops::CHANNELS)
+                    .copied()
+                    .collect();
+                Ok(JobResult {
+                    image: GrayImage {
+                        cols: output.cols,
+                        rows: output.rows,
+                        data,
+                    },
+                    histograms: output.histograms,
+                })
+            }
+            Backend::OpenCl => {
+                let queue = self.opencl.as_ref().ok_or_else(unavailable)?;
+                let output = lab_opencl::job::run(job, queue)?;
+                let histograms = output
+                    .histograms
+                    .into_iter()
+                    .map(|counts| counts.into_iter().map(|count| count as u32).collect())
+                    .collect();
+                Ok(JobResult {
+                    image: GrayImage {
+                        cols: output.cols,
+                        rows: output.rows,
+                        data: output.image,
+                    },
+                    histograms,
+                })
+            }
+        }
+    }
+}
+
+/// Run the ops of `job` with the reference implementations on a gray image.
+fn cpu_job(job: &Job) -> Result<JobResult, JobError> {
+    job.validate()?;
+    let input = job.load_input()?.to_luma32f();
+    let (cols, rows) = (input.width() as usize, input.height() as usize);
+    let mut data = input.into_raw();
+    let mut histograms = Vec::new();
+    for op in &job.ops {
+        match *op {
+            job::Op::GaussianBlur { radius, sigma } => {
+                let filter = rust_wgpu::generate_gaussian_kernel(radius as i32, sigma);
+                data = rust_wgpu::reference::convolution(&data, cols, rows, 1, &filter);
+            }
+            job::Op::Rotate { degrees, interp } => match interp {
+                Interp::Bilinear => {
+                    data =
+                        rust_wgpu::reference::rotation(&data, cols, rows, 1, degrees.to_radians());
+                }
+                Interp::Bicubic => {
+                    let theta = degrees.to_radians();
+                    data = rust_wgpu::reference::bicubic_rotation(&data, cols, rows, 1, theta);
+                }
+            },
+            job::Op::Histogram { bins } => {
+                let counts = rust_wgpu::reference::histogram(
+                    &to_rgba(&data),
+                    rust_wgpu::ops::CHANNELS,
+                    HIST_BINS,
+                );
+                histograms.push(rebin(&counts, bins));
+            }
+        }
+    }
+
+    let image = GrayImage {
+        cols: cols as u32,
+        rows: rows as u32,
+        data,
+    };
+    if let Some(path) = &job.output {
+        image.save(path)?;
+    }
+    Ok(JobResult { image, histograms })
+}
+
+fn wgpu_histogram(state: &WgpuState, image: &GrayImage) -> rust_wgpu::error::Result<Vec<u32>> {
+    let mut pipeline = rust_wgpu::pipeline::ImagePipeline::new(state, image.cols, image.rows);
+    let histogram = pipeline.histogram()?;
+    let outputs = pollster::block_on(pipeline.run(&to_rgba(&image.data)))?;
+    Ok(outputs.histogram(histogram).to_vec())
+}
+
+fn opencl_histogram(queue: &Queue, image: &GrayImage) -> ocl::Result<Vec<i32>> {
+    let mut pipeline = lab_opencl::pipeline::ImagePipeline::new(queue, image.cols, image.rows);
+    let histogram = pipeline.histogram()?;
+    let outputs = pipeline.run(&image.data)?;
+    Ok(outputs.histogram(histogram).to_vec())
+}
+
+/// Replicate a gray image into the RGB channels of an opaque RGBA image.
+fn to_rgba(gray: &[f32]) -> Vec<f32> {
+    gray.iter().flat_map(|&v| [v, v, v, 1.0]).collect()
+}
+
+/// Difference between the outputs of two backends.
+#[derive(Debug, Clone)]
+pub struct PairDiff {
+    pub a: Backend,
+    pub b: Backend,
+    /// Peak signal-to-noise ratio in dB for a signal range of 1, infinite for equal outputs.
+    pub psnr: f64,
+    pub max_abs: f32,
+    /// Absolute difference per pixel.
+    pub diff: Vec<f32>,
+}
+
+impl PairDiff {
+    /// Compare the pixels of `a` and `b` at least `border` pixels away from the image edges,
+    /// where the backends handle borders differently. The heatmap covers the whole image.
+    pub fn new(
+        (a, out_a): (Backend, &[f32]),
+        (b, out_b): (Backend, &[f32]),
+        cols: u32,
+        border: u32,
+    ) -> Self {
+        assert_eq!(out_a.len(), out_b.len(), "output size mismatch");
+        let cols = cols as usize;
+        let rows = out_a.len() / cols.max(1);
+        let border = border as usize;
+        let diff: Vec<f32> = out_a
+            .iter()
+            .zip(out_b)
+            .map(|(a, b)| (a - b).abs())
+            .collect();
+
+        let inner = |i: &usize| {
+            let (x, y) = (i % cols, i / cols);
+            x >= border && x + border < cols && y >= border && y + border < rows
+        };
+        let (mut max_abs, mut sum_sq, mut count) = (0f32, 0f64, 0usize);
+        for (_, &d) in diff.iter().enumerate().filter(|(i, _)| inner(i)) {
+            max_abs = max_abs.max(d);
+            sum_sq += d as f64 * d as f64;
+            count += 1;
+        }
+        let mse = sum_sq / count.max(1) as f64;
+        Self {
+            a,
+            b,
+            psnr: -10.0 * mse.log10(),
+            max_abs,
+            diff,
+        }
+    }
+
+    /// Whether the difference stays within the thresholds.
+    pub fn passes(&self, thresholds: &Thresholds) -> bool {
+        self.psnr >= thresholds.min_psnr && self.max_abs <= thresholds.max_abs
+    }
+
+    /// Save the difference as a black-red-yellow-white heatmap, scaled so that `scale` (or
+    /// more) is white. The scale is usually the largest threshold worth looking at.
+    pub fn save_heatmap(
+        &self,
+        path: impl AsRef<Path>,
+        cols: u32,
+        scale: f32,
+    ) -> image::ImageResult<()> {
+        let rows = self.diff.len() as u32 / cols;
+        let pixels: Vec<u8> = self.diff.iter().flat_map(|&d| heat(d / scale)).collect();
+        image::save_buffer(path, &pixels, cols, rows, image::ColorType::Rgb8)
+    }
+}
+
+impl fmt::Display for PairDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>6} vs {:<6}  psnr {:>8.2} dB  max abs {:.6}",
+            self.a.name(),
+            self.b.name(),
+            self.psnr,
+            self.max_abs
+        )
+    }
+}
+
+/// "hot" color map of `t` in [0, 1].
+fn heat(t: f32) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0) * 3.0;
+    [t, t - 1.0, t - 2.0].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8)
+}
+
+/// Limits a pair of backends must stay within.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    pub min_psnr: f64,
+    pub max_abs: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            min_psnr: 30.0,
+            max_abs: 0.25,
+        }
+    }
+}
+
+/// The outputs of every available backend and their pairwise differences.
+pub struct Report {
+    pub outputs: Vec<(Backend, Vec<f32>)>,
+    pub pairs: Vec<PairDiff>,
+}
+
+/// Run `op` on every available backend and compare all pairs of outputs.
+pub fn check(backends: &Backends, op: Op, image: &GrayImage, border: u32) -> Report {
+    let outputs: Vec<(Backend, Vec<f32>)> = backends
+        .available()
+        .into_iter()
+        .filter_map(|backend| Some((backend, backends.run(backend, op, image)?)))
+        .collect();
+
+    let mut pairs = Vec::new();
+    for (i, (a, out_a)) in outputs.iter().enumerate() {
+        for (b, out_b) in &outputs[i + 1..] {
+            pairs.push(PairDiff::new((*a, out_a), (*b, out_b), image.cols, border));
+        }
+    }
+    Report { outputs, pairs }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{heat, Backend, Job, PairDiff};
+
+    #[test]
+    pub fn test_pair_diff() {
+        let a = [0.0, 0.5, 1.0, 0.25];
+        let b = [0.0, 0.5, 0.5, 0.25];
+        let diff = PairDiff::new((Backend::Cpu, &a), (Backend::Wgpu, &b), 2, 0);
+        assert_eq!(diff.max_abs, 0.5);
+        // mse = 0.25 / 4
+        assert!((diff.psnr - 12.0412).abs() < 1e-3);
+
+        let equal = PairDiff::new((Backend::Cpu, &a), (Backend::Cpu, &a), 2, 0);
+        assert!(equal.psnr.is_infinite());
+
+        // a one pixel border leaves no pixels of a 2x2 image to compare
+        let inner = PairDiff::new((Backend::Cpu, &a), (Backend::Wgpu, &b), 2, 1);
+        assert_eq!(inner.max_abs, 0.0);
+
+        assert_eq!(heat(0.0), [0, 0, 0]);
+        assert_eq!(heat(1.0), [255, 255, 255]);
+    }
+
+    #[test]
+    pub fn test_check_job() {
+        let ops = r#""ops": [{"op": "histogram", "bins": 16}]"#;
+        let job = Job::from_json(&format!(
+            r#"{{"input": "a.png", "backend": "wgpu", {ops}}}"#
+        ))
+        .unwrap();
+        assert!(Backend::Wgpu.check_job(&job).is_ok());
+        assert!(Backend::OpenCl.check_job(&job).is_err());
+        assert_eq!(
+            Backend::Cpu.check_job(&job).unwrap_err().to_string(),
+            "the job is for the wgpu backend, not cpu"
+        );
+
+        let job = Job::from_json(&format!(r#"{{"input": "a.png", {ops}}}"#)).unwrap();
+        assert!([Backend::Cpu, Backend::Wgpu, Backend::OpenCl]
+            .iter()
+            .all(|backend| backend.check_job(&job).is_ok()));
+    }
+}
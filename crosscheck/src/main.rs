@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use crosscheck::{check, Backends, GrayImage, Op, Thresholds};
+
+const USAGE: &str = "usage: crosscheck <convolution|rotation> [--image PATH] [--radius N] \
+[--sigma S] [--degrees D] [--border PX] [--min-psnr DB] [--max-abs V] [--heatmaps DIR]";
+const IMAGE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../rust_wgpu/data/cat.png");
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let name = args.next().ok_or(USAGE)?;
+    let mut op = Op::from_name(&name).ok_or(USAGE)?;
+    let mut image_path = PathBuf::from(IMAGE_PATH);
+    let mut thresholds = Thresholds::default();
+    let mut border = 0;
+    let mut heatmaps = None;
+
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(USAGE)?;
+        match (flag.as_str(), &mut op) {
+            ("--image", _) => image_path = value.into(),
+            ("--radius", Op::Convolution { radius, .. }) => *radius = value.parse()?,
+            ("--sigma", Op::Convolution { sigma, .. }) => *sigma = value.parse()?,
+            ("--degrees", Op::Rotation { theta }) => *theta = value.parse::<f32>()?.to_radians(),
+            ("--border", _) => border = value.parse()?,
+            ("--min-psnr", _) => thresholds.min_psnr = value.parse()?,
+            ("--max-abs", _) => thresholds.max_abs = value.parse()?,
+            ("--heatmaps", _) => heatmaps = Some(PathBuf::from(value)),
+            _ => return Err(format!("unexpected option {flag} for {name}\n{USAGE}").into()),
+        }
+    }
+
+    let image = GrayImage::open(&image_path)?;
+    let backends = Backends::init();
+    println!(
+        "{op:?} on {} ({}x{}), border {border}",
+        image_path.display(),
+        image.cols,
+        image.rows
+    );
+
+    let report = check(&backends, op, &image, border);
+    let names: Vec<&str> = report.outputs.iter().map(|(b, _)| b.name()).collect();
+    println!("backends: {}", names.join(", "));
+    if report.pairs.is_empty() {
+        return Err("fewer than two backends ran the op, nothing to compare".into());
+    }
+
+    let mut failed = 0;
+    for pair in &report.pairs {
+        let passes = pair.passes(&thresholds);
+        println!("{pair}  {}", if passes { "ok" } else { "FAIL" });
+        failed += !passes as usize;
+
+        if let Some(dir) = &heatmaps {
+            std::fs::create_dir_all(dir)?;
+            let path = dir.join(format!("{name}_{}_{}.png", pair.a.name(), pair.b.name()));
+            pair.save_heatmap(&path, image.cols, thresholds.max_abs)?;
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!(
+            "{failed} pair(s) exceed min psnr {} dB / max abs {}",
+            thresholds.min_psnr, thresholds.max_abs
+        )
+        .into());
+    }
+    Ok(())
+}
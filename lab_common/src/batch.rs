@@ -0,0 +1,125 @@
+//! The parts of a batch that do not depend on the backend: its options and report, the
+//! images it reads and where their results go.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Threads decoding and encoding the images.
+    pub threads: usize,
+    /// Images on the device at once; 2 or 3 keep it busy.
+    pub in_flight: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        let threads = std::thread::available_parallelism().map_or(4, |threads| threads.get());
+        Self {
+            threads: threads.min(8),
+            in_flight: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchReport {
+    pub processed: usize,
+    /// The images that could not be read or saved, by relative path, with the error.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// The image files under `dir`, relative to it and sorted; `skip` (e.g. the output
+/// directory) is not entered, however either path is spelled.
+pub fn images(dir: &Path, skip: &Path) -> Vec<PathBuf> {
+    fn visit(root: &Path, dir: &Path, skip: Option<&Path>, images: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                // `./out` and `out` are the same directory
+                if skip.is_none() || path.canonicalize().ok().as_deref() != skip {
+                    visit(root, &path, skip, images);
+                }
+            } else if image::ImageFormat::from_path(&path).is_ok() {
+                images.push(path.strip_prefix(root).unwrap().to_path_buf());
+            }
+        }
+    }
+
+    // a `skip` that does not exist yet is not under `dir` either
+    let skip = skip.canonicalize().ok();
+    let mut images = Vec::new();
+    visit(dir, dir, skip.as_deref(), &mut images);
+    images.sort();
+    images
+}
+
+/// Where the result of the image at `relative` goes: the same path with `.png` appended, so
+/// `a.jpg` and `a.png` do not overwrite each other.
+pub fn output_path(output_dir: &Path, relative: &Path) -> PathBuf {
+    let mut path = output_dir.join(relative).into_os_string();
+    path.push(".png");
+    path.into()
+}
+
+/// Save the result of the image at `relative` with `save_image` at its
+/// [`output_path`], creating the directories on the way, and its `histograms`, if any, as
+/// JSON next to it, e.g. `sub/a.jpg.histogram.json` for `sub/a.jpg`.
+pub fn save<C: Serialize>(
+    output_dir: &Path,
+    relative: &Path,
+    histograms: &[Vec<C>],
+    save_image: impl FnOnce(&Path) -> Result<(), String>,
+) -> Result<(), String> {
+    let path = output_path(output_dir, relative);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    save_image(&path)?;
+    if !histograms.is_empty() {
+        let json = serde_json::to_string(histograms).map_err(|err| err.to_string())?;
+        std::fs::write(path.with_extension("histogram.json"), json)
+            .map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{images, output_path};
+
+    #[test]
+    pub fn test_images() {
+        let dir = std::env::temp_dir().join(format!("batch-{}", std::process::id()));
+        for file in ["b.png", "a.jpg", "notes.txt", "sub/c.png", "out/d.png"] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let found = images(&dir, &dir.join("out"));
+        let found_dotted = images(&dir, &dir.join("sub/../out"));
+        let found_missing = images(&dir, &dir.join("missing"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(found, ["a.jpg", "b.png", "sub/c.png"].map(PathBuf::from));
+        assert_eq!(found_dotted, found);
+        assert_eq!(
+            found_missing,
+            ["a.jpg", "b.png", "out/d.png", "sub/c.png"].map(PathBuf::from)
+        );
+        assert_eq!(
+            output_path(Path::new("out"), Path::new("sub/c.jpg")),
+            Path::new("out/sub/c.jpg.png")
+        );
+        assert_eq!(
+            output_path(Path::new("out"), Path::new("sub/c.png")),
+            Path::new("out/sub/c.png.png")
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Timing and result export shared by the benchmarks.
+//!
+//! Every benchmark times one phase (upload, compute or download) with `iter_custom`, and the
+//! mean times of the measured samples are also written as CSV and JSON to `BENCH_RESULTS`
+//! (default `target/bench-results`), so runs on different devices or commits can be compared.
+
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use criterion::measurement::WallTime;
+use criterion::{BenchmarkGroup, BenchmarkId, Criterion, Throughput};
+use serde::Serialize;
+
+/// Samples criterion measures per benchmark, after the warm-up.
+const SAMPLE_SIZE: usize = 10;
+
+/// Short runs: a device dispatch is already averaged over many iterations.
+pub fn config() -> Criterion {
+    Criterion::default()
+        .sample_size(SAMPLE_SIZE)
+        .warm_up_time(Duration::from_millis(500))
+        .measurement_time(Duration::from_secs(2))
+}
+
+/// The samples of one phase at one size.
+struct Measurement {
+    op: String,
+    phase: String,
+    size: String,
+    throughput: Throughput,
+    iters: u64,
+    elapsed: Duration,
+}
+
+impl Measurement {
+    fn mean_ns(&self) -> f64 {
+        self.elapsed.as_nanos() as f64 / self.iters.max(1) as f64
+    }
+
+    /// GB/s for byte throughputs, Mpixels/s for element throughputs.
+    fn rate(&self) -> (Option<f64>, Option<f64>) {
+        // per nanosecond: bytes/ns = GB/s, elements/ns * 1e3 = M/s
+        match self.throughput {
+            Throughput::Bytes(bytes) => (Some(bytes as f64 / self.mean_ns()), None),
+            Throughput::Elements(n) => (None, Some(n as f64 / self.mean_ns() * 1e3)),
+            _ => (None, None),
+        }
+    }
+}
+
+static RESULTS: Mutex<Vec<Measurement>> = Mutex::new(Vec::new());
+
+/// One line of the CSV and one object of the JSON results.
+#[derive(Serialize)]
+struct Row<'a> {
+    device: &'a str,
+    op: &'a str,
+    phase: &'a str,
+    size: &'a str,
+    mean_ns: u64,
+    gb_per_s: Option<f64>,
+    mpixels_per_s: Option<f64>,
+}
+
+impl<'a> Row<'a> {
+    fn new(device: &'a str, measurement: &'a Measurement) -> Self {
+        let round = |value: f64| (value * 1e3).round() / 1e3;
+        let (gbps, mpixels) = measurement.rate();
+        Self {
+            device,
+            op: &measurement.op,
+            phase: &measurement.phase,
+            size: &measurement.size,
+            mean_ns: measurement.mean_ns().round() as u64,
+            gb_per_s: gbps.map(round),
+            mpixels_per_s: mpixels.map(round),
+        }
+    }
+}
+
+/// A criterion group timing the phases of one op.
+pub struct OpGroup<'a> {
+    op: String,
+    group: BenchmarkGroup<'a, WallTime>,
+}
+
+impl<'a> OpGroup<'a> {
+    pub fn new(c: &'a mut Criterion, op: &str) -> Self {
+        Self {
+            op: op.to_string(),
+            group: c.benchmark_group(op),
+        }
+    }
+
+    /// Benchmark `phase` at `size`; `time(iters)` runs the phase `iters` times and returns
+    /// the elapsed time. Only the samples after the warm-up count towards the written mean.
+    pub fn phase(
+        &mut self,
+        phase: &str,
+        size: &str,
+        throughput: Throughput,
+        mut time: impl FnMut(u64) -> Duration,
+    ) {
+        let mut samples: Vec<(u64, Duration)> = Vec::new();
+        self.group.throughput(throughput.clone());
+        self.group
+            .bench_function(BenchmarkId::new(phase, size), |b| {
+                b.iter_custom(|iters| {
+                    let elapsed = time(iters);
+                    samples.push((iters, elapsed));
+                    elapsed
+                })
+            });
+
+        // criterion warms up with the same routine, then runs one call per sample
+        let measured = &samples[samples.len().saturating_sub(SAMPLE_SIZE)..];
+        for &(iters, elapsed) in measured {
+            record(&self.op, phase, size, &throughput, iters, elapsed);
+        }
+    }
+
+    pub fn finish(self) {
+        self.group.finish();
+    }
+}
+
+fn record(
+    op: &str,
+    phase: &str,
+    size: &str,
+    throughput: &Throughput,
+    iters: u64,
+    elapsed: Duration,
+) {
+    let mut results = RESULTS.lock().unwrap();
+    match results
+        .iter_mut()
+        .find(|row| row.op == op && row.phase == phase && row.size == size)
+    {
+        Some(row) => {
+            row.iters += iters;
+            row.elapsed += elapsed;
+        }
+        None => results.push(Measurement {
+            op: op.to_string(),
+            phase: phase.to_string(),
+            size: size.to_string(),
+            throughput: throughput.clone(),
+            iters,
+            elapsed,
+        }),
+    }
+}
+
+/// Write the results of this run as `<name>.csv` and `<name>.json`.
+pub fn write_results(name: &str, device: &str) -> std::io::Result<()> {
+    let results = RESULTS.lock().unwrap();
+    if results.is_empty() {
+        return Ok(());
+    }
+    let dir = std::env::var("BENCH_RESULTS").unwrap_or_else(|_| "target/bench-results".into());
+    std::fs::create_dir_all(&dir)?;
+
+    let rows: Vec<_> = results
+        .iter()
+        .map(|measurement| Row::new(device, measurement))
+        .collect();
+    let number = |value: Option<f64>| value.map_or(String::new(), |v| v.to_string());
+    let mut csv = String::from("device,op,phase,size,mean_ns,gb_per_s,mpixels_per_s\n");
+    for row in &rows {
+        writeln!(
+            csv,
+            "\"{}\",{},{},{},{},{},{}",
+            row.device.replace('"', "\"\""),
+            row.op,
+            row.phase,
+            row.size,
+            row.mean_ns,
+            number(row.gb_per_s),
+            number(row.mpixels_per_s)
+        )
+        .unwrap();
+    }
+    let json = serde_json::to_string_pretty(&rows).map_err(std::io::Error::other)? + "\n";
+
+    std::fs::write(format!("{dir}/{name}.csv"), csv)?;
+    std::fs::write(format!("{dir}/{name}.json"), json)?;
+    println!("results written to {dir}/{name}.csv and {dir}/{name}.json");
+    Ok(())
+}
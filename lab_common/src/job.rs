@@ -0,0 +1,351 @@
+//! Image processing jobs described in a TOML or JSON file.
+//!
+//! A job names the input image and the ops to run on it in order, so the parameters can be
+//! changed without recompiling:
+//!
+//! ```toml
+//! input = "data/cat.png"
+//! backend = "wgpu"          # optional, the backend the job is written for
+//! size = [512, 512]         # optional, resize the input to fit first
+//! output = "data/cat_job_out.png"
+//!
+//! [[ops]]
+//! op = "gaussian_blur"
+//! radius = 7
+//! sigma = 2.0
+//!
+//! [[ops]]
+//! op = "rotate"
+//! degrees = 30
+//! interp = "bicubic"        # optional, bilinear by default
+//!
+//! [[ops]]
+//! op = "histogram"
+//! bins = 64
+//! ```
+//!
+//! In JSON the ops are objects of the same fields, e.g.
+//! `{"op": "rotate", "degrees": 30}`. The paths are relative to the working directory. The
+//! backends run the ops as one image pipeline, see their `job` modules.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// The bins the backends count a histogram into; the `bins` of a job must divide it.
+pub const HIST_BINS: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub input: PathBuf,
+    #[serde(default)]
+    pub backend: Option<Backend>,
+    /// Resize the input to fit in `[cols, rows]`, keeping its aspect ratio.
+    #[serde(default)]
+    pub size: Option<[u32; 2]>,
+    /// Where to save the image after the last op.
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    pub ops: Vec<Op>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    Opencl,
+    Wgpu,
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Opencl => "opencl",
+            Backend::Wgpu => "wgpu",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Op {
+    /// Convolve with a `(2 * radius + 1)^2` gaussian filter.
+    GaussianBlur { radius: u32, sigma: f32 },
+    /// Rotate around the center of the image.
+    Rotate {
+        degrees: f32,
+        #[serde(default)]
+        interp: Interp,
+    },
+    /// Count the pixels into `bins` bins over `[0, 1]`; the image is unchanged.
+    Histogram {
+        #[serde(default = "default_bins")]
+        bins: usize,
+    },
+}
+
+fn default_bins() -> usize {
+    HIST_BINS
+}
+
+/// Interpolation of the rotation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interp {
+    #[default]
+    Bilinear,
+    /// A Catmull-Rom spline through the 4x4 pixels around the source location.
+    Bicubic,
+}
+
+#[derive(Debug)]
+pub enum JobError {
+    /// A job file that can not be read.
+    Io(PathBuf, std::io::Error),
+    /// A job that does not parse; the message has the location.
+    Parse(String),
+    /// A job that parses but can not run, `op` is the index of the offending op.
+    Invalid {
+        op: Option<usize>,
+        message: String,
+    },
+    Image(image::ImageError),
+    /// The backend failed, e.g. to build a kernel.
+    Device(Box<dyn std::error::Error + Send>),
+}
+
+impl JobError {
+    pub fn device(err: impl std::error::Error + Send + 'static) -> Self {
+        JobError::Device(Box::new(err))
+    }
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            JobError::Parse(message) => write!(f, "{message}"),
+            JobError::Invalid {
+                op: Some(index),
+                message,
+            } => write!(f, "ops[{index}]: {message}"),
+            JobError::Invalid { op: None, message } => write!(f, "{message}"),
+            JobError::Image(err) => write!(f, "{err}"),
+            JobError::Device(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+impl From<image::ImageError> for JobError {
+    fn from(err: image::ImageError) -> Self {
+        JobError::Image(err)
+    }
+}
+
+impl Job {
+    /// Read a job from a `.toml` or `.json` file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, JobError> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read_to_string(path).map_err(|err| JobError::Io(path.to_path_buf(), err))?;
+        let job = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => {
+                return Err(JobError::Parse(format!(
+                    "{}: expected a .toml or .json job file",
+                    path.display()
+                )))
+            }
+        };
+        job.map_err(|err| match err {
+            JobError::Parse(message) => JobError::Parse(format!("{}: {message}", path.display())),
+            err => err,
+        })
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, JobError> {
+        let job: Self = toml::from_str(text).map_err(|err| JobError::Parse(err.to_string()))?;
+        job.validate()?;
+        Ok(job)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, JobError> {
+        let job: Self =
+            serde_json::from_str(text).map_err(|err| JobError::Parse(err.to_string()))?;
+        job.validate()?;
+        Ok(job)
+    }
+
+    /// Check the parameters the file format can't, e.g. a positive sigma.
+    pub fn validate(&self) -> Result<(), JobError> {
+        let invalid = |op, message: String| Err(JobError::Invalid { op, message });
+        if self.size.is_some_and(|[cols, rows]| cols == 0 || rows == 0) {
+            return invalid(None, "the size must not be zero".to_string());
+        }
+        if self.ops.is_empty() {
+            return invalid(None, "the job has no ops".to_string());
+        }
+
+        for (index, op) in self.ops.iter().enumerate() {
+            match *op {
+                Op::GaussianBlur { sigma, .. } if !(sigma.is_finite() && sigma > 0.0) => {
+                    return invalid(Some(index), format!("sigma must be positive, got {sigma}"))
+                }
+                Op::Rotate { degrees, .. } if !degrees.is_finite() => {
+                    return invalid(
+                        Some(index),
+                        format!("degrees must be finite, got {degrees}"),
+                    )
+                }
+                Op::Histogram { bins } if bins == 0 || !HIST_BINS.is_multiple_of(bins) => {
+                    return invalid(
+                        Some(index),
+                        format!("bins must divide {HIST_BINS}, got {bins}"),
+                    )
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the job can run on `backend`, i.e. it names no other backend.
+    pub fn check_backend(&self, backend: Backend) -> Result<(), JobError> {
+        match self.backend {
+            Some(other) if other != backend => Err(JobError::Invalid {
+                op: None,
+                message: format!(
+                    "the job is for the {} backend, not {}",
+                    other.name(),
+                    backend.name()
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// The input image, resized to fit in the `size` of the job if it has one.
+    pub fn load_input(&self) -> Result<image::DynamicImage, JobError> {
+        let img = image::open(&self.input)?;
+        Ok(match self.size {
+            Some([cols, rows]) => img.resize(cols, rows, image::imageops::FilterType::Triangle),
+            None => img,
+        })
+    }
+}
+
+/// Sum adjacent bins of `counts` down to `bins` bins, which must divide its length.
+pub fn rebin<N: Copy + std::iter::Sum>(counts: &[N], bins: usize) -> Vec<N> {
+    counts
+        .chunks(counts.len() / bins)
+        .map(|chunk| chunk.iter().copied().sum())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rebin, Backend, Interp, Job, JobError, Op};
+
+    const TOML: &str = r#"
+input = "data/cat.png"
+backend = "wgpu"
+size = [512, 512]
+
+[[ops]]
+op = "gaussian_blur"
+radius = 7
+sigma = 2.0
+
+[[ops]]
+op = "rotate"
+degrees = 30
+interp = "bicubic"
+
+[[ops]]
+op = "histogram"
+bins = 64
+"#;
+
+    #[test]
+    pub fn test_parse() {
+        let job = Job::from_toml(TOML).unwrap();
+        assert_eq!(job.backend, Some(Backend::Wgpu));
+        assert_eq!(job.size, Some([512, 512]));
+        assert_eq!(
+            job.ops,
+            [
+                Op::GaussianBlur {
+                    radius: 7,
+                    sigma: 2.0
+                },
+                Op::Rotate {
+                    degrees: 30.0,
+                    interp: Interp::Bicubic
+                },
+                Op::Histogram { bins: 64 },
+            ]
+        );
+
+        let json = r#"{
+            "input": "data/cat.png", "backend": "wgpu", "size": [512, 512],
+            "ops": [
+                {"op": "gaussian_blur", "radius": 7, "sigma": 2.0},
+                {"op": "rotate", "degrees": 30, "interp": "bicubic"},
+                {"op": "histogram", "bins": 64}
+            ]
+        }"#;
+        assert_eq!(Job::from_json(json).unwrap(), job);
+    }
+
+    #[test]
+    pub fn test_invalid_jobs() {
+        let message = |text: &str| match Job::from_toml(text) {
+            Err(JobError::Parse(message)) => message,
+            Err(err) => err.to_string(),
+            Ok(job) => panic!("{job:?} is valid"),
+        };
+
+        let lanczos = TOML.replace("\"bicubic\"", "\"lanczos\"");
+        assert!(message(&lanczos)
+            .contains("unknown variant `lanczos`, expected `bilinear` or `bicubic`"));
+        let bilinear = TOML.replace("interp = \"bicubic\"\n", "");
+        assert!(matches!(
+            Job::from_toml(&bilinear).unwrap().ops[1],
+            Op::Rotate {
+                interp: Interp::Bilinear,
+                ..
+            }
+        ));
+        let typo = TOML.replace("sigma = 2.0", "sgima = 2.0");
+        assert!(message(&typo).contains("unknown field `sgima`"));
+        let sharpen = TOML.replace("\"rotate\"", "\"sharpen\"");
+        assert!(message(&sharpen).contains("unknown variant `sharpen`"));
+
+        assert_eq!(
+            message(&TOML.replace("sigma = 2.0", "sigma = 0.0")),
+            "ops[0]: sigma must be positive, got 0"
+        );
+        assert_eq!(
+            message(&TOML.replace("bins = 64", "bins = 100")),
+            "ops[2]: bins must divide 256, got 100"
+        );
+
+        let job = Job::from_toml(TOML).unwrap();
+        assert!(job.check_backend(Backend::Wgpu).is_ok());
+        assert_eq!(
+            job.check_backend(Backend::Opencl).unwrap_err().to_string(),
+            "the job is for the wgpu backend, not opencl"
+        );
+    }
+
+    #[test]
+    pub fn test_rebin() {
+        let counts: Vec<u32> = (0..8).collect();
+        assert_eq!(rebin(&counts, 2), [6, 22]);
+        assert_eq!(rebin(&counts, 8), counts);
+    }
+}
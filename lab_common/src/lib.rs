@@ -0,0 +1,12 @@
+//! The parts of `rust_opencl` and `rust_wgpu` that do not depend on the backend: the job
+//! schema, the batch inputs and outputs, the pipeline cache of the batches, the tune cache,
+//! the trace writer, the source watcher and the benchmark harness.
+
+pub mod batch;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod job;
+pub mod lru;
+pub mod trace;
+pub mod tune;
+pub mod watch;
@@ -0,0 +1,92 @@
+//! A small least-recently-used cache, e.g. of the pipelines of a batch by image size.
+
+use std::collections::VecDeque;
+
+/// Up to `capacity` values by key; inserting into a full cache drops the least recently
+/// used one. The lookups are linear, so the capacity should be a handful.
+pub struct Lru<K, V> {
+    capacity: usize,
+    /// The least recently used first.
+    entries: VecDeque<(K, V)>,
+}
+
+impl<K: PartialEq, V> Lru<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "an LRU cache needs room for one value");
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.entries.iter().any(|(k, _)| k == key)
+    }
+
+    /// The value of `key`, created by `create` if there is none, as the most recently used.
+    pub fn get_or_try_insert_with<E>(
+        &mut self,
+        key: K,
+        create: impl FnOnce() -> Result<V, E>,
+    ) -> Result<&mut V, E> {
+        let entry = match self.entries.iter().position(|(k, _)| *k == key) {
+            Some(index) => self.entries.remove(index).unwrap(),
+            None => {
+                let value = create()?;
+                if self.entries.len() == self.capacity {
+                    self.entries.pop_front();
+                }
+                (key, value)
+            }
+        };
+        self.entries.push_back(entry);
+        Ok(&mut self.entries.back_mut().unwrap().1)
+    }
+
+    /// The value of `key` without marking it used.
+    pub fn peek_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.entries
+            .iter_mut()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lru;
+
+    #[test]
+    pub fn test_lru() {
+        let mut cache = Lru::new(2);
+        let insert = |cache: &mut Lru<u32, String>, key: u32| {
+            cache
+                .get_or_try_insert_with(key, || Ok::<_, ()>(key.to_string()))
+                .unwrap();
+        };
+        insert(&mut cache, 1);
+        insert(&mut cache, 2);
+        // using 1 leaves 2 the least recently used
+        insert(&mut cache, 1);
+        insert(&mut cache, 3);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains(&1) && cache.contains(&3) && !cache.contains(&2));
+
+        // peeking does not mark 1 used
+        assert_eq!(cache.peek_mut(&1).map(|value| value.as_str()), Some("1"));
+        insert(&mut cache, 4);
+        assert!(!cache.contains(&1));
+
+        // a failed insertion keeps the cache
+        assert_eq!(cache.get_or_try_insert_with(5, || Err("no")), Err("no"));
+        assert!(cache.contains(&3) && cache.contains(&4));
+    }
+}
@@ -0,0 +1,173 @@
+//! Writes the profiled commands of the backends as a Chrome Trace Event file.
+//!
+//! The format is a JSON array of events: one complete event (`"ph":"X"`) per command, with
+//! its start and duration in microseconds, and one `thread_name` metadata event per track.
+//! Each queue is a track of its own, so the commands of different queues line up side by
+//! side; see [`Trace`].
+
+use std::fmt::Write;
+use std::path::Path;
+
+/// Timeline of profiled commands in the Chrome Trace Event format.
+///
+/// The written file opens in `chrome://tracing` or <https://ui.perfetto.dev>. Every queue is a
+/// track of its commands, so transfers and kernels that could overlap show up as gaps. Queues
+/// with enqueue times get a second track for the time the commands spent queued.
+#[derive(Default)]
+pub struct Trace {
+    tracks: Vec<String>,
+    events: Vec<TraceEvent>,
+}
+
+struct TraceEvent {
+    name: String,
+    category: &'static str,
+    track: usize,
+    start_ns: u64,
+    end_ns: u64,
+}
+
+/// One profiled command in nanoseconds.
+pub struct Span<'a> {
+    pub label: &'a str,
+    /// E.g. `kernel` or `copy`.
+    pub category: &'static str,
+    /// When the command was enqueued, if the backend knows.
+    pub queued_ns: Option<u64>,
+    pub start_ns: u64,
+    pub end_ns: u64,
+}
+
+/// The profiled commands of one queue, e.g. a profile report.
+pub trait Timeline {
+    /// The commands in enqueue order.
+    fn spans(&self) -> Vec<Span<'_>>;
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the commands of one queue as its own track.
+    ///
+    /// Timestamps of one device share its clock, so its queues line up; host-timed reports
+    /// are relative to the creation of their profiler.
+    pub fn add_queue(&mut self, name: &str, timeline: &impl Timeline) -> &mut Self {
+        let spans = timeline.spans();
+        let track = self.tracks.len();
+        self.tracks.push(name.to_string());
+        if spans.iter().any(|span| span.queued_ns.is_some()) {
+            self.tracks.push(format!("{name} (queued)"));
+        }
+
+        for span in spans {
+            self.events.push(TraceEvent {
+                name: span.label.to_string(),
+                category: span.category,
+                track,
+                start_ns: span.start_ns,
+                end_ns: span.end_ns,
+            });
+            if let Some(queued_ns) = span.queued_ns.filter(|&ns| ns < span.start_ns) {
+                self.events.push(TraceEvent {
+                    name: span.label.to_string(),
+                    category: "queued",
+                    track: track + 1,
+                    start_ns: queued_ns,
+                    end_ns: span.start_ns,
+                });
+            }
+        }
+        self
+    }
+
+    /// The trace as a JSON array of events, with times relative to the first event.
+    pub fn to_json(&self) -> String {
+        let origin = self.events.iter().map(|e| e.start_ns).min().unwrap_or(0);
+        let us = |ns: u64| ns as f64 / 1e3;
+
+        let mut entries: Vec<String> = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(tid, name)| {
+                format!(
+                    r#"{{"name":"thread_name","ph":"M","pid":1,"tid":{tid},"args":{{"name":"{}"}}}}"#,
+                    escape(name)
+                )
+            })
+            .collect();
+        for event in &self.events {
+            entries.push(format!(
+                r#"{{"name":"{}","cat":"{}","ph":"X","pid":1,"tid":{},"ts":{:.3},"dur":{:.3}}}"#,
+                escape(&event.name),
+                event.category,
+                event.track,
+                us(event.start_ns - origin),
+                us(event.end_ns.saturating_sub(event.start_ns))
+            ));
+        }
+        format!("[\n{}\n]\n", entries.join(",\n"))
+    }
+
+    /// Write the trace, e.g. to `trace.json`.
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+}
+
+/// Escape a string for a JSON string literal.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                write!(escaped, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Span, Timeline, Trace};
+
+    struct Commands(Vec<(&'static str, Option<u64>, u64, u64)>);
+
+    impl Timeline for Commands {
+        fn spans(&self) -> Vec<Span<'_>> {
+            self.0
+                .iter()
+                .map(|&(label, queued_ns, start_ns, end_ns)| Span {
+                    label,
+                    category: "kernel",
+                    queued_ns,
+                    start_ns,
+                    end_ns,
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    pub fn test_to_json() {
+        let queued = Commands(vec![("say \"hi\"", Some(1_000), 3_000, 8_500)]);
+        let json = Trace::new().add_queue("queue 0", &queued).to_json();
+        assert!(json.contains(r#""tid":0,"args":{"name":"queue 0"}"#));
+        assert!(json.contains(
+            r#"{"name":"say \"hi\"","cat":"kernel","ph":"X","pid":1,"tid":0,"ts":2.000,"dur":5.500}"#
+        ));
+        assert!(json.contains(r#""cat":"queued","ph":"X","pid":1,"tid":1,"ts":0.000,"dur":2.000}"#));
+
+        // no queued track without enqueue times
+        let timed = Commands(vec![("upload", None, 1_000, 3_000)]);
+        let json = Trace::new().add_queue("queue 0", &timed).to_json();
+        assert!(!json.contains("queued"));
+        assert!(json.contains(r#""cat":"kernel","ph":"X","pid":1,"tid":0,"ts":0.000,"dur":2.000}"#));
+    }
+}
@@ -0,0 +1,92 @@
+//! The on-disk cache of tuned work-group sizes.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Environment variable overriding the cache file.
+pub const CACHE_ENV: &str = "TUNE_CACHE";
+const DEFAULT_CACHE_PATH: &str = "target/tune-cache.tsv";
+
+/// Tuned work-group sizes, one `device \t kernel \t shape \t x,y` line per entry.
+///
+/// `N` is the integer type of the sizes of the backend, e.g. `u32` for wgpu workgroups.
+pub struct TuneCache<N> {
+    path: PathBuf,
+    entries: BTreeMap<String, [N; 2]>,
+}
+
+impl<N: Copy + Display + FromStr> TuneCache<N> {
+    /// Load the cache at `path`; a missing file is an empty cache.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+
+        let entries = text
+            .lines()
+            .filter_map(|line| {
+                let (key, size) = line.rsplit_once('\t')?;
+                let (x, y) = size.split_once(',')?;
+                Some((key.to_string(), [x.parse().ok()?, y.parse().ok()?]))
+            })
+            .collect();
+        Ok(Self { path, entries })
+    }
+
+    /// Load the cache at `TUNE_CACHE`, else `target/tune-cache.tsv`.
+    pub fn open_default() -> io::Result<Self> {
+        Self::open(std::env::var(CACHE_ENV).unwrap_or_else(|_| DEFAULT_CACHE_PATH.into()))
+    }
+
+    pub fn get(&self, key: &str) -> Option<[N; 2]> {
+        self.entries.get(key).copied()
+    }
+
+    pub fn insert(&mut self, key: String, size: [N; 2]) {
+        self.entries.insert(key, size);
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text: String = self
+            .entries
+            .iter()
+            .map(|(key, [x, y])| format!("{key}\t{x},{y}\n"))
+            .collect();
+        std::fs::write(&self.path, text)
+    }
+}
+
+/// Cache key of a kernel running on an input of `shape` on the device `device`.
+pub fn cache_key(device: &str, kernel: &str, shape: &str) -> String {
+    [device, kernel, shape]
+        .map(|part| part.replace(['\t', '\n'], " "))
+        .join("\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_key, TuneCache};
+
+    #[test]
+    pub fn test_tune_cache() {
+        let path = std::env::temp_dir().join(format!("tune-cache-{}.tsv", std::process::id()));
+        let mut cache = TuneCache::<u32>::open(&path).unwrap();
+        let key = cache_key("gpu\t0", "rotation", "64x64");
+        cache.insert(key.clone(), [8, 8]);
+        cache.save().unwrap();
+        assert_eq!(
+            TuneCache::<u32>::open(&path).unwrap().get(&key),
+            Some([8, 8])
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+//! Hot reload of kernel and shader sources during development.
+//!
+//! The sources are embedded at compile time. Each backend keeps a [`SourceDir`] set by
+//! [`SourceDir::set`] or its environment variable, and reads its sources from that directory
+//! instead when there is one, so a program built again picks up the edits. [`SourceDir::run`]
+//! sets the directory and reruns a job each time a file in it changes, printing the errors
+//! instead of exiting.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// How often [`Watcher::wait`] looks at the files.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The directory the sources of one backend are read from, if any.
+pub struct SourceDir {
+    env: &'static str,
+    dir: Mutex<Option<PathBuf>>,
+}
+
+impl SourceDir {
+    /// A source directory set by the environment variable `env` until [`SourceDir::set`].
+    pub const fn new(env: &'static str) -> Self {
+        Self {
+            env,
+            dir: Mutex::new(None),
+        }
+    }
+
+    /// Read the sources from `dir` instead of the embedded ones; `None` goes back to the
+    /// environment variable, if set.
+    pub fn set(&self, dir: Option<PathBuf>) {
+        *self.dir.lock().unwrap() = dir;
+    }
+
+    /// The directory the sources are read from, if any.
+    pub fn get(&self) -> Option<PathBuf> {
+        let dir = self.dir.lock().unwrap().clone();
+        dir.or_else(|| std::env::var_os(self.env).map(PathBuf::from))
+    }
+
+    /// Read the sources from `dir` and run `job`, then again each time a file in `dir`
+    /// changes.
+    ///
+    /// A failing job prints its error, e.g. a build error with the source lines, and the
+    /// next change runs it again. Runs until the process is stopped.
+    pub fn run<E: fmt::Display>(
+        &self,
+        dir: impl Into<PathBuf>,
+        mut job: impl FnMut() -> Result<(), E>,
+    ) -> ! {
+        let dir = dir.into();
+        self.set(Some(dir.clone()));
+        let mut watcher = Watcher::new([&dir]);
+        loop {
+            if let Err(err) = job() {
+                eprintln!("{err}");
+            }
+            eprintln!("watching {} for changes", dir.display());
+            for path in watcher.wait() {
+                eprintln!("changed: {}", path.display());
+            }
+        }
+    }
+}
+
+/// Modification times of the files under a few directories, to find the changed ones.
+pub struct Watcher {
+    dirs: Vec<PathBuf>,
+    modified: BTreeMap<PathBuf, SystemTime>,
+}
+
+impl Watcher {
+    pub fn new<P: Into<PathBuf>>(dirs: impl IntoIterator<Item = P>) -> Self {
+        let dirs: Vec<PathBuf> = dirs.into_iter().map(Into::into).collect();
+        let modified = scan(&dirs);
+        Self { dirs, modified }
+    }
+
+    /// The files created, modified or removed since the last call, in path order.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let modified = scan(&self.dirs);
+        let mut changed: Vec<PathBuf> = modified
+            .iter()
+            .filter(|&(path, time)| self.modified.get(path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(
+            self.modified
+                .keys()
+                .filter(|path| !modified.contains_key(*path))
+                .cloned(),
+        );
+        changed.sort();
+        self.modified = modified;
+        changed
+    }
+
+    /// Block until a file changes.
+    ///
+    /// Editors often write a file in several steps, so the files are given a moment to
+    /// settle before they are returned.
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let mut changed = self.changed();
+            if changed.is_empty() {
+                continue;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+            changed.extend(self.changed());
+            changed.sort();
+            changed.dedup();
+            return changed;
+        }
+    }
+}
+
+/// The modification times of the files under `dirs`; unreadable entries are skipped.
+fn scan(dirs: &[PathBuf]) -> BTreeMap<PathBuf, SystemTime> {
+    fn visit(dir: &Path, modified: &mut BTreeMap<PathBuf, SystemTime>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                visit(&path, modified);
+            } else if let Ok(time) = metadata.modified() {
+                modified.insert(path, time);
+            }
+        }
+    }
+
+    let mut modified = BTreeMap::new();
+    for dir in dirs {
+        visit(dir, &mut modified);
+    }
+    modified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SourceDir, Watcher};
+
+    #[test]
+    pub fn test_watcher() {
+        let dir = std::env::temp_dir().join(format!("watch-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("include")).unwrap();
+        let kernel = dir.join("a.cl");
+        std::fs::write(&kernel, "__kernel void a() {}").unwrap();
+
+        let mut watcher = Watcher::new([&dir]);
+        assert!(watcher.changed().is_empty());
+
+        let include = dir.join("include").join("b.h");
+        std::fs::write(&include, "#define B 1").unwrap();
+        std::fs::remove_file(&kernel).unwrap();
+        assert_eq!(watcher.changed(), [kernel, include]);
+        assert!(watcher.changed().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_source_dir() {
+        let sources = SourceDir::new("LAB_COMMON_TEST_SOURCE_DIR");
+        assert_eq!(sources.get(), None);
+        sources.set(Some("kernels".into()));
+        assert_eq!(sources.get(), Some("kernels".into()));
+        sources.set(None);
+        assert_eq!(sources.get(), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! Upload, compute and download times of the kernels over vector lengths, image sizes and
+//! filter sizes.
+//!
+//! `cargo bench` runs everything, `cargo bench -- convolution` one kernel. The results also
+//! go to `target/bench-results/opencl.{csv,json}`.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, Criterion, Throughput};
+use lab_common::bench::{self, OpGroup};
+use lab_opencl::device;
+use lab_opencl::ops::{Histogram, ImageKernel, VectorAdd, HIST_BINS};
+use lab_opencl::utils::generate_gaussian_kernel;
+use ocl::Queue;
+
+const VECTOR_LENS: [usize; 3] = [1 << 16, 1 << 20, 1 << 22];
+const IMAGE_SIZES: [u32; 3] = [256, 512, 1024];
+const FILTER_RADII: [i32; 3] = [1, 2, 4];
+
+fn queue() -> Option<&'static Queue> {
+    static QUEUE: OnceLock<Option<Queue>> = OnceLock::new();
+    QUEUE
+        .get_or_init(|| match device::default_queue(None) {
+            Ok(queue) => {
+                println!("using OpenCL device: {}", device::describe(&queue));
+                Some(queue)
+            }
+            Err(err) => {
+                println!("skipped: no OpenCL device available ({err})");
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// Time `iters` runs of `f`, waiting for the queue to finish the enqueued work.
+fn time_queue(queue: &Queue, iters: u64, mut f: impl FnMut() -> ocl::Result<()>) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iters {
+        f().expect("failed to enqueue");
+    }
+    queue.finish().expect("failed to finish the queue");
+    start.elapsed()
+}
+
+fn vector_add(c: &mut Criterion) {
+    let Some(queue) = queue() else { return };
+    let mut group = OpGroup::new(c, "vector_add");
+
+    for len in VECTOR_LENS {
+        let op = VectorAdd::new(queue, len).expect("failed to build the kernel");
+        let a: Vec<f32> = (0..len).map(|v| v as f32).collect();
+        let bytes = (len * std::mem::size_of::<f32>()) as u64;
+        let size = len.to_string();
+
+        group.phase("upload", &size, Throughput::Bytes(2 * bytes), |iters| {
+            time_queue(queue, iters, || op.upload(&a, &a))
+        });
+        // two reads and one write per element
+        group.phase("compute", &size, Throughput::Bytes(3 * bytes), |iters| {
+            time_queue(queue, iters, || op.enqueue())
+        });
+        group.phase("download", &size, Throughput::Bytes(bytes), |iters| {
+            time_queue(queue, iters, || op.download().map(drop))
+        });
+    }
+    group.finish();
+}
+
+fn test_image(cols: u32, rows: u32) -> Vec<f32> {
+    (0..cols * rows)
+        .map(|i| ((i * 7919) % 256) as f32 / 255.0)
+        .collect()
+}
+
+/// Upload and download depend only on the image size, so they are timed on one kernel.
+fn image_transfers(group: &mut OpGroup<'_>, queue: &Queue, op: &ImageKernel) {
+    let (cols, rows) = (op.cols(), op.rows());
+    let image = test_image(cols, rows);
+    let bytes = image.len() as u64 * std::mem::size_of::<f32>() as u64;
+    let size = format!("{cols}x{rows}");
+
+    group.phase("upload", &size, Throughput::Bytes(bytes), |iters| {
+        time_queue(queue, iters, || op.upload(&image))
+    });
+    group.phase("download", &size, Throughput::Bytes(bytes), |iters| {
+        time_queue(queue, iters, || op.download().map(drop))
+    });
+}
+
+fn convolution(c: &mut Criterion) {
+    let Some(queue) = queue() else { return };
+    let mut group = OpGroup::new(c, "convolution");
+
+    for n in IMAGE_SIZES {
+        let pixels = Throughput::Elements((n * n) as u64);
+        for radius in FILTER_RADII {
+            let filter = generate_gaussian_kernel(radius, 1.0);
+            let op =
+                ImageKernel::convolution(queue, n, n, &filter).expect("failed to build the kernel");
+            if radius == FILTER_RADII[0] {
+                image_transfers(&mut group, queue, &op);
+            }
+            let size = format!("{n}x{n} filter {0}x{0}", 2 * radius + 1);
+            group.phase("compute", &size, pixels.clone(), |iters| {
+                time_queue(queue, iters, || op.enqueue())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn rotation(c: &mut Criterion) {
+    let Some(queue) = queue() else { return };
+    let mut group = OpGroup::new(c, "rotation");
+
+    for n in IMAGE_SIZES {
+        let op = ImageKernel::rotation(queue, n, n, 30f32.to_radians())
+            .expect("failed to build the kernel");
+        image_transfers(&mut group, queue, &op);
+        group.phase(
+            "compute",
+            &format!("{n}x{n}"),
+            Throughput::Elements((n * n) as u64),
+            |iters| time_queue(queue, iters, || op.enqueue()),
+        );
+    }
+    group.finish();
+}
+
+fn histogram(c: &mut Criterion) {
+    let Some(queue) = queue() else { return };
+    let mut group = OpGroup::new(c, "histogram");
+
+    for len in VECTOR_LENS {
+        let op = Histogram::new(queue, len).expect("failed to build the kernel");
+        let data: Vec<i32> = (0..len).map(|i| (i % HIST_BINS) as i32).collect();
+        let bytes = (len * std::mem::size_of::<i32>()) as u64;
+        let size = len.to_string();
+
+        group.phase("upload", &size, Throughput::Bytes(bytes), |iters| {
+            time_queue(queue, iters, || op.upload(&data))
+        });
+        group.phase("compute", &size, Throughput::Bytes(bytes), |iters| {
+            time_queue(queue, iters, || op.enqueue())
+        });
+        group.phase(
+            "download",
+            &size,
+            Throughput::Bytes((HIST_BINS * std::mem::size_of::<i32>()) as u64),
+            |iters| time_queue(queue, iters, || op.download().map(drop)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = bench::config();
+    targets = vector_add, convolution, rotation, histogram
+}
+
+fn main() {
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+
+    if let Some(queue) = queue() {
+        bench::write_results("opencl", &device::describe(queue))
+            .expect("failed to write the results");
+    }
+}
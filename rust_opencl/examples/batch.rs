@@ -0,0 +1,59 @@
+//! Runs the ops of a job file on every image of a directory:
+//! `cargo run --example batch -- jobs/blur_rotate.toml IN_DIR OUT_DIR [--in-flight N]`.
+//!
+//! The `input` and `output` of the job are ignored; see `lab_opencl::batch`.
+
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+
+use lab_opencl::batch::{self, BatchOptions};
+use lab_opencl::device;
+use lab_opencl::job::{Backend, Job};
+
+const USAGE: &str = "usage: batch <job.toml|job.json> <input dir> <output dir> [--in-flight N]";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = BatchOptions::default();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--in-flight" => {
+                options.in_flight = args.next().and_then(|n| n.parse().ok()).ok_or(USAGE)?
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let [job, input_dir, output_dir] = paths[..] else {
+        return Err(USAGE.into());
+    };
+    let job = Job::load(job)?;
+    job.check_backend(Backend::Opencl)?;
+
+    let queue = device::default_queue(None)?;
+    log::info!("Device : {}", device::describe(&queue));
+
+    let start = Instant::now();
+    let report = batch::run(
+        &queue,
+        &job.ops,
+        Path::new(input_dir),
+        Path::new(output_dir),
+        &options,
+    )?;
+    println!(
+        "{} images in {:?}, {} in flight",
+        report.processed,
+        start.elapsed(),
+        options.in_flight
+    );
+    for (path, err) in &report.failed {
+        println!("failed {}: {err}", path.display());
+    }
+    Ok(())
+}
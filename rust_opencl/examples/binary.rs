@@ -0,0 +1,49 @@
+use std::error::Error;
+use std::time::Instant;
+
+use lab_opencl::device::select_device;
+use lab_opencl::program::{self, ProgramCache};
+use ocl::enums::ProgramInfo;
+use ocl::{Context, Queue};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+    let queue = Queue::new(&context, device, None)?;
+
+    // a precompiled binary, e.g. `cargo run --example binary -- vecadd.aocx` on an FPGA board
+    if let Some(path) = std::env::args().nth(1) {
+        let program = program::load_binary(&context, device, &path, "")?;
+        log::info!(
+            "{path} : kernels {}",
+            program.info(ProgramInfo::KernelNames)?
+        );
+        return Ok(());
+    }
+
+    // otherwise build a kernel twice, the second build loads the cached binary
+    let cache = ProgramCache::from_env().ok_or("the program cache is off")?;
+    let source = std::fs::read_to_string("./kernels/vecadd_kernel.cl")?;
+    log::info!(
+        "cache file : {}",
+        cache.path(device, &source, "")?.display()
+    );
+    for _ in 0..2 {
+        let start = Instant::now();
+        cache.build(
+            &context,
+            queue.device(),
+            "kernels/vecadd_kernel.cl",
+            &source,
+            "",
+        )?;
+        log::info!("build : {:?}", start.elapsed());
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::error::Error;
+
+use lab_opencl::blas::Blas;
+use lab_opencl::device::select_device;
+use ocl::{Buffer, Context, Queue};
+
+const VECTOR_SIZE: usize = 1_000_000;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let x: Vec<f32> = (0..VECTOR_SIZE).map(|v| (v % 100) as f32 * 0.01).collect();
+    let y: Vec<f32> = vec![1.0; VECTOR_SIZE];
+
+    // initialize host-side program.
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+    let queue = Queue::new(&context, device, None)?;
+
+    let buff_x = Buffer::<f32>::builder()
+        .queue(queue.clone())
+        .len(VECTOR_SIZE)
+        .copy_host_slice(&x)
+        .build()?;
+
+    let buff_y = Buffer::<f32>::builder()
+        .queue(queue.clone())
+        .len(VECTOR_SIZE)
+        .copy_host_slice(&y)
+        .build()?;
+
+    let blas = Blas::new(&queue)?;
+
+    // y = 2x + y, then y = 0.5y
+    blas.axpy(2.0, &buff_x, &buff_y)?;
+    blas.scal(0.5, &buff_y)?;
+
+    log::info!("dot(x, y) : {}", blas.dot(&buff_x, &buff_y)?);
+    log::info!("nrm2(y)   : {}", blas.nrm2(&buff_y)?);
+    log::info!("asum(x)   : {}", blas.asum(&buff_x)?);
+    log::info!("iamax(x)  : {:?}", blas.iamax(&buff_x)?);
+
+    Ok(())
+}
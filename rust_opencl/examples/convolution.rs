@@ -1,11 +1,15 @@
 use std::error::Error;
 
 use image::{EncodableLayout, GenericImageView};
+use lab_opencl::device::select_device;
+use lab_opencl::profile::{profiling_queue, Profiler, Stage};
+use lab_opencl::program;
+use lab_opencl::trace::Trace;
 use lab_opencl::utils::generate_gaussian_kernel;
 use ocl::{
     core::{ImageDescriptor, ImageFormat},
     enums::{AddressingMode, FilterMode, ImageChannelOrder, MemObjectType},
-    flags, Buffer, Context, Device, Image, Kernel, Platform, Program, Queue, Sampler,
+    flags, Buffer, Context, Image, Kernel, Sampler,
 };
 
 const IMAGE_PATH: &str = "data/cat.png";
@@ -53,11 +57,16 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // initialize host-side program.
     log::info!("Initialize the host-side program");
-    let platform = Platform::default();
-    let device = Device::first(platform)?;
-    let context = Context::builder().devices(device).build()?;
-    let queue = Queue::new(&context, device, None)?;
-    let source = std::fs::read_to_string("./kernels/convolution.cl")?;
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+    let queue = profiling_queue(&context, device)?;
+    let mut profiler = Profiler::new();
+    // the kernels read their pixels through the macros of pixel.cl, float unless defined
+    let source = std::fs::read_to_string("./kernels/pixel.cl")?
+        + &std::fs::read_to_string("./kernels/convolution.cl")?;
 
     // create the image buffer
     log::info!("Create the image buffers");
@@ -99,6 +108,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         input_img
             .write(&img)
             .region((img_cols, img_rows, 1))
+            .enew(profiler.event(Stage::Write, "image"))
             .enq()?;
 
         // buffer for filter
@@ -108,8 +118,11 @@ fn main() -> Result<(), Box<dyn Error>> {
             .queue(queue.clone())
             .flags(flags::MEM_READ_ONLY)
             .len(kernel.len())
-            .copy_host_slice(&kernel)
             .build()?;
+        filter_buffer
+            .write(&kernel)
+            .enew(profiler.event(Stage::Write, "filter"))
+            .enq()?;
 
         let sampler = Sampler::new(
             &context,
@@ -120,10 +133,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         log::info!("Create the program");
         // create a program
-        let program = Program::builder()
-            .devices(device)
-            .src(source.as_str())
-            .build(&context)?;
+        let program = program::build(&queue, "kernels/convolution.cl", &source, "")?;
 
         //
         let kernel = Kernel::builder()
@@ -138,14 +148,27 @@ fn main() -> Result<(), Box<dyn Error>> {
             .arg(&filter_size)
             .arg_sampler(&sampler)
             .build()?;
-        kernel.enq()?;
+        kernel
+            .cmd()
+            .enew(profiler.event(Stage::Kernel, "convolution"))
+            .enq()?;
 
         output_img
             .read(&mut out)
             .region((img_cols, img_rows, 1))
+            .enew(profiler.event(Stage::Read, "image"))
             .enq()?;
     }
 
+    let report = profiler.report()?;
+    log::info!("Profile\n{report}");
+
+    // TRACE=trace.json writes the timeline
+    if let Ok(path) = std::env::var("TRACE") {
+        Trace::new().add_queue("queue", &report).write(&path)?;
+        log::info!("Trace written to {path}");
+    }
+
     save_img(IMAGE_OUT_PATH, &out, img_cols, img_rows)?;
     Ok(())
 }
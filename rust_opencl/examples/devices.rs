@@ -0,0 +1,23 @@
+//! Prints the capabilities of every OpenCL device: `cargo run --example devices [-- --json]`.
+//!
+//! See `lab_opencl::capabilities` for the fields.
+
+use std::error::Error;
+
+use lab_opencl::capabilities;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let json = std::env::args().skip(1).any(|arg| arg == "--json");
+    let devices = capabilities::report()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&devices)?);
+        return Ok(());
+    }
+    for device in &devices {
+        println!("{device}\n");
+    }
+    Ok(())
+}
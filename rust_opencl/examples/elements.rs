@@ -0,0 +1,68 @@
+use std::error::Error;
+
+use lab_opencl::blas::Blas;
+use lab_opencl::device::select_device;
+use lab_opencl::element::{check_support, Element, F16};
+use lab_opencl::map::MapKernels;
+use ocl::{Buffer, Context, Queue};
+
+fn upload<T: Element>(queue: &Queue, values: &[T]) -> ocl::Result<Buffer<T>> {
+    Buffer::<T>::builder()
+        .queue(queue.clone())
+        .len(values.len())
+        .copy_host_slice(values)
+        .build()
+}
+
+fn download<T: Element>(buffer: &Buffer<T>) -> ocl::Result<Vec<T>> {
+    let mut out = vec![T::default(); buffer.len()];
+    buffer.read(&mut out).enq()?;
+    Ok(out)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    // initialize host-side program.
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+    let queue = Queue::new(&context, device, None)?;
+
+    let kernels = MapKernels::new(&queue);
+
+    // int -> int
+    let x: Vec<i32> = (-8..8).collect();
+    let y: Buffer<i32> = kernels.map(&upload(&queue, &x)?, "a * a - 3")?;
+    log::info!("int : {:?}", download(&y)?);
+
+    // uint -> float
+    let x: Vec<u32> = (0..16).collect();
+    let y: Buffer<f32> = kernels.map(&upload(&queue, &x)?, "sqrt((float)a)")?;
+    log::info!("uint -> float : {:?}", download(&y)?);
+
+    // half needs cl_khr_fp16
+    if check_support::<F16>(&device).is_ok() {
+        let x: Vec<F16> = (0..16).map(|v| F16::from(v as f32 * 0.5)).collect();
+        let y: Buffer<F16> = kernels.map(&upload(&queue, &x)?, "a * (half)2 + (half)0.25")?;
+        let y: Vec<f32> = download(&y)?.into_iter().map(f32::from).collect();
+        log::info!("half : {:?}", y);
+    } else {
+        log::info!("half : not supported by the device");
+    }
+
+    // double needs cl_khr_fp64
+    if check_support::<f64>(&device).is_ok() {
+        let blas = Blas::<f64>::new(&queue)?;
+        let x: Vec<f64> = (0..1000).map(|v| v as f64 * 0.001).collect();
+        let x = upload(&queue, &x)?;
+        log::info!("double dot : {}", blas.dot(&x, &x)?);
+    } else {
+        log::info!("double : not supported by the device");
+    }
+
+    Ok(())
+}
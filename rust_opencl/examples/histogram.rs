@@ -1,10 +1,16 @@
 use std::error::Error;
 
 use image::GenericImageView;
-use ocl::{flags, Buffer, Context, Device, Kernel, Platform, Program, Queue};
+use lab_opencl::device::select_device;
+use lab_opencl::profile::{profiling_queue, Profiler, Stage};
+use lab_opencl::program;
+use lab_opencl::trace::Trace;
+use ocl::{flags, Buffer, Context, Kernel};
 
 const HIST_BINS: usize = 256;
 fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+
     // load the image
     let img = image::open("data/cat.png")?;
     let (img_cols, img_rows) = img.dimensions();
@@ -23,19 +29,27 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut histogram: Vec<i32> = vec![0; HIST_BINS];
 
     // initialize host-side program.
-    let platform = Platform::default();
-    let device = Device::first(platform)?;
-    let context = Context::builder().devices(device).build()?;
-    let queue = Queue::new(&context, device, None)?;
-    let source = std::fs::read_to_string("./kernels/histogram.cl")?;
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+    let queue = profiling_queue(&context, device)?;
+    let mut profiler = Profiler::new();
+    // the kernels read their pixels through the macros of pixel.cl, int here
+    let source = std::fs::read_to_string("./kernels/pixel.cl")?
+        + &std::fs::read_to_string("./kernels/histogram.cl")?;
 
     // buffers
     // buffer input image
     let buff_in = Buffer::<i32>::builder()
         .queue(queue.clone())
         .len(img.len())
-        .copy_host_slice(&img)
         .build()?;
+    buff_in
+        .write(&img)
+        .enew(profiler.event(Stage::Write, "image"))
+        .enq()?;
 
     // initialize the histogram buffer with zero
     let buff_out = Buffer::<i32>::builder()
@@ -46,10 +60,12 @@ fn main() -> Result<(), Box<dyn Error>> {
         .build()?;
 
     // create a program
-    let program = Program::builder()
-        .devices(device)
-        .src(source.as_str())
-        .build(&context)?;
+    let program = program::build(
+        &queue,
+        "kernels/histogram.cl",
+        &source,
+        "-D T=int -D PIXEL_INT -D HIST_RANGE=256.0f",
+    )?;
 
     //
     let kernel = Kernel::builder()
@@ -64,11 +80,25 @@ fn main() -> Result<(), Box<dyn Error>> {
         .build()?;
 
     unsafe {
-        kernel.enq()?;
+        kernel
+            .cmd()
+            .enew(profiler.event(Stage::Kernel, "histogram"))
+            .enq()?;
     }
 
-    buff_out.read(&mut histogram).enq()?;
+    buff_out
+        .read(&mut histogram)
+        .enew(profiler.event(Stage::Read, "histogram"))
+        .enq()?;
     println!("Result: {histogram:?}");
+    let report = profiler.report()?;
+    println!("{report}");
+
+    // TRACE=trace.json writes the timeline
+    if let Ok(path) = std::env::var("TRACE") {
+        Trace::new().add_queue("queue", &report).write(&path)?;
+        println!("Trace written to {path}");
+    }
 
     Ok(())
 }
@@ -0,0 +1,39 @@
+//! Runs an image processing job file: `cargo run --example job -- jobs/blur_rotate.toml`.
+//!
+//! See `lab_common::job` for the format.
+
+use std::error::Error;
+use std::time::Instant;
+
+use lab_opencl::device;
+use lab_opencl::job::{self, Job};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let path = std::env::args()
+        .nth(1)
+        .ok_or("usage: job <job.toml|job.json>")?;
+    let job = Job::load(&path)?;
+
+    let queue = device::default_queue(None)?;
+    log::info!("Device : {}", device::describe(&queue));
+
+    let start = Instant::now();
+    let output = job::run(&job, &queue)?;
+    println!(
+        "{path}: {} ops on {}x{} in {:?}",
+        job.ops.len(),
+        output.cols,
+        output.rows,
+        start.elapsed()
+    );
+    for (index, histogram) in output.histograms.iter().enumerate() {
+        println!("histogram {index}: {histogram:?}");
+    }
+    if let Some(out_path) = &job.output {
+        println!("saved {}", out_path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::error::Error;
+
+use lab_opencl::device::select_device;
+use lab_opencl::map::MapKernels;
+use ocl::{Buffer, Context, Queue};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let x: Vec<f32> = (0..1024).map(|v| v as f32).collect();
+    let y: Vec<f32> = (0..1024).map(|v| v as f32 * 0.01).collect();
+
+    // initialize host-side program.
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+    let queue = Queue::new(&context, device, None)?;
+
+    let buff_x = Buffer::<f32>::builder()
+        .queue(queue.clone())
+        .len(x.len())
+        .copy_host_slice(&x)
+        .build()?;
+
+    let buff_y = Buffer::<f32>::builder()
+        .queue(queue.clone())
+        .len(y.len())
+        .copy_host_slice(&y)
+        .build()?;
+
+    let kernels = MapKernels::new(&queue);
+
+    // float -> float with two inputs
+    let z: Buffer<f32> = kernels.map2(&buff_x, &buff_y, "a * 2.0f + sin(b)")?;
+    // float -> uint with one input, the element index is available as `i`
+    let classes: Buffer<u32> = kernels.map(&buff_x, "((uint)a + i / 2) % 3")?;
+    // same expression again, served from the cache
+    let z2: Buffer<f32> = kernels.map2(&z, &buff_y, "a * 2.0f + sin(b)")?;
+
+    let mut out = vec![0f32; x.len()];
+    z.read(&mut out).enq()?;
+    log::info!("z      : {:?}", &out[..8]);
+
+    let mut out_classes = vec![0u32; x.len()];
+    classes.read(&mut out_classes).enq()?;
+    log::info!("classes: {:?}", &out_classes[..8]);
+
+    z2.read(&mut out).enq()?;
+    log::info!("z2     : {:?}", &out[..8]);
+    log::info!("built programs : {}", kernels.cached());
+
+    Ok(())
+}
@@ -1,10 +1,12 @@
 use std::error::Error;
 
 use image::{EncodableLayout, GenericImageView};
+use lab_opencl::device::select_device;
+use lab_opencl::program;
 use ocl::{
     core::{ImageDescriptor, ImageFormat},
     enums::{ImageChannelOrder, MemObjectType},
-    flags, Context, Device, Image, Kernel, Platform, Program, Queue,
+    flags, Context, Image, Kernel, Queue,
 };
 
 const IMAGE_PATH: &str = "data/cat.png";
@@ -51,11 +53,15 @@ fn main() -> Result<(), Box<dyn Error>> {
     // histogram
     // initialize host-side program.
     log::info!("Initialize the host-side program");
-    let platform = Platform::default();
-    let device = Device::first(platform)?;
-    let context = Context::builder().devices(device).build()?;
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
     let queue = Queue::new(&context, device, None)?;
-    let source = std::fs::read_to_string("./kernels/rotation.cl")?;
+    // the kernels read their pixels through the macros of pixel.cl, float unless defined
+    let source = std::fs::read_to_string("./kernels/pixel.cl")?
+        + &std::fs::read_to_string("./kernels/rotation.cl")?;
 
     // create the image buffer
     log::info!("Create the image buffers");
@@ -101,10 +107,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         log::info!("Create the program");
         // create a program
-        let program = Program::builder()
-            .devices(device)
-            .src(source.as_str())
-            .build(&context)?;
+        let program = program::build(&queue, "kernels/rotation.cl", &source, "")?;
 
         //
         let kernel = Kernel::builder()
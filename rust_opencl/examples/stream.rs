@@ -0,0 +1,39 @@
+use std::error::Error;
+
+use lab_opencl::device::select_device;
+use lab_opencl::stream::{max_mem_alloc_size, Streamer};
+use ocl::{Context, Queue};
+
+const VECTOR_SIZE: usize = 40_000_000;
+// stream the host vectors in 64MB chunks
+const CHUNK_BYTES: usize = 64 << 20;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let x: Vec<f32> = (0..VECTOR_SIZE).map(|v| (v % 100) as f32 * 0.01).collect();
+    let y: Vec<f32> = vec![1.0; VECTOR_SIZE];
+
+    // initialize host-side program.
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+    let queue = Queue::new(&context, device, None)?;
+    log::info!(
+        "max mem alloc size : {} bytes",
+        max_mem_alloc_size(&device)?
+    );
+
+    let streamer = Streamer::new(&queue)?.with_chunk_bytes(CHUNK_BYTES);
+    log::info!("stream dot(x, y) : {}", streamer.dot(&x, &y)?);
+    log::info!("stream nrm2(x)   : {}", streamer.nrm2(&x)?);
+    log::info!("stream iamax(x)  : {:?}", streamer.iamax(&x)?);
+
+    let z: Vec<f32> = streamer.zip(&[&x, &y], "a + b")?;
+    log::info!("stream a + b     : {}", z[VECTOR_SIZE - 1]);
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use std::error::Error;
+
+use lab_opencl::device::select_device;
+use lab_opencl::transpose::{measure_bandwidth, transpose};
+use ocl::{Context, Queue};
+
+const ROWS: usize = 1000;
+const COLS: usize = 1500;
+const ITERATIONS: u32 = 20;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    // initialize host-side program.
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+    let queue = Queue::new(&context, device, None)?;
+
+    // check the result against the host transpose
+    let input: Vec<f32> = (0..ROWS * COLS).map(|v| v as f32).collect();
+    let out = transpose(&queue, &input, ROWS, COLS)?;
+    let matched = (0..ROWS).all(|r| (0..COLS).all(|c| out[c * ROWS + r] == input[r * COLS + c]));
+    log::info!("Transpose ({ROWS} x {COLS}) matched : {matched}");
+
+    let report = measure_bandwidth::<f32>(&queue, 4096, 4096, ITERATIONS)?;
+    log::info!("Bandwidth (4096 x 4096 f32)");
+    log::info!("\tcopy      : {:.2} GB/s", report.copy_gbps());
+    log::info!("\ttranspose : {:.2} GB/s", report.transpose_gbps());
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use std::error::Error;
+
+use lab_opencl::device;
+use lab_opencl::ops::{Histogram, ImageKernel};
+use lab_opencl::tune::{TuneCache, Tuner};
+use lab_opencl::utils::generate_gaussian_kernel;
+
+const COLS: u32 = 1024;
+const ROWS: u32 = 1024;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let queue = device::default_queue(None)?;
+    log::info!("Device : {}", device::describe(&queue));
+
+    let mut tuner = Tuner::new(TuneCache::open_default()?);
+    let image = vec![0.5f32; (COLS * ROWS) as usize];
+    let filter = generate_gaussian_kernel(2, 1.0);
+
+    for (name, mut kernel) in [
+        (
+            "convolution 5x5",
+            ImageKernel::convolution(&queue, COLS, ROWS, &filter)?,
+        ),
+        ("rotation", ImageKernel::rotation(&queue, COLS, ROWS, 0.5)?),
+    ] {
+        kernel.upload(&image)?;
+        let size = kernel.tune(&mut tuner)?;
+        log::info!("{name} ({COLS} x {ROWS}) : local work size {size:?}");
+    }
+
+    let data: Vec<i32> = (0..1 << 20).map(|i| i % 256).collect();
+    let mut histogram = Histogram::new(&queue, data.len())?;
+    histogram.upload(&data)?;
+    let size = histogram.tune(&mut tuner)?;
+    log::info!(
+        "histogram ({} values) : local work size {size:?}",
+        data.len()
+    );
+    Ok(())
+}
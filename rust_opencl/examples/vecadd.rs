@@ -1,4 +1,6 @@
-use ocl::{Buffer, Context, Device, Kernel, Platform, Program, Queue};
+use lab_opencl::device::select_device;
+use lab_opencl::program;
+use ocl::{Buffer, Context, Kernel, Queue};
 use std::error::Error;
 
 const VECTOR_SIZE: usize = 1024;
@@ -12,18 +14,21 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // let platform_list = Platform::list();
     // let platform = platform_list[0];
-    let platform = Platform::default();
     // platform infos
     // println!("{}", platform.version()?);
 
     // let device_list = Device::list_all(platform)?;
     // let device = device_list[0];
-    let device = Device::first(platform)?;
+    // a GPU, else a CPU device such as PoCL
+    let (platform, device) = select_device()?;
 
     // device infos
     println!("{} - {}", device.name()?, device.vendor()?);
 
-    let context = Context::builder().devices(device).build()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
     let queue = Queue::new(&context, device, None)?;
 
     let source = std::fs::read_to_string("./kernels/vecadd_kernel.cl")?;
@@ -45,10 +50,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         .len(VECTOR_SIZE)
         .build()?;
 
-    let program = Program::builder()
-        .devices(device)
-        .src(source.as_str())
-        .build(&context)?;
+    let program = program::build(&queue, "kernels/vecadd_kernel.cl", &source, "")?;
 
     let kernel = Kernel::builder()
         .program(&program)
@@ -0,0 +1,49 @@
+//! Reruns an image kernel each time a file in `kernels/` changes, for editing kernels without
+//! restarting: `cargo run --example watch -- [rotation|convolution]`.
+//!
+//! Each run builds the kernel from `kernels/`, saves the output image and prints how long
+//! the kernel took, or prints the build errors.
+
+use std::error::Error;
+use std::time::Instant;
+
+use image::{EncodableLayout, GenericImageView};
+use lab_opencl::device;
+use lab_opencl::ops::ImageKernel;
+use lab_opencl::utils::generate_gaussian_kernel;
+use lab_opencl::watch;
+
+const IMAGE_PATH: &str = "data/cat.png";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let job = std::env::args().nth(1).unwrap_or("rotation".to_string());
+    let img = image::open(IMAGE_PATH)?;
+    let (cols, rows) = img.dimensions();
+    let image: Vec<f32> = img.into_luma8().iter().map(|&v| v as f32 / 255.0).collect();
+    let out_path = format!("data/cat_{job}_watch_out.png");
+
+    let queue = device::default_queue(None)?;
+    log::info!("Device : {}", device::describe(&queue));
+
+    watch::SOURCE_DIR.run("kernels", || -> Result<(), Box<dyn Error>> {
+        let kernel = match job.as_str() {
+            "rotation" => ImageKernel::rotation(&queue, cols, rows, 0.5)?,
+            "convolution" => {
+                ImageKernel::convolution(&queue, cols, rows, &generate_gaussian_kernel(2, 1.0))?
+            }
+            _ => {
+                return Err(format!("unknown job `{job}`, expected rotation or convolution").into())
+            }
+        };
+        let start = Instant::now();
+        let out = kernel.run(&image)?;
+        let elapsed = start.elapsed();
+        let out: Vec<u8> = out.iter().map(|v| (v * 255.0) as u8).collect();
+        image::save_buffer(&out_path, out.as_bytes(), cols, rows, image::ColorType::L8)?;
+        println!("{job}: {elapsed:?}, saved {out_path}");
+        Ok(())
+    })
+}
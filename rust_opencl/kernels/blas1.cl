@@ -0,0 +1,198 @@
+// BLAS level-1 operations on float vectors of any length.
+// T is given by the host as a build option (-D T=float or -D T=double).
+//
+// element-wise kernels use a grid-stride loop, so the global size is capped by the host.
+// reductions run in two stages: `*_partial` writes one value per work-group into
+// `partials`, then `*_final` reduces the partials with a single work-group.
+#define WORK_GROUP_SIZE 256
+
+// y = alpha * x + y
+__kernel void axpy(T alpha, __global const T *x, __global T *y,
+                   int n) {
+  for (int i = get_global_id(0); i < n; i += get_global_size(0)) {
+    y[i] = alpha * x[i] + y[i];
+  }
+}
+
+// x = alpha * x
+__kernel void scal(T alpha, __global T *x, int n) {
+  for (int i = get_global_id(0); i < n; i += get_global_size(0)) {
+    x[i] = alpha * x[i];
+  }
+}
+
+// out = a * b (element-wise)
+__kernel void mul(__global const T *a, __global const T *b,
+                  __global T *out, int n) {
+  for (int i = get_global_id(0); i < n; i += get_global_size(0)) {
+    out[i] = a[i] * b[i];
+  }
+}
+
+// y = x
+__kernel void copy(__global const T *x, __global T *y, int n) {
+  for (int i = get_global_id(0); i < n; i += get_global_size(0)) {
+    y[i] = x[i];
+  }
+}
+
+// tree reduction of `scratch` into scratch[0].
+void reduce_sum(__local T *scratch, int lid) {
+  for (int stride = WORK_GROUP_SIZE / 2; stride > 0; stride /= 2) {
+    barrier(CLK_LOCAL_MEM_FENCE);
+    if (lid < stride) {
+      scratch[lid] += scratch[lid + stride];
+    }
+  }
+}
+
+// sum(x * y)
+__kernel void dot_partial(__global const T *x, __global const T *y,
+                          int n, __global T *partials) {
+  __local T scratch[WORK_GROUP_SIZE];
+  int lid = get_local_id(0);
+
+  T sum = 0;
+  for (int i = get_global_id(0); i < n; i += get_global_size(0)) {
+    sum += x[i] * y[i];
+  }
+  scratch[lid] = sum;
+  reduce_sum(scratch, lid);
+  if (lid == 0) {
+    partials[get_group_id(0)] = scratch[0];
+  }
+}
+
+// sum(x)
+__kernel void sum_partial(__global const T *x, int n,
+                          __global T *partials) {
+  __local T scratch[WORK_GROUP_SIZE];
+  int lid = get_local_id(0);
+
+  T sum = 0;
+  for (int i = get_global_id(0); i < n; i += get_global_size(0)) {
+    sum += x[i];
+  }
+  scratch[lid] = sum;
+  reduce_sum(scratch, lid);
+  if (lid == 0) {
+    partials[get_group_id(0)] = scratch[0];
+  }
+}
+
+// sum(|x|)
+__kernel void asum_partial(__global const T *x, int n,
+                           __global T *partials) {
+  __local T scratch[WORK_GROUP_SIZE];
+  int lid = get_local_id(0);
+
+  T sum = 0;
+  for (int i = get_global_id(0); i < n; i += get_global_size(0)) {
+    sum += fabs(x[i]);
+  }
+  scratch[lid] = sum;
+  reduce_sum(scratch, lid);
+  if (lid == 0) {
+    partials[get_group_id(0)] = scratch[0];
+  }
+}
+
+// sum(x * x)
+__kernel void sumsq_partial(__global const T *x, int n,
+                            __global T *partials) {
+  __local T scratch[WORK_GROUP_SIZE];
+  int lid = get_local_id(0);
+
+  T sum = 0;
+  for (int i = get_global_id(0); i < n; i += get_global_size(0)) {
+    sum += x[i] * x[i];
+  }
+  scratch[lid] = sum;
+  reduce_sum(scratch, lid);
+  if (lid == 0) {
+    partials[get_group_id(0)] = scratch[0];
+  }
+}
+
+// result[0] = sum(partials[0..n]), or its square root.
+__kernel void sum_final(__global const T *partials, int n,
+                        __global T *result, int take_sqrt) {
+  __local T scratch[WORK_GROUP_SIZE];
+  int lid = get_local_id(0);
+
+  T sum = 0;
+  for (int i = lid; i < n; i += WORK_GROUP_SIZE) {
+    sum += partials[i];
+  }
+  scratch[lid] = sum;
+  reduce_sum(scratch, lid);
+  if (lid == 0) {
+    result[0] = take_sqrt ? sqrt(scratch[0]) : scratch[0];
+  }
+}
+
+// tree reduction of (`scratch`, `scratch_idx`) into index 0.
+// keeps the larger value, and the smaller index on a tie.
+void reduce_max(__local T *scratch, __local uint *scratch_idx, int lid) {
+  for (int stride = WORK_GROUP_SIZE / 2; stride > 0; stride /= 2) {
+    barrier(CLK_LOCAL_MEM_FENCE);
+    if (lid < stride) {
+      T v = scratch[lid + stride];
+      uint idx = scratch_idx[lid + stride];
+      if (v > scratch[lid] || (v == scratch[lid] && idx < scratch_idx[lid])) {
+        scratch[lid] = v;
+        scratch_idx[lid] = idx;
+      }
+    }
+  }
+}
+
+// first index of max(|x|)
+__kernel void iamax_partial(__global const T *x, int n,
+                            __global T *partials,
+                            __global uint *partial_idx) {
+  __local T scratch[WORK_GROUP_SIZE];
+  __local uint scratch_idx[WORK_GROUP_SIZE];
+  int lid = get_local_id(0);
+
+  T best = -1;
+  uint best_idx = UINT_MAX;
+  for (int i = get_global_id(0); i < n; i += get_global_size(0)) {
+    T v = fabs(x[i]);
+    if (v > best) {
+      best = v;
+      best_idx = i;
+    }
+  }
+  scratch[lid] = best;
+  scratch_idx[lid] = best_idx;
+  reduce_max(scratch, scratch_idx, lid);
+  if (lid == 0) {
+    partials[get_group_id(0)] = scratch[0];
+    partial_idx[get_group_id(0)] = scratch_idx[0];
+  }
+}
+
+// result_idx[0] = index of max(partials[0..n])
+__kernel void iamax_final(__global const T *partials,
+                          __global const uint *partial_idx, int n,
+                          __global uint *result_idx) {
+  __local T scratch[WORK_GROUP_SIZE];
+  __local uint scratch_idx[WORK_GROUP_SIZE];
+  int lid = get_local_id(0);
+
+  T best = -1;
+  uint best_idx = UINT_MAX;
+  for (int i = lid; i < n; i += WORK_GROUP_SIZE) {
+    if (partials[i] > best || (partials[i] == best && partial_idx[i] < best_idx)) {
+      best = partials[i];
+      best_idx = partial_idx[i];
+    }
+  }
+  scratch[lid] = best;
+  scratch_idx[lid] = best_idx;
+  reduce_max(scratch, scratch_idx, lid);
+  if (lid == 0) {
+    result_idx[0] = scratch_idx[0];
+  }
+}
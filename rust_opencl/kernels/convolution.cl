@@ -24,11 +24,11 @@ __kernel void convolution(__read_only image2d_t input_img,
       pixel_pos.x = column + j;
 
       sum.x +=
-          read_imagef(input_img, sampler, pixel_pos).x * filter[filt_idx++];
+          read_pixel(input_img, sampler, pixel_pos).x * filter[filt_idx++];
     }
   }
 
   pixel_pos.x = column;
   pixel_pos.y = row;
-  write_imagef(output_img, pixel_pos, sum);
+  write_pixel(output_img, pixel_pos, sum);
 }
@@ -1,6 +1,9 @@
 #define HIST_BINS 256
 
-__kernel void histogram(__global int *data, int num_data,
+// histogram of values in HIST_BINS bins over [0, HIST_RANGE]; values outside of the range go
+// to the end bins. T is given by the host for this kernel only.
+#ifdef T
+__kernel void histogram(__global const T *data, int num_data,
                         __global int *histogram) {
   __local int local_histogram[HIST_BINS];
   int lid = get_local_id(0);
@@ -16,7 +19,9 @@ __kernel void histogram(__global int *data, int num_data,
 
   // compute the local histogram
   for (int i = gid; i < num_data; i += get_global_size(0)) {
-    atomic_add(&local_histogram[data[i]], 1);
+    int bin = (int)clamp((float)data[i] * (HIST_BINS / HIST_RANGE), 0.0f,
+                         HIST_BINS - 1.0f);
+    atomic_add(&local_histogram[bin], 1);
   }
 
   // wait until all work-items within the work-group have completed
@@ -27,3 +32,34 @@ __kernel void histogram(__global int *data, int num_data,
     atomic_add(&histogram[i], local_histogram[i]);
   }
 }
+#endif
+
+const sampler_t pixel_sampler =
+    CLK_NORMALIZED_COORDS_FALSE | CLK_ADDRESS_CLAMP_TO_EDGE | CLK_FILTER_NEAREST;
+
+// histogram of a single channel image in HIST_BINS bins over [0, HIST_RANGE], one
+// work-item per pixel; the global size may overhang the image.
+__kernel void image_histogram(__read_only image2d_t image, int cols, int rows,
+                              __global int *histogram) {
+  __local int local_histogram[HIST_BINS];
+  int lid = get_local_id(1) * get_local_size(0) + get_local_id(0);
+  int local_size = get_local_size(0) * get_local_size(1);
+  int x = get_global_id(0);
+  int y = get_global_id(1);
+
+  for (int i = lid; i < HIST_BINS; i += local_size) {
+    local_histogram[i] = 0;
+  }
+  barrier(CLK_LOCAL_MEM_FENCE);
+
+  if (x < cols && y < rows) {
+    float value = read_pixel(image, pixel_sampler, (int2)(x, y)).x;
+    int bin = (int)clamp(value * (HIST_BINS / HIST_RANGE), 0.0f, HIST_BINS - 1.0f);
+    atomic_add(&local_histogram[bin], 1);
+  }
+  barrier(CLK_LOCAL_MEM_FENCE);
+
+  for (int i = lid; i < HIST_BINS; i += local_size) {
+    atomic_add(&histogram[i], local_histogram[i]);
+  }
+}
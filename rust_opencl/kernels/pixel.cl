@@ -0,0 +1,21 @@
+// Image access for the pixel type the host selects with -D PIXEL_FLOAT (float and half
+// images), -D PIXEL_UINT or -D PIXEL_INT. The kernels compute in float; integer pixels are
+// rounded to the nearest value and saturated when written.
+#if defined(PIXEL_UINT)
+#define read_pixel(image, sampler, coord)                                      \
+  convert_float4(read_imageui(image, sampler, coord))
+#define write_pixel(image, coord, value)                                       \
+  write_imageui(image, coord, convert_uint4_sat_rte(value))
+#elif defined(PIXEL_INT)
+#define read_pixel(image, sampler, coord)                                      \
+  convert_float4(read_imagei(image, sampler, coord))
+#define write_pixel(image, coord, value)                                       \
+  write_imagei(image, coord, convert_int4_sat_rte(value))
+#else
+#define read_pixel(image, sampler, coord) read_imagef(image, sampler, coord)
+#define write_pixel(image, coord, value) write_imagef(image, coord, value)
+#endif
+
+#ifndef HIST_RANGE
+#define HIST_RANGE 1.0f
+#endif
@@ -1,6 +1,17 @@
 
+// integer images can not be filtered, the kernel interpolates itself
 __constant sampler_t sampler =
-    CLK_NORMALIZED_COORDS_FALSE | CLK_FILTER_LINEAR | CLK_ADDRESS_CLAMP;
+    CLK_NORMALIZED_COORDS_FALSE | CLK_FILTER_NEAREST | CLK_ADDRESS_CLAMP;
+
+#ifdef BICUBIC
+// Catmull-Rom weights of the four texels around a location t past the second one
+void cubic_weights(float t, float w[4]) {
+  w[0] = ((-0.5f * t + 1.0f) * t - 0.5f) * t;
+  w[1] = (1.5f * t - 2.5f) * t * t + 1.0f;
+  w[2] = ((-1.5f * t + 2.0f) * t + 0.5f) * t;
+  w[3] = (0.5f * t - 0.5f) * t * t;
+}
+#endif
 
 __kernel void rotation(__read_only image2d_t input_img,
                        __write_only image2d_t output_img, int img_width,
@@ -24,9 +35,33 @@ __kernel void rotation(__read_only image2d_t input_img,
   read_coord.x = x_ * cos_theta - y_ * sin_theta + x0;
   read_coord.y = x_ * sin_theta + y_ * cos_theta + y0;
 
-  // read the pixel from input
-  float value = read_imagef(input_img, sampler, read_coord).x; // dim 1.
+  // texel centers at +0.5 and a zero border, like CLK_FILTER_LINEAR
+  float2 pos = read_coord - 0.5f;
+  float2 base = floor(pos);
+  float2 frac = pos - base;
+  int2 p = convert_int2(base);
+#ifdef BICUBIC
+  // bicubic interpolation over the 4x4 texels around the location
+  float wx[4], wy[4];
+  cubic_weights(frac.x, wx);
+  cubic_weights(frac.y, wy);
+  float value = 0.0f;
+  for (int j = 0; j < 4; j++) {
+    float row = 0.0f;
+    for (int i = 0; i < 4; i++) {
+      row += wx[i] * read_pixel(input_img, sampler, p + (int2)(i - 1, j - 1)).x;
+    }
+    value += wy[j] * row;
+  }
+#else
+  // bilinear interpolation
+  float value =
+      (1.0f - frac.x) * (1.0f - frac.y) * read_pixel(input_img, sampler, p).x +
+      frac.x * (1.0f - frac.y) * read_pixel(input_img, sampler, p + (int2)(1, 0)).x +
+      (1.0f - frac.x) * frac.y * read_pixel(input_img, sampler, p + (int2)(0, 1)).x +
+      frac.x * frac.y * read_pixel(input_img, sampler, p + (int2)(1, 1)).x;
+#endif
 
   // write to the output
-  write_imagef(output_img, (int2)(x, y), (float4)(value, 0.f, 0.f, 0.f));
+  write_pixel(output_img, (int2)(x, y), (float4)(value, 0.f, 0.f, 0.f));
 }
\ No newline at end of file
@@ -0,0 +1,38 @@
+// The element type T is given by the host as a build option (-D T=float).
+#define TILE_DIM 16
+
+__kernel void transpose(__global const T *input, __global T *output, int rows,
+                        int cols) {
+  // padding one column to avoid the bank conflicts on the column access.
+  __local T tile[TILE_DIM][TILE_DIM + 1];
+
+  int lx = get_local_id(0);
+  int ly = get_local_id(1);
+
+  // read the tile from the input (row-major, rows x cols)
+  int x = get_group_id(0) * TILE_DIM + lx;
+  int y = get_group_id(1) * TILE_DIM + ly;
+  if (x < cols && y < rows) {
+    tile[ly][lx] = input[y * cols + x];
+  }
+
+  // wait until the whole tile is loaded
+  barrier(CLK_LOCAL_MEM_FENCE);
+
+  // write the transposed tile to the output (cols x rows)
+  int out_x = get_group_id(1) * TILE_DIM + lx;
+  int out_y = get_group_id(0) * TILE_DIM + ly;
+  if (out_x < rows && out_y < cols) {
+    output[out_y * rows + out_x] = tile[lx][ly];
+  }
+}
+
+// plain 2D copy with the same access pattern. Used as the bandwidth baseline.
+__kernel void copy(__global const T *input, __global T *output, int rows,
+                   int cols) {
+  int x = get_global_id(0);
+  int y = get_global_id(1);
+  if (x < cols && y < rows) {
+    output[y * cols + x] = input[y * cols + x];
+  }
+}
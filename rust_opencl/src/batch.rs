@@ -0,0 +1,170 @@
+//! Runs the ops of a job on every image of a directory.
+//!
+//! [`run`] decodes the images on a few threads and keeps up to `in_flight` of them on the
+//! device: each in-flight slot has a queue and pipelines of its own and waits on its reads
+//! alone, so the writes and reads of one image overlap the kernels of the others. The
+//! results are encoded on the decoding threads and saved as grayscale PNG under the same
+//! relative path of the output directory, with `.png` appended (see [`output_path`]); the
+//! histograms of an image, if the job has any, go next to it, e.g.
+//! `sub/a.jpg.histogram.json` for `sub/a.jpg`.
+//!
+//! ```no_run
+//! # let queue = lab_opencl::device::default_queue(None).unwrap();
+//! use lab_opencl::batch::{self, BatchOptions};
+//! use lab_opencl::job::Job;
+//!
+//! let job = Job::load("jobs/blur_rotate.toml")?;
+//! let report = batch::run(&queue, &job.ops, "photos".as_ref(), "out".as_ref(), &BatchOptions::default())?;
+//! println!("{} images, {} failed", report.processed, report.failed.len());
+//! # Ok::<_, lab_opencl::job::JobError>(())
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Mutex;
+
+use lab_common::lru::Lru;
+use ocl::Queue;
+
+use crate::job::{luma, save_luma, JobError, JobOutput, JobPipeline, Op};
+
+// the options, the listing and the output paths are shared with the other backend
+pub use lab_common::batch::{images, output_path, BatchOptions, BatchReport};
+
+/// Pipelines each in-flight slot keeps for images of different sizes.
+const MAX_PIPELINES: usize = 4;
+
+/// A decoded image, `cols x rows` single channel.
+type Decoded = Result<(u32, u32, Vec<f32>), String>;
+
+/// Run `ops` on the images under `input_dir` and save the results under `output_dir`.
+///
+/// The in-flight queues are created on the context and device of `queue`. An image that
+/// can not be read or saved is reported and skipped; a device error stops the batch. Images
+/// of different sizes get a pipeline each; a slot drops its least recently used one beyond
+/// `MAX_PIPELINES` sizes. The `ops` are those of a [`Job`](crate::job::Job) that passed
+/// [`validate`](crate::job::Job::validate).
+pub fn run(
+    queue: &Queue,
+    ops: &[Op],
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &BatchOptions,
+) -> Result<BatchReport, JobError> {
+    let queues = (0..options.in_flight.max(1))
+        .map(|_| Queue::new(&queue.context(), queue.device(), None))
+        .collect::<ocl::Result<Vec<_>>>()
+        .map_err(JobError::device)?;
+    let files = images(input_dir, output_dir);
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let processed = AtomicUsize::new(0);
+    let failed = Mutex::new(Vec::new());
+    let (decoded_sender, decoded) = sync_channel::<(PathBuf, Decoded)>(options.in_flight);
+    let (done_sender, done) = sync_channel::<(PathBuf, JobOutput)>(options.in_flight);
+    let (decoded, done) = (Mutex::new(decoded), Mutex::new(done));
+
+    let results = std::thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            let (files, next, stop) = (&files, &next, &stop);
+            let sender = decoded_sender.clone();
+            scope.spawn(move || {
+                while let Some(relative) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    sender
+                        .send((relative.clone(), decode(&input_dir.join(relative))))
+                        .unwrap();
+                }
+            });
+
+            let (done, processed, failed) = (&done, &processed, &failed);
+            scope.spawn(move || loop {
+                let Ok((relative, output)) = done.lock().unwrap().recv() else {
+                    break;
+                };
+                match save(output_dir, &relative, &output) {
+                    Ok(()) => {
+                        processed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(err) => failed.lock().unwrap().push((relative, err)),
+                }
+            });
+        }
+        drop(decoded_sender);
+
+        let slots: Vec<_> = queues
+            .into_iter()
+            .map(|queue| {
+                let (decoded, done, stop, failed) = (&decoded, done_sender.clone(), &stop, &failed);
+                scope.spawn(move || {
+                    let result = run_slot(&queue, ops, decoded, &done, failed);
+                    if result.is_err() {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    // the other slots and the decoders must not block on this one
+                    while decoded.lock().unwrap().recv().is_ok() {}
+                    result
+                })
+            })
+            .collect();
+        drop(done_sender);
+        slots
+            .into_iter()
+            .map(|slot| slot.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    results.into_iter().collect::<Result<(), JobError>>()?;
+    let mut failed = failed.into_inner().unwrap();
+    failed.sort();
+    Ok(BatchReport {
+        processed: processed.into_inner(),
+        failed,
+    })
+}
+
+fn decode(path: &Path) -> Decoded {
+    let image = image::open(path).map_err(|err| err.to_string())?;
+    let (cols, rows) = (image.width(), image.height());
+    Ok((cols, rows, luma(image)))
+}
+
+fn save(output_dir: &Path, relative: &Path, output: &JobOutput) -> Result<(), String> {
+    lab_common::batch::save(output_dir, relative, &output.histograms, |path| {
+        save_luma(path, output).map_err(|err| err.to_string())
+    })
+}
+
+/// Run the decoded images one after the other on `queue` and pass the results to the
+/// encoders.
+fn run_slot(
+    queue: &Queue,
+    ops: &[Op],
+    decoded: &Mutex<Receiver<(PathBuf, Decoded)>>,
+    done: &SyncSender<(PathBuf, JobOutput)>,
+    failed: &Mutex<Vec<(PathBuf, String)>>,
+) -> Result<(), JobError> {
+    let mut pipelines = Lru::new(MAX_PIPELINES);
+    loop {
+        // the lock is released before the image is run
+        let Ok((relative, decoded)) = decoded.lock().unwrap().recv() else {
+            return Ok(());
+        };
+        let (cols, rows, image) = match decoded {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                failed.lock().unwrap().push((relative, err));
+                continue;
+            }
+        };
+        let pipeline = pipelines
+            .get_or_try_insert_with((cols, rows), || JobPipeline::new(queue, ops, cols, rows))
+            .map_err(JobError::device)?;
+        let outputs = pipeline.pipeline.run(&image).map_err(JobError::device)?;
+        // the encoders only stop when the batch does
+        done.send((relative, pipeline.output(&outputs))).ok();
+    }
+}
@@ -0,0 +1,247 @@
+use std::marker::PhantomData;
+
+use ocl::{Buffer, Kernel, OclPrm, Program, Queue};
+
+use crate::element::{check_support, element_define, with_extensions, Float};
+use crate::error::check_len;
+use crate::{program, watch};
+
+/// Work-group size of the BLAS kernels.
+pub const WORK_GROUP_SIZE: usize = 256;
+/// Upper bound of work-groups for the element-wise kernels (they loop over the rest).
+const MAX_WORK_GROUPS: usize = 65535;
+/// Number of work-groups of the first reduction stage, i.e. the length of the partials.
+const REDUCE_WORK_GROUPS: usize = 256;
+
+/// BLAS level-1 operations on device-resident float buffers, `f32` unless specified.
+///
+/// `Blas::<f64>` needs a device with `cl_khr_fp64`.
+///
+/// All kernels are enqueued on the same in-order queue, so the calls can be chained without
+/// reading back in between. The reductions keep the partial sums on the device and only the
+/// final scalar is read back.
+pub struct Blas<T: Float = f32> {
+    program: Program,
+    queue: Queue,
+    element: PhantomData<T>,
+}
+
+impl<T: Float> Blas<T> {
+    pub fn new(queue: &Queue) -> ocl::Result<Self> {
+        check_support::<T>(&queue.device())?;
+        let program = program::build(
+            queue,
+            "kernels/blas1.cl",
+            &with_extensions(
+                &watch::source("kernels/blas1.cl", include_str!("../kernels/blas1.cl")),
+                &[T::EXTENSION],
+            ),
+            &element_define::<T>(),
+        )?;
+
+        Ok(Self {
+            program,
+            queue: queue.clone(),
+            element: PhantomData,
+        })
+    }
+
+    /// y = alpha * x + y
+    pub fn axpy(&self, alpha: T, x: &Buffer<T>, y: &Buffer<T>) -> ocl::Result<()> {
+        check_len("vector length", x.len(), y.len())?;
+        let n = y.len() as i32;
+        let kernel = self
+            .kernel("axpy", elementwise_size(y.len()))
+            .arg(&alpha)
+            .arg(x)
+            .arg(y)
+            .arg(&n)
+            .build()?;
+        unsafe { kernel.enq() }
+    }
+
+    /// x = alpha * x
+    pub fn scal(&self, alpha: T, x: &Buffer<T>) -> ocl::Result<()> {
+        let n = x.len() as i32;
+        let kernel = self
+            .kernel("scal", elementwise_size(x.len()))
+            .arg(&alpha)
+            .arg(x)
+            .arg(&n)
+            .build()?;
+        unsafe { kernel.enq() }
+    }
+
+    /// out = a * b, element-wise.
+    pub fn mul(&self, a: &Buffer<T>, b: &Buffer<T>, out: &Buffer<T>) -> ocl::Result<()> {
+        check_len("vector length", a.len(), b.len())?;
+        check_len("vector length", a.len(), out.len())?;
+        let n = out.len() as i32;
+        let kernel = self
+            .kernel("mul", elementwise_size(out.len()))
+            .arg(a)
+            .arg(b)
+            .arg(out)
+            .arg(&n)
+            .build()?;
+        unsafe { kernel.enq() }
+    }
+
+    /// y = x
+    pub fn copy(&self, x: &Buffer<T>, y: &Buffer<T>) -> ocl::Result<()> {
+        check_len("vector length", x.len(), y.len())?;
+        let n = y.len() as i32;
+        let kernel = self
+            .kernel("copy", elementwise_size(y.len()))
+            .arg(x)
+            .arg(y)
+            .arg(&n)
+            .build()?;
+        unsafe { kernel.enq() }
+    }
+
+    /// Dot product of `x` and `y`, left on the device as a one-element buffer.
+    pub fn dot_device(&self, x: &Buffer<T>, y: &Buffer<T>) -> ocl::Result<Buffer<T>> {
+        check_len("vector length", x.len(), y.len())?;
+        let n = x.len() as i32;
+        let partials = self.buffer::<T>(REDUCE_WORK_GROUPS)?;
+        let kernel = self
+            .kernel("dot_partial", REDUCE_WORK_GROUPS * WORK_GROUP_SIZE)
+            .arg(x)
+            .arg(y)
+            .arg(&n)
+            .arg(&partials)
+            .build()?;
+        unsafe { kernel.enq()? };
+        self.sum_final(&partials, false)
+    }
+
+    /// Sum of the elements of `x`, left on the device as a one-element buffer.
+    pub fn sum_device(&self, x: &Buffer<T>) -> ocl::Result<Buffer<T>> {
+        let partials = self.reduce_partial("sum_partial", x)?;
+        self.sum_final(&partials, false)
+    }
+
+    /// Euclidean norm of `x`, left on the device as a one-element buffer.
+    pub fn nrm2_device(&self, x: &Buffer<T>) -> ocl::Result<Buffer<T>> {
+        let partials = self.reduce_partial("sumsq_partial", x)?;
+        self.sum_final(&partials, true)
+    }
+
+    /// Sum of the absolute values of `x`, left on the device as a one-element buffer.
+    pub fn asum_device(&self, x: &Buffer<T>) -> ocl::Result<Buffer<T>> {
+        let partials = self.reduce_partial("asum_partial", x)?;
+        self.sum_final(&partials, false)
+    }
+
+    /// Index of the first element with the largest absolute value, left on the device.
+    pub fn iamax_device(&self, x: &Buffer<T>) -> ocl::Result<Buffer<u32>> {
+        let n = x.len() as i32;
+        let partials = self.buffer::<T>(REDUCE_WORK_GROUPS)?;
+        let partial_idx = self.buffer::<u32>(REDUCE_WORK_GROUPS)?;
+        let result_idx = self.buffer::<u32>(1)?;
+
+        let kernel = self
+            .kernel("iamax_partial", REDUCE_WORK_GROUPS * WORK_GROUP_SIZE)
+            .arg(x)
+            .arg(&n)
+            .arg(&partials)
+            .arg(&partial_idx)
+            .build()?;
+        unsafe { kernel.enq()? };
+
+        let kernel = self
+            .kernel("iamax_final", WORK_GROUP_SIZE)
+            .arg(&partials)
+            .arg(&partial_idx)
+            .arg(&(REDUCE_WORK_GROUPS as i32))
+            .arg(&result_idx)
+            .build()?;
+        unsafe { kernel.enq()? };
+
+        Ok(result_idx)
+    }
+
+    /// Dot product of `x` and `y`.
+    pub fn dot(&self, x: &Buffer<T>, y: &Buffer<T>) -> ocl::Result<T> {
+        read_scalar(&self.dot_device(x, y)?)
+    }
+
+    /// Sum of the elements of `x`.
+    pub fn sum(&self, x: &Buffer<T>) -> ocl::Result<T> {
+        read_scalar(&self.sum_device(x)?)
+    }
+
+    /// Euclidean norm of `x`.
+    pub fn nrm2(&self, x: &Buffer<T>) -> ocl::Result<T> {
+        read_scalar(&self.nrm2_device(x)?)
+    }
+
+    /// Sum of the absolute values of `x`.
+    pub fn asum(&self, x: &Buffer<T>) -> ocl::Result<T> {
+        read_scalar(&self.asum_device(x)?)
+    }
+
+    /// Index of the first element with the largest absolute value, `None` for an empty buffer.
+    pub fn iamax(&self, x: &Buffer<T>) -> ocl::Result<Option<u32>> {
+        if x.len() == 0 {
+            return Ok(None);
+        }
+        read_scalar(&self.iamax_device(x)?).map(Some)
+    }
+
+    fn reduce_partial(&self, name: &str, x: &Buffer<T>) -> ocl::Result<Buffer<T>> {
+        let n = x.len() as i32;
+        let partials = self.buffer::<T>(REDUCE_WORK_GROUPS)?;
+        let kernel = self
+            .kernel(name, REDUCE_WORK_GROUPS * WORK_GROUP_SIZE)
+            .arg(x)
+            .arg(&n)
+            .arg(&partials)
+            .build()?;
+        unsafe { kernel.enq()? };
+        Ok(partials)
+    }
+
+    fn sum_final(&self, partials: &Buffer<T>, take_sqrt: bool) -> ocl::Result<Buffer<T>> {
+        let result = self.buffer::<T>(1)?;
+        let kernel = self
+            .kernel("sum_final", WORK_GROUP_SIZE)
+            .arg(partials)
+            .arg(&(partials.len() as i32))
+            .arg(&result)
+            .arg(&(take_sqrt as i32))
+            .build()?;
+        unsafe { kernel.enq()? };
+        Ok(result)
+    }
+
+    fn kernel(&self, name: &str, global_work_size: usize) -> ocl::builders::KernelBuilder<'_> {
+        let mut builder = Kernel::builder();
+        builder
+            .program(&self.program)
+            .name(name)
+            .queue(self.queue.clone())
+            .global_work_size(global_work_size)
+            .local_work_size(WORK_GROUP_SIZE);
+        builder
+    }
+
+    fn buffer<E: OclPrm>(&self, len: usize) -> ocl::Result<Buffer<E>> {
+        Buffer::<E>::builder()
+            .queue(self.queue.clone())
+            .len(len)
+            .build()
+    }
+}
+
+/// Global work size of an element-wise kernel over `n` elements.
+fn elementwise_size(n: usize) -> usize {
+    n.div_ceil(WORK_GROUP_SIZE).clamp(1, MAX_WORK_GROUPS) * WORK_GROUP_SIZE
+}
+
+fn read_scalar<T: OclPrm>(buffer: &Buffer<T>) -> ocl::Result<T> {
+    let mut value = vec![T::default(); 1];
+    buffer.read(&mut value).enq()?;
+    Ok(value[0])
+}
@@ -0,0 +1,177 @@
+//! What each OpenCL device can do, as text or JSON, e.g. to pick a device for a job.
+//!
+//! ```no_run
+//! for device in lab_opencl::capabilities::report()? {
+//!     println!("{device}");
+//! }
+//! println!("{}", serde_json::to_string_pretty(&lab_opencl::capabilities::report()?).unwrap());
+//! # Ok::<_, ocl::Error>(())
+//! ```
+
+use std::fmt;
+
+use ocl::enums::{DeviceInfo, DeviceInfoResult};
+use ocl::{Device, Platform};
+use serde::Serialize;
+
+use crate::device::{device_type, list_devices};
+
+/// The value of a device info query, e.g. `info!(device, MaxComputeUnits)`.
+macro_rules! info {
+    ($device:expr, $info:ident) => {
+        match $device.info(DeviceInfo::$info)? {
+            DeviceInfoResult::$info(value) => value,
+            other => return Err(format!("unexpected device info: {other:?}").into()),
+        }
+    };
+}
+
+/// The capabilities of one device, with the sizes in bytes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceCapabilities {
+    pub platform: String,
+    pub platform_version: String,
+    pub name: String,
+    pub vendor: String,
+    /// E.g. `GPU` or `CPU`.
+    pub device_type: String,
+    pub version: String,
+    pub driver_version: String,
+    pub opencl_c_version: String,
+    pub compute_units: u32,
+    pub max_clock_mhz: u32,
+    pub max_work_group_size: usize,
+    pub max_work_item_sizes: Vec<usize>,
+    pub global_mem_size: u64,
+    pub max_mem_alloc_size: u64,
+    pub local_mem_size: u64,
+    pub max_constant_buffer_size: u64,
+    pub image_support: bool,
+    /// Width and height.
+    pub image2d_max: [usize; 2],
+    /// Width, height and depth.
+    pub image3d_max: [usize; 3],
+    /// `cl_khr_fp64` is supported.
+    pub fp64: bool,
+    /// `cl_khr_fp16` is supported.
+    pub fp16: bool,
+    pub extensions: Vec<String>,
+}
+
+impl DeviceCapabilities {
+    pub fn query(platform: Platform, device: Device) -> ocl::Result<Self> {
+        let extensions: Vec<String> = info!(device, Extensions)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        let has = |extension: &str| extensions.iter().any(|e| e == extension);
+        Ok(Self {
+            platform: platform.name()?,
+            platform_version: platform.version()?,
+            name: device.name()?,
+            vendor: device.vendor()?,
+            device_type: device_type(&device),
+            version: device.info(DeviceInfo::Version)?.to_string(),
+            driver_version: info!(device, DriverVersion),
+            opencl_c_version: info!(device, OpenclCVersion),
+            compute_units: info!(device, MaxComputeUnits),
+            max_clock_mhz: info!(device, MaxClockFrequency),
+            max_work_group_size: info!(device, MaxWorkGroupSize),
+            max_work_item_sizes: info!(device, MaxWorkItemSizes),
+            global_mem_size: info!(device, GlobalMemSize),
+            max_mem_alloc_size: info!(device, MaxMemAllocSize),
+            local_mem_size: info!(device, LocalMemSize),
+            max_constant_buffer_size: info!(device, MaxConstantBufferSize),
+            image_support: info!(device, ImageSupport),
+            image2d_max: [
+                info!(device, Image2dMaxWidth),
+                info!(device, Image2dMaxHeight),
+            ],
+            image3d_max: [
+                info!(device, Image3dMaxWidth),
+                info!(device, Image3dMaxHeight),
+                info!(device, Image3dMaxDepth),
+            ],
+            fp64: has("cl_khr_fp64"),
+            fp16: has("cl_khr_fp16"),
+            extensions,
+        })
+    }
+}
+
+/// The capabilities of every device of every platform.
+pub fn report() -> ocl::Result<Vec<DeviceCapabilities>> {
+    list_devices()?
+        .into_iter()
+        .map(|(platform, device)| DeviceCapabilities::query(platform, device))
+        .collect()
+}
+
+/// `bytes` in the largest binary unit it has a whole number of, e.g. `48 KiB`.
+fn size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let unit = (0..units.len())
+        .rev()
+        .find(|&unit| bytes >> (10 * unit) > 0 && bytes.is_multiple_of(1 << (10 * unit)))
+        .unwrap_or(0);
+    format!("{} {}", bytes >> (10 * unit), units[unit])
+}
+
+impl fmt::Display for DeviceCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |supported| if supported { "yes" } else { "no" };
+        writeln!(f, "{} ({}, {})", self.name, self.platform, self.device_type)?;
+        writeln!(f, "  vendor          {}", self.vendor)?;
+        writeln!(
+            f,
+            "  version         {}, {}, driver {}",
+            self.version, self.opencl_c_version, self.driver_version
+        )?;
+        writeln!(f, "  platform        {}", self.platform_version)?;
+        writeln!(
+            f,
+            "  compute units   {} at {} MHz",
+            self.compute_units, self.max_clock_mhz
+        )?;
+        writeln!(
+            f,
+            "  work-group      {} items, {:?} per dimension",
+            self.max_work_group_size, self.max_work_item_sizes
+        )?;
+        writeln!(
+            f,
+            "  memory          global {}, allocation {}, local {}, constant {}",
+            size(self.global_mem_size),
+            size(self.max_mem_alloc_size),
+            size(self.local_mem_size),
+            size(self.max_constant_buffer_size)
+        )?;
+        if self.image_support {
+            let ([w2, h2], [w3, h3, d3]) = (self.image2d_max, self.image3d_max);
+            writeln!(f, "  images          2D {w2}x{h2}, 3D {w3}x{h3}x{d3}")?;
+        } else {
+            writeln!(f, "  images          no")?;
+        }
+        writeln!(
+            f,
+            "  fp64 / fp16     {} / {}",
+            yes_no(self.fp64),
+            yes_no(self.fp16)
+        )?;
+        write!(f, "  extensions      {}", self.extensions.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::size;
+
+    #[test]
+    pub fn test_size() {
+        assert_eq!(size(0), "0 B");
+        assert_eq!(size(1000), "1000 B");
+        assert_eq!(size(48 * 1024), "48 KiB");
+        assert_eq!(size(1536 * 1024 * 1024), "1536 MiB");
+        assert_eq!(size(8 << 30), "8 GiB");
+    }
+}
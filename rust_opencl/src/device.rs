@@ -0,0 +1,90 @@
+//! Device selection that falls back to CPU implementations (e.g. PoCL) when there is no GPU,
+//! so the examples and tests also run on headless CI machines.
+
+use ocl::enums::{DeviceInfo, DeviceInfoResult};
+use ocl::{flags, Context, Device, Platform, Queue};
+
+/// Environment variable selecting a device by a case-insensitive substring of its name.
+pub const DEVICE_ENV: &str = "OCL_DEVICE";
+
+/// Find a device: the one named by `OCL_DEVICE` if set, else the first GPU, else the first
+/// CPU device of any platform.
+pub fn select_device() -> ocl::Result<(Platform, Device)> {
+    let platforms: Vec<Platform> = ocl::core::get_platform_ids()?
+        .into_iter()
+        .map(Platform::new)
+        .collect();
+    // a platform without devices of a type reports an error, treat it as an empty list
+    let devices = |device_type| {
+        platforms.iter().flat_map(move |&platform| {
+            Device::list(platform, Some(device_type))
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |device| (platform, device))
+        })
+    };
+
+    if let Ok(wanted) = std::env::var(DEVICE_ENV) {
+        let wanted = wanted.to_lowercase();
+        return devices(flags::DEVICE_TYPE_ALL)
+            .find(|(_, device)| {
+                device
+                    .name()
+                    .is_ok_and(|name| name.to_lowercase().contains(&wanted))
+            })
+            .ok_or_else(|| format!("no OpenCL device matches {DEVICE_ENV}={wanted}").into());
+    }
+    devices(flags::DEVICE_TYPE_GPU)
+        .chain(devices(flags::DEVICE_TYPE_CPU))
+        .next()
+        .ok_or_else(|| "no OpenCL GPU or CPU device available".to_string().into())
+}
+
+/// A queue on the [`select_device`] device, in a context of its own.
+pub fn default_queue(properties: Option<flags::CommandQueueProperties>) -> ocl::Result<Queue> {
+    let (platform, device) = select_device()?;
+    let context = Context::builder()
+        .platform(platform)
+        .devices(device)
+        .build()?;
+    Queue::new(&context, device, properties)
+}
+
+/// Every device of every platform, e.g. to list them.
+pub fn list_devices() -> ocl::Result<Vec<(Platform, Device)>> {
+    let mut devices = Vec::new();
+    for platform in ocl::core::get_platform_ids()?
+        .into_iter()
+        .map(Platform::new)
+    {
+        for device in Device::list(platform, Some(flags::DEVICE_TYPE_ALL)).unwrap_or_default() {
+            devices.push((platform, device));
+        }
+    }
+    Ok(devices)
+}
+
+/// The type of a device, e.g. `GPU` or `CPU`.
+pub fn device_type(device: &Device) -> String {
+    match device.info(DeviceInfo::Type) {
+        Ok(DeviceInfoResult::Type(device_type)) => format!("{device_type:?}"),
+        _ => "unknown type".to_string(),
+    }
+}
+
+/// Name, platform and type of a device, e.g. for test logs.
+pub fn describe(queue: &Queue) -> String {
+    let device = queue.device();
+    let platform = queue
+        .context()
+        .platform()
+        .ok()
+        .flatten()
+        .and_then(|platform| platform.name().ok())
+        .unwrap_or_default();
+    format!(
+        "{} ({platform}, {})",
+        device.name().unwrap_or_default(),
+        device_type(&device)
+    )
+}
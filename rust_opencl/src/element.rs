@@ -0,0 +1,167 @@
+use ocl::enums::{DeviceInfo, DeviceInfoResult, ImageChannelDataType};
+use ocl::Device;
+
+/// Element types that can be stored in a buffer and used by the kernels.
+///
+/// The kernels refer to the element type as `T`, which is defined at build time with
+/// `-D T=<CL_TYPE>`. Types behind a device extension also need [`with_extensions`] on the
+/// source to enable it.
+pub trait Element: ocl::OclPrm {
+    /// Name of the matching OpenCL C type.
+    const CL_TYPE: &'static str;
+    /// Device extension required to use the type in a kernel.
+    const EXTENSION: Option<&'static str> = None;
+}
+
+impl Element for f32 {
+    const CL_TYPE: &'static str = "float";
+}
+
+impl Element for u32 {
+    const CL_TYPE: &'static str = "uint";
+}
+
+impl Element for i32 {
+    const CL_TYPE: &'static str = "int";
+}
+
+impl Element for f64 {
+    const CL_TYPE: &'static str = "double";
+    const EXTENSION: Option<&'static str> = Some("cl_khr_fp64");
+}
+
+/// Half precision element, a wrapper because `ocl::OclPrm` can not be implemented for
+/// `half::f16` here.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct F16(pub half::f16);
+
+unsafe impl ocl::OclPrm for F16 {}
+
+impl Element for F16 {
+    const CL_TYPE: &'static str = "half";
+    const EXTENSION: Option<&'static str> = Some("cl_khr_fp16");
+}
+
+impl From<f32> for F16 {
+    fn from(value: f32) -> Self {
+        Self(half::f16::from_f32(value))
+    }
+}
+
+impl From<F16> for f32 {
+    fn from(value: F16) -> Self {
+        value.0.to_f32()
+    }
+}
+
+/// Element types of the image kernels, the channel type of the single channel images.
+///
+/// The image kernels read the pixels as `float4`, compute in `float` and round to the
+/// nearest value when writing integer pixels; [`pixel_defines`] selects the image functions
+/// for the type. `F16` images are read and written as `float` and need no `cl_khr_fp16`.
+/// `f64` has no image channel type.
+pub trait Pixel: Element {
+    const CHANNEL_TYPE: ImageChannelDataType;
+    /// Define selecting the image functions in `kernels/pixel.cl`.
+    const PIXEL_DEFINE: &'static str;
+    /// Pixel values the histogram bins span from zero: `[0, 1]` for the float types and the
+    /// 8-bit values `0..256` for the integer ones.
+    const HIST_RANGE: f32;
+}
+
+impl Pixel for f32 {
+    const CHANNEL_TYPE: ImageChannelDataType = ImageChannelDataType::Float;
+    const PIXEL_DEFINE: &'static str = "PIXEL_FLOAT";
+    const HIST_RANGE: f32 = 1.0;
+}
+
+impl Pixel for F16 {
+    const CHANNEL_TYPE: ImageChannelDataType = ImageChannelDataType::HalfFloat;
+    const PIXEL_DEFINE: &'static str = "PIXEL_FLOAT";
+    const HIST_RANGE: f32 = 1.0;
+}
+
+impl Pixel for u32 {
+    const CHANNEL_TYPE: ImageChannelDataType = ImageChannelDataType::UnsignedInt32;
+    const PIXEL_DEFINE: &'static str = "PIXEL_UINT";
+    const HIST_RANGE: f32 = 256.0;
+}
+
+impl Pixel for i32 {
+    const CHANNEL_TYPE: ImageChannelDataType = ImageChannelDataType::SignedInt32;
+    const PIXEL_DEFINE: &'static str = "PIXEL_INT";
+    const HIST_RANGE: f32 = 256.0;
+}
+
+/// Build options of the image kernels for the pixel type.
+pub fn pixel_defines<T: Pixel>() -> String {
+    format!("-D {} -D HIST_RANGE={:?}f", T::PIXEL_DEFINE, T::HIST_RANGE)
+}
+
+/// Floating point element types supported by the BLAS kernels.
+pub trait Float: Element {
+    /// Widen to `f64` for host-side comparisons.
+    fn to_f64(self) -> f64;
+}
+
+impl Float for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Float for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Build option that defines `T` as the element type.
+pub fn element_define<T: Element>() -> String {
+    format!("-D T={}", T::CL_TYPE)
+}
+
+/// Prepend the pragmas enabling the extensions of the given element types to the source.
+pub fn with_extensions(source: &str, extensions: &[Option<&str>]) -> String {
+    let mut pragmas = String::new();
+    for extension in extensions.iter().flatten() {
+        let pragma = format!("#pragma OPENCL EXTENSION {extension} : enable\n");
+        if !pragmas.contains(&pragma) {
+            pragmas += &pragma;
+        }
+    }
+    pragmas + source
+}
+
+/// Fail unless the device supports the extension the element type needs.
+pub fn check_support<T: Element>(device: &Device) -> ocl::Result<()> {
+    let Some(extension) = T::EXTENSION else {
+        return Ok(());
+    };
+    match device.info(DeviceInfo::Extensions)? {
+        DeviceInfoResult::Extensions(extensions)
+            if extensions.split_whitespace().any(|e| e == extension) =>
+        {
+            Ok(())
+        }
+        DeviceInfoResult::Extensions(_) => {
+            Err(format!("the device does not support {} ({extension})", T::CL_TYPE).into())
+        }
+        other => Err(format!("unexpected device info: {other:?}").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_extensions, Element, F16};
+
+    #[test]
+    pub fn test_with_extensions() {
+        let source = with_extensions("kernel", &[F16::EXTENSION, f32::EXTENSION, F16::EXTENSION]);
+        assert_eq!(
+            source,
+            "#pragma OPENCL EXTENSION cl_khr_fp16 : enable\nkernel"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! Errors with the build log of OpenCL programs parsed into diagnostics.
+//!
+//! A failed build in `ocl` is one long string. [`BuildError`] keeps the log of the device,
+//! parses the compiler diagnostics (`file:line:column: error: message`, as printed by the
+//! clang based compilers) and prints each one with its source line underlined.
+
+use std::ffi::CString;
+use std::fmt;
+
+use ocl::enums::{ProgramBuildInfo, ProgramBuildInfoResult};
+use ocl::{Context, Device};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An OpenCL error, or a program that failed to build.
+#[derive(Debug)]
+pub enum Error {
+    Ocl(ocl::Error),
+    Build(BuildError),
+}
+
+/// An error unless `actual == expected`, e.g. for the lengths of two vectors.
+pub(crate) fn check_len(what: &str, expected: usize, actual: usize) -> ocl::Result<()> {
+    if actual == expected {
+        return Ok(());
+    }
+    Err(format!("{what} mismatch: expected {expected}, got {actual}").into())
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Ocl(err) => write!(f, "{err}"),
+            Error::Build(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ocl::Error> for Error {
+    fn from(err: ocl::Error) -> Self {
+        Error::Ocl(err)
+    }
+}
+
+impl From<BuildError> for Error {
+    fn from(err: BuildError) -> Self {
+        Error::Build(err)
+    }
+}
+
+/// For the APIs returning `ocl::Result`; the diagnostics are kept as text.
+impl From<Error> for ocl::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Ocl(err) => err,
+            Error::Build(err) => err.to_string().into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+/// One compiler message; `line` and `column` are 1-based and refer to `file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The file as the compiler names it, e.g. `<source>` for the compiled source, the file
+    /// of a `#line` directive in it or the path of a header it includes.
+    pub file: String,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// The message refers to the compiled source rather than a header or a compiler built-in.
+    /// The compilers give the source different names, e.g. `<source>`, `<kernel>` or PoCL's
+    /// `.../input.cl`, so only the others are recognized.
+    pub fn in_source(&self) -> bool {
+        let header = [".h", ".hpp", ".inc"]
+            .iter()
+            .any(|extension| self.file.ends_with(extension));
+        let built_in = matches!(
+            self.file.as_str(),
+            "<built-in>" | "<command line>" | "<scratch space>"
+        );
+        !(header || built_in)
+    }
+}
+
+/// A program that failed to build on a device.
+#[derive(Debug, Clone)]
+pub struct BuildError {
+    /// The device the program was built for.
+    pub device: String,
+    /// Name of the source in the messages, e.g. `kernels/rotation.cl`.
+    pub name: String,
+    pub source: String,
+    pub log: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl BuildError {
+    pub fn new(device: String, name: &str, source: &str, log: String) -> Self {
+        Self {
+            device,
+            name: name.to_string(),
+            source: source.to_string(),
+            diagnostics: parse_build_log(&log),
+            log,
+        }
+    }
+
+    /// Rebuild the program through the core API, which keeps the program object after a
+    /// failure, and read the device's build log. `None` when the build now succeeds or
+    /// there is no log.
+    pub fn collect(
+        context: &Context,
+        device: Device,
+        name: &str,
+        source: &str,
+        options: &str,
+    ) -> Option<Self> {
+        let src = CString::new(source).ok()?;
+        let options = CString::new(options).ok()?;
+        let program = ocl::core::create_program_with_source(context.as_core(), &[src]).ok()?;
+        if ocl::core::build_program(&program, Some(&[device]), &options, None, None).is_ok() {
+            return None;
+        }
+        let log =
+            match ocl::core::get_program_build_info(&program, device, ProgramBuildInfo::BuildLog) {
+                Ok(ProgramBuildInfoResult::BuildLog(log)) if !log.trim().is_empty() => log,
+                _ => return None,
+            };
+        let device = device
+            .name()
+            .unwrap_or_else(|_| "unknown device".to_string());
+        Some(Self::new(device, name, source, log))
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "failed to build {} for {}", self.name, self.device)?;
+        // an unknown log format is printed as is
+        if self.diagnostics.is_empty() {
+            return write!(f, "{}", self.log.trim_end());
+        }
+
+        let lines = located_lines(&self.source);
+        for diagnostic in &self.diagnostics {
+            // a file named by a `#line` directive, e.g. `kernels/pixel.cl`, keeps its name
+            let named = lines
+                .iter()
+                .any(|&(file, _, _)| file == Some(diagnostic.file.as_str()));
+            let (file, excerpt) = if named {
+                (&diagnostic.file, Some(Some(diagnostic.file.as_str())))
+            } else if diagnostic.in_source() {
+                (&self.name, Some(None))
+            } else {
+                (&diagnostic.file, None)
+            };
+            let location = match diagnostic.column {
+                Some(column) => format!("{file}:{}:{column}", diagnostic.line),
+                None => format!("{file}:{}", diagnostic.line),
+            };
+            writeln!(
+                f,
+                "{location}: {}: {}",
+                diagnostic.severity.name(),
+                diagnostic.message
+            )?;
+
+            // the excerpt only for the lines of the compiled source, not of a header
+            let Some(&(_, _, text)) = excerpt.and_then(|excerpt| {
+                lines
+                    .iter()
+                    .find(|&&(file, line, _)| file == excerpt && line == diagnostic.line)
+            }) else {
+                continue;
+            };
+            let number = diagnostic.line.to_string();
+            let gutter = " ".repeat(number.len());
+            writeln!(f, " {number} | {text}")?;
+            if let Some(column) = diagnostic.column {
+                // underline from the column to the end of the token
+                let start = column.saturating_sub(1).min(text.len());
+                let token = text[start..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(text.len() - start)
+                    .max(1);
+                let indent: String = text[..start]
+                    .chars()
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                writeln!(f, " {gutter} | {indent}{}", "^".repeat(token))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// The lines of `source` with their file and 1-based line as its `#line` directives set
+/// them; the file is `None` before the first directive that names one.
+fn located_lines(source: &str) -> Vec<(Option<&str>, usize, &str)> {
+    let mut file = None;
+    let mut next = 1;
+    let mut lines = Vec::new();
+    for text in source.lines() {
+        let mut directive = text
+            .trim_start()
+            .strip_prefix("#line")
+            .map(str::split_whitespace);
+        if let Some(line) = directive
+            .as_mut()
+            .and_then(|words| words.next()?.parse().ok())
+        {
+            next = line;
+            let name = directive.and_then(|mut words| words.next());
+            if let Some(name) = name.and_then(|name| name.strip_prefix('"')?.strip_suffix('"')) {
+                file = Some(name);
+            }
+            continue;
+        }
+        lines.push((file, next, text));
+        next += 1;
+    }
+    lines
+}
+
+/// Parse the `file:line[:column]: severity: message` lines of a build log; other lines
+/// (source excerpts, summaries) are skipped.
+pub fn parse_build_log(log: &str) -> Vec<Diagnostic> {
+    log.lines().filter_map(parse_diagnostic).collect()
+}
+
+fn parse_diagnostic(line: &str) -> Option<Diagnostic> {
+    let (severity, marker) = [
+        (Severity::Error, ": error: "),
+        (Severity::Error, ": fatal error: "),
+        (Severity::Warning, ": warning: "),
+        (Severity::Note, ": note: "),
+    ]
+    .into_iter()
+    .find(|(_, marker)| line.contains(marker))?;
+    let (location, message) = line.split_once(marker)?;
+
+    // the file name may contain ':', so the numbers are taken from the end
+    let mut numbers = location
+        .rsplit(':')
+        .map_while(|part| part.trim().parse::<usize>().ok())
+        .collect::<Vec<_>>();
+    numbers.reverse();
+    let (line, column) = match numbers[..] {
+        [.., line, column] => (line, Some(column)),
+        [line] => (line, None),
+        [] => return None,
+    };
+    let file = location
+        .rsplitn(numbers.len().min(2) + 1, ':')
+        .last()
+        .unwrap_or_default();
+    Some(Diagnostic {
+        file: file.trim().to_string(),
+        line,
+        column,
+        severity,
+        message: message.trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_build_log, BuildError, Diagnostic, Severity};
+
+    #[test]
+    pub fn test_parse_build_log() {
+        let log = "<source>:3:5: error: use of undeclared identifier 'y'\n    y = 1;\n    ^\n\
+                   /tmp/pocl/input.cl:1:10: warning: unused variable 'z'\n\
+                   /usr/include/clc/opencl-c.h:3:1: note: candidate function\n\
+                   1 error generated.\n";
+        let diagnostics = parse_build_log(log);
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic {
+                    file: "<source>".to_string(),
+                    line: 3,
+                    column: Some(5),
+                    severity: Severity::Error,
+                    message: "use of undeclared identifier 'y'".to_string(),
+                },
+                Diagnostic {
+                    file: "/tmp/pocl/input.cl".to_string(),
+                    line: 1,
+                    column: Some(10),
+                    severity: Severity::Warning,
+                    message: "unused variable 'z'".to_string(),
+                },
+                Diagnostic {
+                    file: "/usr/include/clc/opencl-c.h".to_string(),
+                    line: 3,
+                    column: Some(1),
+                    severity: Severity::Note,
+                    message: "candidate function".to_string(),
+                },
+            ]
+        );
+
+        let source = "__kernel void k() {\n  int x;\n    y = 1;\n}\n";
+        let err = BuildError::new("cpu".to_string(), "k.cl", source, log.to_string());
+        assert_eq!(err.errors().count(), 1);
+        let text = err.to_string();
+        assert!(text.contains(
+            "k.cl:3:5: error: use of undeclared identifier 'y'\n 3 |     y = 1;\n   |     ^\n"
+        ));
+        // no excerpt of the compiled source for a line of a header
+        assert!(text.ends_with("/usr/include/clc/opencl-c.h:3:1: note: candidate function\n"));
+
+        // the files of the `#line` directives keep their names and lines
+        let source = "#line 1 \"kernels/pixel.cl\"\nfloat4 p;\n#line 1 \"k.cl\"\n  q = 1;\n";
+        let log = "kernels/pixel.cl:1:1: warning: unused 'p'\n\
+                   k.cl:1:3: error: use of undeclared identifier 'q'\n";
+        let text = BuildError::new("cpu".to_string(), "k.cl", source, log.to_string()).to_string();
+        assert!(text.contains("kernels/pixel.cl:1:1: warning: unused 'p'\n 1 | float4 p;\n"));
+        assert!(text.contains("k.cl:1:3: error: use of undeclared identifier 'q'\n 1 |   q = 1;\n"));
+    }
+}
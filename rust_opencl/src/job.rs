@@ -0,0 +1,114 @@
+//! Image processing jobs, see [`lab_common::job`] for the file format, run on the device.
+//!
+//! [`run`] runs the ops of a job as one [`ImagePipeline`] on a gray image.
+
+use std::path::Path;
+
+use image::GenericImageView;
+use ocl::Queue;
+
+pub use lab_common::job::{rebin, Backend, Interp, Job, JobError, Op};
+
+use crate::ops::HIST_BINS;
+use crate::pipeline::{HistogramOutput, ImageOutput, ImagePipeline, Outputs};
+use crate::utils::generate_gaussian_kernel;
+
+// the jobs check their bins against the shared count
+const _: () = assert!(HIST_BINS == lab_common::job::HIST_BINS);
+
+/// The results of a job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobOutput {
+    pub cols: u32,
+    pub rows: u32,
+    /// The single channel image after the last op, row-major.
+    pub image: Vec<f32>,
+    /// The counts of the histogram ops, in order.
+    pub histograms: Vec<Vec<i32>>,
+}
+
+/// Load the input as a gray image, run the ops on the device and save the output, if the job
+/// has one.
+pub fn run(job: &Job, queue: &Queue) -> Result<JobOutput, JobError> {
+    job.validate()?;
+    job.check_backend(Backend::Opencl)?;
+    let img = job.load_input()?;
+    let (cols, rows) = img.dimensions();
+    let image = luma(img);
+
+    let mut pipeline = JobPipeline::new(queue, &job.ops, cols, rows).map_err(JobError::device)?;
+    let outputs = pipeline.pipeline.run(&image).map_err(JobError::device)?;
+    let output = pipeline.output(&outputs);
+    if let Some(path) = &job.output {
+        save_luma(path, &output)?;
+    }
+    Ok(output)
+}
+
+/// The ops of a job as an [`ImagePipeline`] on images of one size.
+pub struct JobPipeline {
+    pub pipeline: ImagePipeline,
+    /// The image after the last op.
+    image: ImageOutput,
+    histograms: Vec<(HistogramOutput, usize)>,
+}
+
+impl JobPipeline {
+    pub fn new(queue: &Queue, ops: &[Op], cols: u32, rows: u32) -> ocl::Result<Self> {
+        let mut pipeline = ImagePipeline::new(queue, cols, rows);
+        let mut histograms = Vec::new();
+        for op in ops {
+            match *op {
+                Op::GaussianBlur { radius, sigma } => {
+                    pipeline.convolution(&generate_gaussian_kernel(radius as i32, sigma))?;
+                }
+                Op::Rotate { degrees, interp } => match interp {
+                    Interp::Bilinear => {
+                        pipeline.rotation(degrees.to_radians())?;
+                    }
+                    Interp::Bicubic => {
+                        pipeline.bicubic_rotation(degrees.to_radians())?;
+                    }
+                },
+                Op::Histogram { bins } => histograms.push((pipeline.histogram()?, bins)),
+            }
+        }
+        let image = pipeline.output();
+        Ok(Self {
+            pipeline,
+            image,
+            histograms,
+        })
+    }
+
+    /// The results in the `outputs` of a run of the pipeline.
+    pub fn output(&self, outputs: &Outputs) -> JobOutput {
+        JobOutput {
+            cols: self.pipeline.cols(),
+            rows: self.pipeline.rows(),
+            image: outputs.image(self.image).to_vec(),
+            histograms: self
+                .histograms
+                .iter()
+                .map(|&(histogram, bins)| rebin(outputs.histogram(histogram), bins))
+                .collect(),
+        }
+    }
+}
+
+/// Convert an image to the single channel input of a [`JobPipeline`].
+pub fn luma(img: image::DynamicImage) -> Vec<f32> {
+    img.into_luma8().iter().map(|&v| v as f32 / 255.0).collect()
+}
+
+/// Save the image of `output` as 8-bit grayscale.
+pub fn save_luma(path: &Path, output: &JobOutput) -> image::ImageResult<()> {
+    let pixels: Vec<u8> = output.image.iter().map(|v| (v * 255.0) as u8).collect();
+    image::save_buffer(
+        path,
+        &pixels,
+        output.cols,
+        output.rows,
+        image::ColorType::L8,
+    )
+}
@@ -1 +1,21 @@
+pub mod batch;
+pub mod blas;
+pub mod capabilities;
+pub mod device;
+pub mod element;
+pub mod error;
+pub mod job;
+pub mod map;
+pub mod ops;
+pub mod pipeline;
+pub mod profile;
+pub mod program;
+pub mod reference;
+pub mod stream;
+pub mod transpose;
+pub mod tune;
 pub mod utils;
+pub mod watch;
+
+// the trace writer is shared with the other backend
+pub use lab_common::trace;
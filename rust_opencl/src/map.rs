@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use ocl::{Buffer, Kernel, Program, Queue};
+
+use crate::element::{check_support, element_define, with_extensions, Element};
+use crate::error::check_len;
+use crate::program;
+
+/// Work-group size of the generated kernels.
+pub const WORK_GROUP_SIZE: usize = 256;
+/// Upper bound of work-groups for one launch (the kernel loops over the rest).
+const MAX_WORK_GROUPS: usize = 65535;
+
+/// Names of the inputs inside an expression, in argument order.
+pub const INPUT_NAMES: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+
+/// Element-wise kernels generated from OpenCL C expression snippets.
+///
+/// The expression sees the input elements as `a`, `b`, `c`, ... and the element index as `i`,
+/// and its value is cast to the output type, e.g. `map2(&x, &y, "a * 2.0f + sin(b)")`.
+/// Each generated program is built once and cached by its types, arity and expression.
+pub struct MapKernels {
+    queue: Queue,
+    cache: RefCell<HashMap<String, Program>>,
+}
+
+impl MapKernels {
+    pub fn new(queue: &Queue) -> Self {
+        Self {
+            queue: queue.clone(),
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// `out[i] = expr(a[i])`
+    pub fn map<T: Element, O: Element>(&self, a: &Buffer<T>, expr: &str) -> ocl::Result<Buffer<O>> {
+        self.zip(&[a], expr)
+    }
+
+    /// `out[i] = expr(a[i], b[i])`
+    pub fn map2<T: Element, O: Element>(
+        &self,
+        a: &Buffer<T>,
+        b: &Buffer<T>,
+        expr: &str,
+    ) -> ocl::Result<Buffer<O>> {
+        self.zip(&[a, b], expr)
+    }
+
+    /// `out[i] = expr(inputs[0][i], inputs[1][i], ...)` for up to 8 inputs of the same length.
+    pub fn zip<T: Element, O: Element>(
+        &self,
+        inputs: &[&Buffer<T>],
+        expr: &str,
+    ) -> ocl::Result<Buffer<O>> {
+        check_arity(inputs.len())?;
+        let len = inputs[0].len();
+        for input in inputs {
+            check_len("vector length", len, input.len())?;
+        }
+
+        let key = format!("{}:{}:{}:{}", T::CL_TYPE, O::CL_TYPE, inputs.len(), expr);
+        let mut cache = self.cache.borrow_mut();
+        let program = match cache.entry(key) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                check_support::<T>(&self.queue.device())?;
+                check_support::<O>(&self.queue.device())?;
+                let program = program::build(
+                    &self.queue,
+                    "generated map kernel",
+                    &with_extensions(
+                        &generate_kernel(inputs.len(), expr),
+                        &[T::EXTENSION, O::EXTENSION],
+                    ),
+                    &format!("{} -D O={}", element_define::<T>(), O::CL_TYPE),
+                )?;
+                entry.insert(program)
+            }
+        };
+
+        let out = Buffer::<O>::builder()
+            .queue(self.queue.clone())
+            .len(len)
+            .build()?;
+
+        let global_work_size =
+            len.div_ceil(WORK_GROUP_SIZE).clamp(1, MAX_WORK_GROUPS) * WORK_GROUP_SIZE;
+        let n = len as i32;
+
+        let mut builder = Kernel::builder();
+        builder
+            .program(program)
+            .name("map")
+            .queue(self.queue.clone())
+            .global_work_size(global_work_size)
+            .local_work_size(WORK_GROUP_SIZE);
+        for input in inputs {
+            builder.arg(*input);
+        }
+        let kernel = builder.arg(&out).arg(&n).build()?;
+
+        unsafe {
+            kernel.enq()?;
+        }
+        Ok(out)
+    }
+
+    /// Number of built programs in the cache.
+    pub fn cached(&self) -> usize {
+        self.cache.borrow().len()
+    }
+}
+
+/// An error unless there are between 1 and 8 inputs, one per name of [`INPUT_NAMES`].
+pub(crate) fn check_arity(arity: usize) -> ocl::Result<()> {
+    if arity == 0 || arity > INPUT_NAMES.len() {
+        return Err(format!("arity must be between 1 and {}", INPUT_NAMES.len()).into());
+    }
+    Ok(())
+}
+
+/// Splice the expression into the element-wise kernel template.
+///
+/// The kernel takes the inputs in order, then the output and the length. `T` and `O` are
+/// defined at build time.
+pub fn generate_kernel(arity: usize, expr: &str) -> String {
+    let inputs = INPUT_NAMES[..arity]
+        .iter()
+        .map(|name| format!("__global const T *in_{name}, "))
+        .collect::<String>();
+    let loads = INPUT_NAMES[..arity]
+        .iter()
+        .map(|name| format!("    T {name} = in_{name}[i];\n"))
+        .collect::<String>();
+
+    format!(
+        "__kernel void map({inputs}__global O *out, int n) {{\n\
+         \x20 for (int i = get_global_id(0); i < n; i += get_global_size(0)) {{\n\
+         {loads}\
+         \x20   out[i] = (O)({expr});\n\
+         \x20 }}\n\
+         }}\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_kernel;
+
+    #[test]
+    pub fn test_generate_kernel() {
+        let source = generate_kernel(2, "a * 2.0f + sin(b)");
+        assert!(source.starts_with(
+            "__kernel void map(__global const T *in_a, __global const T *in_b, __global O *out, int n) {"
+        ));
+        assert!(source.contains("    T b = in_b[i];\n"));
+        assert!(source.contains("    out[i] = (O)(a * 2.0f + sin(b));\n"));
+    }
+}
@@ -0,0 +1,491 @@
+use std::marker::PhantomData;
+
+use ocl::{
+    core::{ImageDescriptor, ImageFormat},
+    enums::{AddressingMode, FilterMode, ImageChannelOrder, MemObjectType},
+    flags, Buffer, Image, Kernel, Program, Queue, Sampler,
+};
+
+use crate::element::{check_support, element_define, pixel_defines, with_extensions, Pixel};
+use crate::error::check_len;
+use crate::tune::Tuner;
+use crate::watch;
+
+/// Bins of the histogram kernels, over `[0, HIST_RANGE]` of the pixel type, see
+/// [`Pixel::HIST_RANGE`].
+pub const HIST_BINS: usize = 256;
+/// Work-items of the histogram kernel, in work-groups of `HIST_LOCAL_SIZE`.
+const HIST_GLOBAL_SIZE: usize = 1024;
+const HIST_LOCAL_SIZE: usize = 64;
+
+/// c = a + b with the `add_vectors` kernel, on device buffers of a fixed length.
+///
+/// Uploading, enqueueing and downloading are separate steps so they can be timed apart.
+pub struct VectorAdd {
+    kernel: Kernel,
+    a: Buffer<f32>,
+    b: Buffer<f32>,
+    c: Buffer<f32>,
+}
+
+impl VectorAdd {
+    pub fn new(queue: &Queue, len: usize) -> ocl::Result<Self> {
+        let buffer = || {
+            Buffer::<f32>::builder()
+                .queue(queue.clone())
+                .len(len)
+                .build()
+        };
+        let (a, b, c) = (buffer()?, buffer()?, buffer()?);
+
+        let program = program(
+            queue,
+            "kernels/vecadd_kernel.cl",
+            include_str!("../kernels/vecadd_kernel.cl"),
+        )?;
+        let kernel = Kernel::builder()
+            .program(&program)
+            .name("add_vectors")
+            .queue(queue.clone())
+            .global_work_size(len)
+            .arg(&a)
+            .arg(&b)
+            .arg(&c)
+            .build()?;
+        Ok(Self { kernel, a, b, c })
+    }
+
+    pub fn len(&self) -> usize {
+        self.c.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.c.len() == 0
+    }
+
+    pub fn upload(&self, a: &[f32], b: &[f32]) -> ocl::Result<()> {
+        self.a.write(a).enq()?;
+        self.b.write(b).enq()
+    }
+
+    pub fn enqueue(&self) -> ocl::Result<()> {
+        unsafe { self.kernel.enq() }
+    }
+
+    pub fn download(&self) -> ocl::Result<Vec<f32>> {
+        let mut out = vec![0f32; self.len()];
+        self.c.read(&mut out).enq()?;
+        Ok(out)
+    }
+}
+
+/// c = a + b with the `add_vectors` kernel.
+pub fn vector_add(queue: &Queue, a: &[f32], b: &[f32]) -> ocl::Result<Vec<f32>> {
+    check_len("vector length", a.len(), b.len())?;
+    let op = VectorAdd::new(queue, a.len())?;
+    op.upload(a, b)?;
+    op.enqueue()?;
+    op.download()
+}
+
+/// A kernel reading a single channel input image and writing a single channel output
+/// image (`cols x rows`, row-major) of a fixed size and pixel type, `f32` unless specified,
+/// the first two kernel arguments. They are set by [`ImagePass::enqueue`], so the kernel
+/// runs on any pair of images of that size and type.
+pub struct ImagePass<T: Pixel = f32> {
+    name: String,
+    kernel: Kernel,
+    cols: u32,
+    rows: u32,
+    pixel: PhantomData<T>,
+}
+
+impl<T: Pixel> ImagePass<T> {
+    /// Convolution with a square filter; the sampler clamps the coordinates to the edge of
+    /// the image.
+    pub fn convolution(queue: &Queue, cols: u32, rows: u32, filter: &[f32]) -> ocl::Result<Self> {
+        let filter_size = (filter.len() as f64).sqrt() as i32;
+        let filter_buffer = Buffer::<f32>::builder()
+            .queue(queue.clone())
+            .flags(flags::MEM_READ_ONLY)
+            .len(filter.len())
+            .copy_host_slice(filter)
+            .build()?;
+        let sampler = Sampler::new(
+            &queue.context(),
+            false,
+            AddressingMode::ClampToEdge,
+            FilterMode::Nearest,
+        )?;
+
+        let program = image_program::<T>(
+            queue,
+            "kernels/convolution.cl",
+            include_str!("../kernels/convolution.cl"),
+            "",
+        )?;
+        let kernel = Kernel::builder()
+            .program(&program)
+            .name("convolution")
+            .queue(queue.clone())
+            .global_work_size((cols as usize, rows as usize))
+            .arg(None::<&Image<T>>)
+            .arg(None::<&Image<T>>)
+            .arg(&filter_buffer)
+            .arg(&filter_size)
+            .arg_sampler(&sampler)
+            .build()?;
+        Ok(Self {
+            name: format!("convolution {filter_size}x{filter_size}"),
+            kernel,
+            cols,
+            rows,
+            pixel: PhantomData,
+        })
+    }
+
+    /// Rotation by `theta` radians around the image center.
+    ///
+    /// The kernel interpolates bilinearly like linear filtering; pixels outside of the image
+    /// read as zero.
+    pub fn rotation(queue: &Queue, cols: u32, rows: u32, theta: f32) -> ocl::Result<Self> {
+        Self::rotate(queue, "rotation", "", cols, rows, theta)
+    }
+
+    /// Rotation like [`ImagePass::rotation`], interpolating bicubically instead: a
+    /// Catmull-Rom spline through the 4x4 pixels around the source location.
+    pub fn bicubic_rotation(queue: &Queue, cols: u32, rows: u32, theta: f32) -> ocl::Result<Self> {
+        Self::rotate(queue, "bicubic rotation", "-D BICUBIC", cols, rows, theta)
+    }
+
+    /// The rotation kernel built with `defines`, which pick the interpolation.
+    fn rotate(
+        queue: &Queue,
+        name: &str,
+        defines: &str,
+        cols: u32,
+        rows: u32,
+        theta: f32,
+    ) -> ocl::Result<Self> {
+        let program = image_program::<T>(
+            queue,
+            "kernels/rotation.cl",
+            include_str!("../kernels/rotation.cl"),
+            defines,
+        )?;
+        let kernel = Kernel::builder()
+            .program(&program)
+            .name("rotation")
+            .queue(queue.clone())
+            .global_work_size((cols as usize, rows as usize))
+            .arg(None::<&Image<T>>)
+            .arg(None::<&Image<T>>)
+            .arg(&(cols as i32))
+            .arg(&(rows as i32))
+            .arg(&theta)
+            .build()?;
+        Ok(Self {
+            name: name.to_string(),
+            kernel,
+            cols,
+            rows,
+            pixel: PhantomData,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Switch to the fastest local work size for this kernel and image size on the device,
+    /// from the tuner's cache or by timing the candidates; see [`Tuner::tune_kernel`] for
+    /// `None`. The images must be set.
+    fn tune(&mut self, tuner: &mut Tuner) -> ocl::Result<Option<[usize; 2]>> {
+        let name = format!("{} {}", self.name, T::CL_TYPE);
+        tuner.tune_kernel(&mut self.kernel, &name)
+    }
+
+    /// Set the images and enqueue the kernel.
+    pub fn enqueue(&self, input: &Image<T>, output: &Image<T>) -> ocl::Result<()> {
+        self.set_images(input, output)?;
+        unsafe { self.kernel.enq() }
+    }
+
+    fn set_images(&self, input: &Image<T>, output: &Image<T>) -> ocl::Result<()> {
+        self.kernel.set_arg(0, input)?;
+        self.kernel.set_arg(1, output)
+    }
+}
+
+/// An [`ImagePass`] with its own input and output images.
+///
+/// Uploading, enqueueing and downloading are separate steps so they can be timed apart.
+pub struct ImageKernel<T: Pixel = f32> {
+    pass: ImagePass<T>,
+    input: Image<T>,
+    output: Image<T>,
+}
+
+impl<T: Pixel> ImageKernel<T> {
+    /// Convolution with a square filter, see [`ImagePass::convolution`].
+    pub fn convolution(queue: &Queue, cols: u32, rows: u32, filter: &[f32]) -> ocl::Result<Self> {
+        Self::new(queue, ImagePass::convolution(queue, cols, rows, filter)?)
+    }
+
+    /// Rotation by `theta` radians around the image center, see [`ImagePass::rotation`].
+    pub fn rotation(queue: &Queue, cols: u32, rows: u32, theta: f32) -> ocl::Result<Self> {
+        Self::new(queue, ImagePass::rotation(queue, cols, rows, theta)?)
+    }
+
+    /// Bicubic rotation, see [`ImagePass::bicubic_rotation`].
+    pub fn bicubic_rotation(queue: &Queue, cols: u32, rows: u32, theta: f32) -> ocl::Result<Self> {
+        Self::new(
+            queue,
+            ImagePass::bicubic_rotation(queue, cols, rows, theta)?,
+        )
+    }
+
+    pub fn new(queue: &Queue, pass: ImagePass<T>) -> ocl::Result<Self> {
+        let input = image_2d(queue, flags::MEM_READ_ONLY, pass.cols, pass.rows)?;
+        let output = image_2d(queue, flags::MEM_WRITE_ONLY, pass.cols, pass.rows)?;
+        pass.set_images(&input, &output)?;
+        Ok(Self {
+            pass,
+            input,
+            output,
+        })
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.pass.cols
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.pass.rows
+    }
+
+    /// Switch to the fastest local work size for this kernel and image size on the device,
+    /// from the tuner's cache or by timing the candidates; `None` leaves it to the driver.
+    pub fn tune(&mut self, tuner: &mut Tuner) -> ocl::Result<Option<[usize; 2]>> {
+        self.pass.tune(tuner)
+    }
+
+    pub fn upload(&self, image: &[T]) -> ocl::Result<()> {
+        write_image(&self.input, image, self.cols(), self.rows())
+    }
+
+    pub fn enqueue(&self) -> ocl::Result<()> {
+        unsafe { self.pass.kernel.enq() }
+    }
+
+    pub fn download(&self) -> ocl::Result<Vec<T>> {
+        read_image(&self.output, self.cols(), self.rows())
+    }
+
+    /// Upload, enqueue and download.
+    pub fn run(&self, image: &[T]) -> ocl::Result<Vec<T>> {
+        self.upload(image)?;
+        self.enqueue()?;
+        self.download()
+    }
+}
+
+/// Convolve a single channel image (`cols x rows`, row-major) with a square filter.
+///
+/// The sampler clamps the coordinates to the edge of the image.
+pub fn convolution<T: Pixel>(
+    queue: &Queue,
+    image: &[T],
+    cols: u32,
+    rows: u32,
+    filter: &[f32],
+) -> ocl::Result<Vec<T>> {
+    ImageKernel::convolution(queue, cols, rows, filter)?.run(image)
+}
+
+/// Rotate a single channel image (`cols x rows`, row-major) by `theta` radians around its
+/// center.
+///
+/// The kernel interpolates bilinearly like linear filtering; pixels outside of the image read
+/// as zero.
+pub fn rotation<T: Pixel>(
+    queue: &Queue,
+    image: &[T],
+    cols: u32,
+    rows: u32,
+    theta: f32,
+) -> ocl::Result<Vec<T>> {
+    ImageKernel::rotation(queue, cols, rows, theta)?.run(image)
+}
+
+/// Histogram of `len` values with the `histogram` kernel, in [`HIST_BINS`] bins over
+/// `[0, HIST_RANGE]` of the pixel type, `i32` unless specified; i.e. the bins of integers
+/// are the values `0..HIST_BINS`. Values outside of the range go to the end bins.
+///
+/// Uploading, enqueueing and downloading are separate steps so they can be timed apart.
+/// `Histogram::<F16>` needs a device with `cl_khr_fp16`.
+pub struct Histogram<T: Pixel = i32> {
+    kernel: Kernel,
+    data: Buffer<T>,
+    bins: Buffer<i32>,
+}
+
+impl<T: Pixel> Histogram<T> {
+    pub fn new(queue: &Queue, len: usize) -> ocl::Result<Self> {
+        check_support::<T>(&queue.device())?;
+        let data = Buffer::<T>::builder()
+            .queue(queue.clone())
+            .len(len)
+            .build()?;
+        let bins = Buffer::<i32>::builder()
+            .queue(queue.clone())
+            .flags(flags::MEM_HOST_READ_ONLY)
+            .len(HIST_BINS)
+            .build()?;
+
+        let name = "kernels/histogram.cl";
+        let program = crate::program::build(
+            queue,
+            name,
+            &with_extensions(
+                &pixel_source(name, include_str!("../kernels/histogram.cl")),
+                &[T::EXTENSION],
+            ),
+            &format!("{} {}", pixel_defines::<T>(), element_define::<T>()),
+        )?;
+        let kernel = Kernel::builder()
+            .program(&program)
+            .name("histogram")
+            .queue(queue.clone())
+            .global_work_size(HIST_GLOBAL_SIZE)
+            .local_work_size(HIST_LOCAL_SIZE)
+            .arg(&data)
+            .arg(&(len as i32))
+            .arg(&bins)
+            .build()?;
+        Ok(Self { kernel, data, bins })
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.len() == 0
+    }
+
+    /// Switch to the fastest local work size on the device, from the tuner's cache or by
+    /// timing the candidates; `None` leaves it to the driver.
+    pub fn tune(&mut self, tuner: &mut Tuner) -> ocl::Result<Option<usize>> {
+        // the kernel accumulates into the bins on every launch, enqueue() clears them
+        let name = format!("histogram {}", T::CL_TYPE);
+        let size = tuner.tune_kernel(&mut self.kernel, &name)?;
+        Ok(size.map(|[size, _]| size))
+    }
+
+    pub fn upload(&self, data: &[T]) -> ocl::Result<()> {
+        self.data.write(data).enq()
+    }
+
+    /// Clear the bins and count the values; the kernel accumulates into the bins.
+    pub fn enqueue(&self) -> ocl::Result<()> {
+        self.bins.cmd().fill(0, None).enq()?;
+        unsafe { self.kernel.enq() }
+    }
+
+    pub fn download(&self) -> ocl::Result<Vec<i32>> {
+        let mut histogram = vec![0; HIST_BINS];
+        self.bins.read(&mut histogram).enq()?;
+        Ok(histogram)
+    }
+}
+
+/// Histogram of `data`, see [`Histogram`].
+pub fn histogram<T: Pixel>(queue: &Queue, data: &[T]) -> ocl::Result<Vec<i32>> {
+    let op = Histogram::<T>::new(queue, data.len())?;
+    op.upload(data)?;
+    op.enqueue()?;
+    op.download()
+}
+
+/// Build the embedded `source` of `name`, or the one in the source directory, see
+/// [`watch::source`].
+pub(crate) fn program(queue: &Queue, name: &str, source: &'static str) -> ocl::Result<Program> {
+    Ok(crate::program::build(
+        queue,
+        name,
+        &watch::source(name, source),
+        "",
+    )?)
+}
+
+/// Build the image kernels of `name` for the pixel type with the extra `defines`, see
+/// [`pixel_source`].
+pub(crate) fn image_program<T: Pixel>(
+    queue: &Queue,
+    name: &str,
+    source: &'static str,
+    defines: &str,
+) -> ocl::Result<Program> {
+    Ok(crate::program::build(
+        queue,
+        name,
+        &pixel_source(name, source),
+        &format!("{} {defines}", pixel_defines::<T>()),
+    )?)
+}
+
+/// The source of `name` (see [`watch::source`]) after the image access of
+/// `kernels/pixel.cl`.
+pub(crate) fn pixel_source(name: &str, source: &'static str) -> String {
+    let pixel = watch::source("kernels/pixel.cl", include_str!("../kernels/pixel.cl"));
+    // the diagnostics name the file and keep its line numbers, e.g. `kernels/blur.cl:12:5`
+    format!(
+        "#line 1 \"kernels/pixel.cl\"\n{pixel}#line 1 \"{name}\"\n{}",
+        watch::source(name, source)
+    )
+}
+
+pub(crate) fn write_image<T: Pixel>(
+    image: &Image<T>,
+    data: &[T],
+    cols: u32,
+    rows: u32,
+) -> ocl::Result<()> {
+    image.write(data).region((cols, rows, 1)).enq()
+}
+
+pub(crate) fn read_image<T: Pixel>(image: &Image<T>, cols: u32, rows: u32) -> ocl::Result<Vec<T>> {
+    let mut out = vec![T::default(); (cols * rows) as usize];
+    image.read(&mut out).region((cols, rows, 1)).enq()?;
+    Ok(out)
+}
+
+/// A single channel image of the pixel type.
+pub(crate) fn image_2d<T: Pixel>(
+    queue: &Queue,
+    flags: flags::MemFlags,
+    cols: u32,
+    rows: u32,
+) -> ocl::Result<Image<T>> {
+    let img_desc = ImageDescriptor::new(
+        MemObjectType::Image2d,
+        cols as usize,
+        rows as usize,
+        0,
+        0,
+        0,
+        0,
+        None,
+    );
+    let img_format = ImageFormat::new(ImageChannelOrder::R, T::CHANNEL_TYPE);
+    unsafe { Image::<T>::new(queue, flags, img_format, img_desc, None) }
+}
@@ -0,0 +1,280 @@
+//! Chains of image kernels that keep the images on the device.
+//!
+//! An [`ImagePipeline`] enqueues its kernels, e.g. blur -> rotate -> histogram, on one
+//! in-order queue without waiting in between. The intermediate images come from an
+//! [`ImagePool`] and only the marked outputs are read back, after the last kernel:
+//!
+//! ```no_run
+//! # let queue = lab_opencl::device::default_queue(None).unwrap();
+//! # let (image, filter) = (vec![0.0; 64 * 64], lab_opencl::utils::generate_gaussian_kernel(2, 1.0));
+//! use lab_opencl::pipeline::ImagePipeline;
+//!
+//! let mut pipeline = ImagePipeline::new(&queue, 64, 64);
+//! pipeline.convolution(&filter)?.rotation(0.5)?;
+//! let rotated = pipeline.output();
+//! let histogram = pipeline.histogram()?;
+//!
+//! let outputs = pipeline.run(&image)?;
+//! println!("{} pixels, {:?}", outputs.image(rotated).len(), outputs.histogram(histogram));
+//! # Ok::<_, ocl::Error>(())
+//! ```
+
+use ocl::{flags, Buffer, Image, Kernel, Program, Queue};
+
+use crate::element::Pixel;
+use crate::ops::{image_2d, image_program, read_image, write_image, ImagePass, HIST_BINS};
+
+/// Work-group size of the `image_histogram` kernel in each dimension.
+const HIST_LOCAL_DIM: usize = 16;
+
+/// An image output of a pipeline, see [`ImagePipeline::output`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageOutput(usize);
+
+/// A histogram output of a pipeline, see [`ImagePipeline::histogram`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistogramOutput(usize);
+
+/// The outputs of one run of a pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outputs<T: Pixel = f32> {
+    images: Vec<Vec<T>>,
+    histograms: Vec<Vec<i32>>,
+}
+
+impl<T: Pixel> Outputs<T> {
+    /// The single channel image at `output`, row-major.
+    pub fn image(&self, output: ImageOutput) -> &[T] {
+        &self.images[output.0]
+    }
+
+    pub fn histogram(&self, output: HistogramOutput) -> &[i32] {
+        &self.histograms[output.0]
+    }
+}
+
+/// Intermediate images of a pixel type, reused once the kernel reading them is enqueued.
+///
+/// The queue runs the kernels in order, so an image can be written again by a later kernel.
+#[derive(Default)]
+pub struct ImagePool<T: Pixel = f32> {
+    free: Vec<Image<T>>,
+    created: usize,
+}
+
+impl<T: Pixel> ImagePool<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A free `cols x rows` image, or a new read-write one.
+    pub fn acquire(&mut self, queue: &Queue, cols: u32, rows: u32) -> ocl::Result<Image<T>> {
+        let free = self.free.iter().position(|image| {
+            let dims = image.dims().to_lens().unwrap_or_default();
+            (dims[0], dims[1]) == (cols as usize, rows as usize)
+        });
+        if let Some(index) = free {
+            return Ok(self.free.swap_remove(index));
+        }
+        self.created += 1;
+        image_2d(queue, flags::MEM_READ_WRITE, cols, rows)
+    }
+
+    pub fn release(&mut self, image: Image<T>) {
+        self.free.push(image);
+    }
+
+    /// Number of images created so far.
+    pub fn created(&self) -> usize {
+        self.created
+    }
+}
+
+enum Step<T: Pixel> {
+    Pass(ImagePass<T>),
+    /// Keep the current image for reading back.
+    Output,
+    /// Count the current image into the bins.
+    Histogram(Buffer<i32>),
+}
+
+/// A chain of image kernels on single channel images (`cols x rows`, row-major) of a fixed
+/// size and pixel type, `f32` unless specified.
+pub struct ImagePipeline<T: Pixel = f32> {
+    queue: Queue,
+    cols: u32,
+    rows: u32,
+    steps: Vec<Step<T>>,
+    images: usize,
+    histograms: usize,
+    histogram_program: Option<Program>,
+    pool: ImagePool<T>,
+}
+
+impl<T: Pixel> ImagePipeline<T> {
+    pub fn new(queue: &Queue, cols: u32, rows: u32) -> Self {
+        Self {
+            queue: queue.clone(),
+            cols,
+            rows,
+            steps: Vec::new(),
+            images: 0,
+            histograms: 0,
+            histogram_program: None,
+            pool: ImagePool::new(),
+        }
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Append a kernel on the current image, which becomes the kernel's output.
+    pub fn pass(&mut self, pass: ImagePass<T>) -> &mut Self {
+        assert_eq!(
+            (pass.cols(), pass.rows()),
+            (self.cols, self.rows),
+            "image size mismatch in {}",
+            pass.name()
+        );
+        self.steps.push(Step::Pass(pass));
+        self
+    }
+
+    /// Append a convolution, see [`ImagePass::convolution`].
+    pub fn convolution(&mut self, filter: &[f32]) -> ocl::Result<&mut Self> {
+        let pass = ImagePass::convolution(&self.queue, self.cols, self.rows, filter)?;
+        Ok(self.pass(pass))
+    }
+
+    /// Append a rotation, see [`ImagePass::rotation`].
+    pub fn rotation(&mut self, theta: f32) -> ocl::Result<&mut Self> {
+        let pass = ImagePass::rotation(&self.queue, self.cols, self.rows, theta)?;
+        Ok(self.pass(pass))
+    }
+
+    /// Append a bicubic rotation, see [`ImagePass::bicubic_rotation`].
+    pub fn bicubic_rotation(&mut self, theta: f32) -> ocl::Result<&mut Self> {
+        let pass = ImagePass::bicubic_rotation(&self.queue, self.cols, self.rows, theta)?;
+        Ok(self.pass(pass))
+    }
+
+    /// Read back the current image; the kernels appended later do not change it.
+    pub fn output(&mut self) -> ImageOutput {
+        self.steps.push(Step::Output);
+        self.images += 1;
+        ImageOutput(self.images - 1)
+    }
+
+    /// Count the current image into [`HIST_BINS`] bins over `[0, HIST_RANGE]` and read back
+    /// the bins, see [`Pixel::HIST_RANGE`].
+    pub fn histogram(&mut self) -> ocl::Result<HistogramOutput> {
+        if self.histogram_program.is_none() {
+            self.histogram_program = Some(image_program::<T>(
+                &self.queue,
+                "kernels/histogram.cl",
+                include_str!("../kernels/histogram.cl"),
+                "",
+            )?);
+        }
+        let bins = Buffer::<i32>::builder()
+            .queue(self.queue.clone())
+            .flags(flags::MEM_HOST_READ_ONLY)
+            .len(HIST_BINS)
+            .build()?;
+        self.steps.push(Step::Histogram(bins));
+        self.histograms += 1;
+        Ok(HistogramOutput(self.histograms - 1))
+    }
+
+    /// Number of images the runs so far have allocated.
+    pub fn images(&self) -> usize {
+        self.pool.created()
+    }
+
+    /// Upload `image`, enqueue all steps and read back the outputs.
+    pub fn run(&mut self, image: &[T]) -> ocl::Result<Outputs<T>> {
+        let (cols, rows) = (self.cols, self.rows);
+        let mut current = self.pool.acquire(&self.queue, cols, rows)?;
+        write_image(&current, image, cols, rows)?;
+
+        // the output images stay out of the pool until they are read
+        let mut kept: Vec<Image<T>> = Vec::new();
+        let mut outputs = Vec::with_capacity(self.images);
+        let mut current_kept = false;
+        for step in &self.steps {
+            match step {
+                Step::Pass(pass) => {
+                    let output = self.pool.acquire(&self.queue, cols, rows)?;
+                    pass.enqueue(&current, &output)?;
+                    let input = std::mem::replace(&mut current, output);
+                    if !std::mem::take(&mut current_kept) {
+                        self.pool.release(input);
+                    }
+                }
+                Step::Output => {
+                    if !current_kept {
+                        kept.push(current.clone());
+                        current_kept = true;
+                    }
+                    outputs.push(kept.len() - 1);
+                }
+                Step::Histogram(bins) => {
+                    let program = self.histogram_program.as_ref().unwrap();
+                    bins.cmd().fill(0, None).enq()?;
+                    // the global size has to be a multiple of the local size
+                    let kernel = Kernel::builder()
+                        .program(program)
+                        .name("image_histogram")
+                        .queue(self.queue.clone())
+                        .global_work_size((
+                            (cols as usize).div_ceil(HIST_LOCAL_DIM) * HIST_LOCAL_DIM,
+                            (rows as usize).div_ceil(HIST_LOCAL_DIM) * HIST_LOCAL_DIM,
+                        ))
+                        .local_work_size((HIST_LOCAL_DIM, HIST_LOCAL_DIM))
+                        .arg(&current)
+                        .arg(&(cols as i32))
+                        .arg(&(rows as i32))
+                        .arg(bins)
+                        .build()?;
+                    unsafe { kernel.enq()? };
+                }
+            }
+        }
+        if !current_kept {
+            self.pool.release(current);
+        }
+
+        // the blocking reads wait for the kernels before them
+        let kept = kept
+            .into_iter()
+            .map(|image| {
+                let data = read_image(&image, cols, rows);
+                self.pool.release(image);
+                data
+            })
+            .collect::<ocl::Result<Vec<_>>>()?;
+        let images = outputs
+            .into_iter()
+            .map(|index| kept[index].clone())
+            .collect();
+        let histograms = self
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Histogram(bins) => Some(bins),
+                _ => None,
+            })
+            .map(|bins| {
+                let mut histogram = vec![0; HIST_BINS];
+                bins.read(&mut histogram).enq()?;
+                Ok(histogram)
+            })
+            .collect::<ocl::Result<_>>()?;
+        Ok(Outputs { images, histograms })
+    }
+}
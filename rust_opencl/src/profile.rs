@@ -0,0 +1,193 @@
+use std::fmt;
+use std::time::Duration;
+
+use ocl::enums::ProfilingInfo;
+use ocl::flags::CommandQueueProperties;
+use ocl::{Context, Device, Event, Queue};
+
+use crate::trace::{Span, Timeline};
+
+/// Kind of command a profiled event belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Write,
+    Kernel,
+    Read,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 3] = [Stage::Write, Stage::Kernel, Stage::Read];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Write => "write",
+            Stage::Kernel => "kernel",
+            Stage::Read => "read",
+        }
+    }
+}
+
+/// Create an in-order queue with `CL_QUEUE_PROFILING_ENABLE`, required by [`Profiler`].
+pub fn profiling_queue(context: &Context, device: Device) -> ocl::Result<Queue> {
+    Queue::new(
+        context,
+        device,
+        Some(CommandQueueProperties::PROFILING_ENABLE),
+    )
+}
+
+/// Collects the events of profiled commands.
+///
+/// Pass `profiler.event(stage, label)` to the `enew` of a write, kernel or read command
+/// enqueued on a [`profiling_queue`], then call [`Profiler::report`] once they are enqueued.
+#[derive(Default)]
+pub struct Profiler {
+    events: Vec<(Stage, String, Event)>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A new event to record the command of the given stage.
+    pub fn event(&mut self, stage: Stage, label: &str) -> &mut Event {
+        self.events.push((stage, label.to_string(), Event::empty()));
+        &mut self.events.last_mut().unwrap().2
+    }
+
+    /// Wait for the recorded commands and read their timestamps.
+    pub fn report(&self) -> ocl::Result<ProfileReport> {
+        let mut commands = Vec::with_capacity(self.events.len());
+        for (stage, label, event) in &self.events {
+            event.wait_for()?;
+            commands.push(CommandTiming {
+                stage: *stage,
+                label: label.clone(),
+                queued_ns: event.profiling_info(ProfilingInfo::Queued)?.time()?,
+                start_ns: event.profiling_info(ProfilingInfo::Start)?.time()?,
+                end_ns: event.profiling_info(ProfilingInfo::End)?.time()?,
+            });
+        }
+        Ok(ProfileReport { commands })
+    }
+}
+
+/// Device timestamps of one command in nanoseconds.
+#[derive(Debug, Clone)]
+pub struct CommandTiming {
+    pub stage: Stage,
+    pub label: String,
+    pub queued_ns: u64,
+    pub start_ns: u64,
+    pub end_ns: u64,
+}
+
+impl CommandTiming {
+    /// Time spent executing on the device.
+    pub fn duration(&self) -> Duration {
+        Duration::from_nanos(self.end_ns.saturating_sub(self.start_ns))
+    }
+
+    /// Time between being enqueued and starting.
+    pub fn wait(&self) -> Duration {
+        Duration::from_nanos(self.start_ns.saturating_sub(self.queued_ns))
+    }
+}
+
+/// Timings of the profiled commands, in enqueue order.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub commands: Vec<CommandTiming>,
+}
+
+impl ProfileReport {
+    /// Total execution time of the commands of a stage.
+    pub fn stage_total(&self, stage: Stage) -> Duration {
+        self.commands
+            .iter()
+            .filter(|command| command.stage == stage)
+            .map(CommandTiming::duration)
+            .sum()
+    }
+
+    /// Total execution time of all commands.
+    pub fn total(&self) -> Duration {
+        self.commands.iter().map(CommandTiming::duration).sum()
+    }
+}
+
+impl Timeline for ProfileReport {
+    fn spans(&self) -> Vec<Span<'_>> {
+        self.commands
+            .iter()
+            .map(|command| Span {
+                label: &command.label,
+                category: command.stage.name(),
+                queued_ns: Some(command.queued_ns),
+                start_ns: command.start_ns,
+                end_ns: command.end_ns,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1e3;
+
+        writeln!(
+            f,
+            "{:<8}{:<24}{:>12}{:>12}",
+            "stage", "command", "wait (ms)", "run (ms)"
+        )?;
+        for command in &self.commands {
+            writeln!(
+                f,
+                "{:<8}{:<24}{:>12.3}{:>12.3}",
+                command.stage.name(),
+                command.label,
+                ms(command.wait()),
+                ms(command.duration())
+            )?;
+        }
+        writeln!(f, "{}", "-".repeat(56))?;
+        for stage in Stage::ALL {
+            writeln!(
+                f,
+                "{:<8}{:<24}{:>12}{:>12.3}",
+                stage.name(),
+                "total",
+                "",
+                ms(self.stage_total(stage))
+            )?;
+        }
+        write!(f, "{:<32}{:>12}{:>12.3}", "total", "", ms(self.total()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandTiming, ProfileReport, Stage};
+
+    #[test]
+    pub fn test_stage_total() {
+        let command = |stage, start_ns, end_ns| CommandTiming {
+            stage,
+            label: String::new(),
+            queued_ns: 0,
+            start_ns,
+            end_ns,
+        };
+        let report = ProfileReport {
+            commands: vec![
+                command(Stage::Write, 0, 1_000),
+                command(Stage::Kernel, 1_000, 4_000),
+                command(Stage::Write, 4_000, 6_000),
+            ],
+        };
+        assert_eq!(report.stage_total(Stage::Write).as_nanos(), 3_000);
+        assert_eq!(report.stage_total(Stage::Read).as_nanos(), 0);
+        assert_eq!(report.total().as_nanos(), 6_000);
+    }
+}
@@ -0,0 +1,196 @@
+//! Program building with an on-disk cache of device binaries, and loading of precompiled
+//! binaries such as the `.aocx` files of the Intel FPGA offline compiler.
+//!
+//! A cached binary is keyed by the device name, vendor and version, the driver version, the
+//! build options and the source, so any of them changing rebuilds from source.
+
+use std::path::{Path, PathBuf};
+
+use ocl::enums::{DeviceInfo, ProgramInfo, ProgramInfoResult};
+use ocl::{Context, Device, Program, Queue};
+
+use crate::error::{self, BuildError, Error};
+
+/// Environment variable overriding the cache directory; `off` disables the cache.
+pub const CACHE_ENV: &str = "OCL_PROGRAM_CACHE";
+const DEFAULT_CACHE_DIR: &str = "target/program-cache";
+
+/// Directory of cached program binaries.
+#[derive(Debug, Clone)]
+pub struct ProgramCache {
+    dir: PathBuf,
+}
+
+impl ProgramCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The cache at `OCL_PROGRAM_CACHE`, else at `target/program-cache`; `None` when the
+    /// variable is `off`.
+    pub fn from_env() -> Option<Self> {
+        match std::env::var(CACHE_ENV) {
+            Ok(dir) if dir == "off" => None,
+            Ok(dir) => Some(Self::new(dir)),
+            Err(_) => Some(Self::new(DEFAULT_CACHE_DIR)),
+        }
+    }
+
+    /// File of the binary built from `source` with `options` for `device`.
+    pub fn path(&self, device: Device, source: &str, options: &str) -> ocl::Result<PathBuf> {
+        let mut identity = Vec::new();
+        for info in [
+            DeviceInfo::Name,
+            DeviceInfo::Vendor,
+            DeviceInfo::Version,
+            DeviceInfo::DriverVersion,
+        ] {
+            identity.push(device.info(info)?.to_string());
+        }
+        Ok(self.dir.join(cache_file_name(&identity, source, options)))
+    }
+
+    /// Build `source` (named `name` in build errors) for `device`, reusing the cached binary
+    /// when there is one and caching the binary otherwise. A binary the driver rejects is
+    /// rebuilt from source.
+    pub fn build(
+        &self,
+        context: &Context,
+        device: Device,
+        name: &str,
+        source: &str,
+        options: &str,
+    ) -> error::Result<Program> {
+        let path = self.path(device, source, options)?;
+        if let Ok(binary) = std::fs::read(&path) {
+            match build_binary(context, device, &binary, options) {
+                Ok(program) => return Ok(program),
+                Err(err) => log::warn!("rebuilding {} - {err}", path.display()),
+            }
+        }
+
+        let program = build_source(context, device, name, source, options)?;
+        // a failed write only costs a rebuild next time
+        if let Err(err) = save_binary(&program, &path) {
+            log::warn!("could not cache {} - {err}", path.display());
+        }
+        Ok(program)
+    }
+}
+
+/// Build `source` (named `name` in build errors) with `options` for the queue's device,
+/// through the [`ProgramCache`] from the environment.
+pub fn build(queue: &Queue, name: &str, source: &str, options: &str) -> error::Result<Program> {
+    let (context, device) = (queue.context(), queue.device());
+    match ProgramCache::from_env() {
+        Some(cache) => cache.build(&context, device, name, source, options),
+        None => build_source(&context, device, name, source, options),
+    }
+}
+
+/// Build from source; a failed build reports the device's diagnostics.
+fn build_source(
+    context: &Context,
+    device: Device,
+    name: &str,
+    source: &str,
+    options: &str,
+) -> error::Result<Program> {
+    Program::builder()
+        .devices(device)
+        .src(source)
+        .cmplr_opt(options)
+        .build(context)
+        .map_err(
+            |err| match BuildError::collect(context, device, name, source, options) {
+                Some(build_error) => Error::Build(build_error),
+                None => Error::Ocl(err),
+            },
+        )
+}
+
+/// Load a precompiled binary for `device`, e.g. an `.aocx` file built offline for an FPGA.
+pub fn load_binary(
+    context: &Context,
+    device: Device,
+    path: impl AsRef<Path>,
+    options: &str,
+) -> ocl::Result<Program> {
+    let path = path.as_ref();
+    let binary =
+        std::fs::read(path).map_err(|err| format!("could not read {} - {err}", path.display()))?;
+    build_binary(context, device, &binary, options)
+}
+
+fn build_binary(
+    context: &Context,
+    device: Device,
+    binary: &[u8],
+    options: &str,
+) -> ocl::Result<Program> {
+    Program::builder()
+        .devices(device)
+        .binaries(&[binary])
+        .cmplr_opt(options)
+        .build(context)
+}
+
+fn save_binary(program: &Program, path: &Path) -> ocl::Result<()> {
+    let binary = match program.info(ProgramInfo::Binaries)? {
+        ProgramInfoResult::Binaries(mut binaries) if !binaries.is_empty() => {
+            binaries.swap_remove(0)
+        }
+        other => return Err(format!("unexpected program info: {other:?}").into()),
+    };
+    let write = || -> std::io::Result<()> {
+        std::fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+        // write then rename, so a concurrent build never reads a partial binary
+        let partial = path.with_extension("partial");
+        std::fs::write(&partial, binary)?;
+        std::fs::rename(partial, path)
+    };
+    write().map_err(|err| err.to_string().into())
+}
+
+/// `<hash>.bin` of the device identity, source and build options.
+fn cache_file_name(identity: &[String], source: &str, options: &str) -> String {
+    let mut hash = FNV_OFFSET;
+    for part in identity.iter().map(String::as_str).chain([options, source]) {
+        // the separator keeps ("ab", "c") and ("a", "bc") apart
+        hash = fnv1a(hash, part.as_bytes());
+        hash = fnv1a(hash, &[0]);
+    }
+    format!("{hash:016x}.bin")
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across Rust versions.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_file_name, fnv1a, FNV_OFFSET};
+
+    #[test]
+    pub fn test_cache_file_name() {
+        assert_eq!(fnv1a(FNV_OFFSET, b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(FNV_OFFSET, b"a"), 0xaf63dc4c8601ec8c);
+
+        let identity = ["gpu".to_string(), "OpenCL 3.0".to_string()];
+        let name = cache_file_name(&identity, "kernel", "-D T=float");
+        assert!(name.ends_with(".bin"));
+        assert_eq!(name, cache_file_name(&identity, "kernel", "-D T=float"));
+        assert_ne!(name, cache_file_name(&identity, "kernel", "-D T=double"));
+        assert_ne!(
+            name,
+            cache_file_name(&identity[..1], "kernel", "-D T=float")
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! CPU reference implementations of the kernels, for golden comparisons.
+//!
+//! They follow the kernels' conventions (sampler addressing, filtering) rather than an ideal
+//! definition, so the results only differ by floating point rounding and the precision of
+//! the device's image filtering. With the `rayon` feature the image operations run in
+//! parallel over rows.
+
+use crate::ops::HIST_BINS;
+
+/// Apply `f(row, out_row)` to every row of `out`, in parallel with the `rayon` feature.
+fn for_each_row(out: &mut [f32], row_len: usize, f: impl Fn(usize, &mut [f32]) + Sync + Send) {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        out.par_chunks_mut(row_len)
+            .enumerate()
+            .for_each(|(y, row)| f(y, row));
+    }
+    #[cfg(not(feature = "rayon"))]
+    out.chunks_mut(row_len)
+        .enumerate()
+        .for_each(|(y, row)| f(y, row));
+}
+
+/// c = a + b
+pub fn vector_add(a: &[f32], b: &[f32]) -> Vec<f32> {
+    a.iter().zip(b).map(|(a, b)| a + b).collect()
+}
+
+/// Convolve a single channel `cols x rows` image with a square filter; coordinates outside
+/// of the image are clamped to the edge.
+pub fn convolution(image: &[f32], cols: usize, rows: usize, filter: &[f32]) -> Vec<f32> {
+    let filter_size = (filter.len() as f64).sqrt() as usize;
+    let half = (filter_size / 2) as isize;
+    let mut out = vec![0.0; image.len()];
+
+    for_each_row(&mut out, cols, |y, out_row| {
+        for (x, value) in out_row.iter_mut().enumerate() {
+            let mut filt_idx = 0;
+            for i in -half..=half {
+                let py = (y as isize + i).clamp(0, rows as isize - 1) as usize;
+                for j in -half..=half {
+                    let px = (x as isize + j).clamp(0, cols as isize - 1) as usize;
+                    *value += image[py * cols + px] * filter[filt_idx];
+                    filt_idx += 1;
+                }
+            }
+        }
+    });
+    out
+}
+
+/// Rotate a single channel `cols x rows` image by `theta` radians around its center.
+///
+/// Like the kernel, the relative position is truncated to an integer and the source is
+/// interpolated like the linear filtering of OpenCL images: texel centers at `+0.5` and a
+/// zero border.
+pub fn rotation(image: &[f32], cols: usize, rows: usize, theta: f32) -> Vec<f32> {
+    let (x0, y0) = (cols as f32 / 2.0, rows as f32 / 2.0);
+    let (sin_theta, cos_theta) = (theta.sin(), theta.cos());
+    let texel = |x: i32, y: i32| {
+        if x < 0 || x >= cols as i32 || y < 0 || y >= rows as i32 {
+            0.0
+        } else {
+            image[y as usize * cols + x as usize]
+        }
+    };
+
+    let mut out = vec![0.0; image.len()];
+    for_each_row(&mut out, cols, |y, out_row| {
+        for (x, value) in out_row.iter_mut().enumerate() {
+            let x_ = (x as f32 - x0) as i32 as f32;
+            let y_ = (y as f32 - y0) as i32 as f32;
+            let u = x_ * cos_theta - y_ * sin_theta + x0 - 0.5;
+            let v = x_ * sin_theta + y_ * cos_theta + y0 - 0.5;
+            let (i0, j0) = (u.floor() as i32, v.floor() as i32);
+            let (a, b) = (u - u.floor(), v - v.floor());
+
+            *value = (1.0 - a) * (1.0 - b) * texel(i0, j0)
+                + a * (1.0 - b) * texel(i0 + 1, j0)
+                + (1.0 - a) * b * texel(i0, j0 + 1)
+                + a * b * texel(i0 + 1, j0 + 1);
+        }
+    });
+    out
+}
+
+/// Rotate like [`rotation`], interpolating with a Catmull-Rom spline through the 4x4 pixels
+/// around the source location, like the kernel built with `BICUBIC`.
+pub fn bicubic_rotation(image: &[f32], cols: usize, rows: usize, theta: f32) -> Vec<f32> {
+    let (x0, y0) = (cols as f32 / 2.0, rows as f32 / 2.0);
+    let (sin_theta, cos_theta) = (theta.sin(), theta.cos());
+    let texel = |x: i32, y: i32| {
+        if x < 0 || x >= cols as i32 || y < 0 || y >= rows as i32 {
+            0.0
+        } else {
+            image[y as usize * cols + x as usize]
+        }
+    };
+
+    let mut out = vec![0.0; image.len()];
+    for_each_row(&mut out, cols, |y, out_row| {
+        for (x, value) in out_row.iter_mut().enumerate() {
+            let x_ = (x as f32 - x0) as i32 as f32;
+            let y_ = (y as f32 - y0) as i32 as f32;
+            let u = x_ * cos_theta - y_ * sin_theta + x0 - 0.5;
+            let v = x_ * sin_theta + y_ * cos_theta + y0 - 0.5;
+            let (i0, j0) = (u.floor() as i32, v.floor() as i32);
+            let (wx, wy) = (cubic_weights(u - u.floor()), cubic_weights(v - v.floor()));
+
+            *value = (0..4)
+                .map(|j| {
+                    let row: f32 = (0..4)
+                        .map(|i| wx[i] * texel(i0 + i as i32 - 1, j0 + j as i32 - 1))
+                        .sum();
+                    wy[j] * row
+                })
+                .sum();
+        }
+    });
+    out
+}
+
+/// The Catmull-Rom weights of the four pixels around a location `t` past the second one.
+fn cubic_weights(t: f32) -> [f32; 4] {
+    [
+        ((-0.5 * t + 1.0) * t - 0.5) * t,
+        (1.5 * t - 2.5) * t * t + 1.0,
+        ((-1.5 * t + 2.0) * t + 0.5) * t,
+        (0.5 * t - 0.5) * t * t,
+    ]
+}
+
+/// Histogram of `data`, whose values must be in `0..HIST_BINS`, like the `histogram` kernel
+/// on integers.
+pub fn histogram(data: &[i32]) -> Vec<i32> {
+    let mut histogram = vec![0; HIST_BINS];
+    for &value in data {
+        histogram[value as usize] += 1;
+    }
+    histogram
+}
+
+/// Histogram of a single channel image in `HIST_BINS` bins over `[0, 1]`, like the
+/// `image_histogram` kernel; values outside of the range go to the end bins.
+pub fn image_histogram(image: &[f32]) -> Vec<i32> {
+    let mut histogram = vec![0; HIST_BINS];
+    for &value in image {
+        let bin = (value * HIST_BINS as f32).clamp(0.0, (HIST_BINS - 1) as f32) as usize;
+        histogram[bin] += 1;
+    }
+    histogram
+}
+
+/// Element-wise error of a result against its reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorStats {
+    pub max_abs: f32,
+    pub mean_abs: f32,
+}
+
+impl ErrorStats {
+    /// Compare `actual` with `expected`, which must have the same length.
+    pub fn compare(actual: &[f32], expected: &[f32]) -> Self {
+        assert_eq!(actual.len(), expected.len(), "length mismatch");
+        let (max_abs, sum_abs) = actual
+            .iter()
+            .zip(expected)
+            .map(|(a, e)| (a - e).abs())
+            .fold((0f32, 0f64), |(max, sum), diff| {
+                (max.max(diff), sum + diff as f64)
+            });
+        Self {
+            max_abs,
+            mean_abs: (sum_abs / actual.len().max(1) as f64) as f32,
+        }
+    }
+
+    /// Whether both errors are within the tolerances.
+    pub fn within(&self, max_abs: f32, mean_abs: f32) -> bool {
+        self.max_abs <= max_abs && self.mean_abs <= mean_abs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convolution, histogram, ErrorStats};
+
+    #[test]
+    pub fn test_reference_identities() {
+        let image: Vec<f32> = (0..6 * 4).map(|v| v as f32).collect();
+
+        // a centered one-hot filter copies the image
+        let mut filter = vec![0.0; 9];
+        filter[4] = 1.0;
+        assert_eq!(convolution(&image, 6, 4, &filter), image);
+
+        let counts = histogram(&[0, 3, 3, 255]);
+        assert_eq!((counts[0], counts[3], counts[255]), (1, 2, 1));
+
+        let stats = ErrorStats::compare(&[1.0, 2.0], &[1.5, 2.0]);
+        assert_eq!(stats.max_abs, 0.5);
+        assert_eq!(stats.mean_abs, 0.25);
+    }
+}
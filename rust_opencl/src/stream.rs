@@ -0,0 +1,170 @@
+use std::ops::Range;
+
+use ocl::enums::{DeviceInfo, DeviceInfoResult};
+use ocl::{Buffer, Device, Queue};
+
+use crate::blas::Blas;
+use crate::element::{Element, Float};
+use crate::error::check_len;
+use crate::map::{check_arity, MapKernels};
+
+/// Streams host vectors that do not fit in one device allocation through it chunk by chunk.
+///
+/// Each chunk is uploaded, processed with [`MapKernels`] or [`Blas`] and released before the
+/// next one. Element-wise results are read back per chunk; the per-chunk results of a
+/// reduction are gathered in a device buffer and reduced there, so only the final scalar is
+/// read back. The reductions run on `f32` vectors unless specified.
+pub struct Streamer<F: Float = f32> {
+    queue: Queue,
+    blas: Blas<F>,
+    kernels: MapKernels,
+    chunk_bytes: usize,
+}
+
+impl<F: Float> Streamer<F> {
+    /// Use chunks of the device's `CL_DEVICE_MAX_MEM_ALLOC_SIZE`, of at most `i32::MAX` elements.
+    pub fn new(queue: &Queue) -> ocl::Result<Self> {
+        let chunk_bytes = max_mem_alloc_size(&queue.device())? as usize;
+        Ok(Self {
+            queue: queue.clone(),
+            blas: Blas::new(queue)?,
+            kernels: MapKernels::new(queue),
+            chunk_bytes,
+        })
+    }
+
+    /// Override the size of one chunk of one vector in bytes.
+    pub fn with_chunk_bytes(mut self, chunk_bytes: usize) -> Self {
+        self.chunk_bytes = chunk_bytes;
+        self
+    }
+
+    /// `out[i] = expr(inputs[0][i], inputs[1][i], ...)`, see [`MapKernels::zip`].
+    pub fn zip<T: Element, O: Element>(&self, inputs: &[&[T]], expr: &str) -> ocl::Result<Vec<O>> {
+        check_arity(inputs.len())?;
+        let len = inputs[0].len();
+        for input in inputs {
+            check_len("vector length", len, input.len())?;
+        }
+
+        let elem_size = std::mem::size_of::<T>().max(std::mem::size_of::<O>());
+        let mut out = vec![O::default(); len];
+        for range in self.chunks(len, elem_size) {
+            let buffers = self.upload(inputs, range.clone())?;
+            let buffers: Vec<&Buffer<T>> = buffers.iter().collect();
+            let result: Buffer<O> = self.kernels.zip(&buffers, expr)?;
+            result.read(&mut out[range]).enq()?;
+        }
+        Ok(out)
+    }
+
+    /// Dot product of `x` and `y`.
+    pub fn dot(&self, x: &[F], y: &[F]) -> ocl::Result<F> {
+        check_len("vector length", x.len(), y.len())?;
+        self.reduce(
+            &[x, y],
+            |v| self.blas.dot_device(v[0], v[1]),
+            |results| self.blas.sum_device(results),
+        )
+    }
+
+    /// Euclidean norm of `x`, i.e. the norm of the per-chunk norms.
+    pub fn nrm2(&self, x: &[F]) -> ocl::Result<F> {
+        self.reduce(
+            &[x],
+            |v| self.blas.nrm2_device(v[0]),
+            |results| self.blas.nrm2_device(results),
+        )
+    }
+
+    /// Sum of the absolute values of `x`.
+    pub fn asum(&self, x: &[F]) -> ocl::Result<F> {
+        self.reduce(
+            &[x],
+            |v| self.blas.asum_device(v[0]),
+            |results| self.blas.sum_device(results),
+        )
+    }
+
+    /// Index of the first element with the largest absolute value, `None` for an empty vector.
+    pub fn iamax(&self, x: &[F]) -> ocl::Result<Option<usize>> {
+        let mut best: Option<(usize, f64)> = None;
+        for range in self.chunks(x.len(), std::mem::size_of::<F>()) {
+            let start = range.start;
+            let chunk = &self.upload(&[x], range)?[0];
+            let Some(idx) = self.blas.iamax(chunk)? else {
+                continue;
+            };
+            let idx = start + idx as usize;
+            let value = x[idx].to_f64().abs();
+            if best.is_none_or(|(_, best_value)| value > best_value) {
+                best = Some((idx, value));
+            }
+        }
+        Ok(best.map(|(idx, _)| idx))
+    }
+
+    fn reduce(
+        &self,
+        vectors: &[&[F]],
+        chunk_op: impl Fn(&[&Buffer<F>]) -> ocl::Result<Buffer<F>>,
+        final_op: impl Fn(&Buffer<F>) -> ocl::Result<Buffer<F>>,
+    ) -> ocl::Result<F> {
+        let chunks = self.chunks(vectors[0].len(), std::mem::size_of::<F>());
+        if chunks.is_empty() {
+            return Ok(F::default());
+        }
+
+        let results = Buffer::<F>::builder()
+            .queue(self.queue.clone())
+            .len(chunks.len())
+            .build()?;
+
+        for (i, range) in chunks.into_iter().enumerate() {
+            let buffers = self.upload(vectors, range)?;
+            let buffers: Vec<&Buffer<F>> = buffers.iter().collect();
+
+            // gather the chunk result on the device
+            let result = chunk_op(&buffers)?;
+            result.copy(&results, Some(i), Some(1)).enq()?;
+        }
+
+        let mut value = vec![F::default(); 1];
+        final_op(&results)?.read(&mut value).enq()?;
+        Ok(value[0])
+    }
+
+    fn upload<T: Element>(
+        &self,
+        vectors: &[&[T]],
+        range: Range<usize>,
+    ) -> ocl::Result<Vec<Buffer<T>>> {
+        vectors
+            .iter()
+            .map(|v| {
+                Buffer::<T>::builder()
+                    .queue(self.queue.clone())
+                    .len(range.len())
+                    .copy_host_slice(&v[range.clone()])
+                    .build()
+            })
+            .collect()
+    }
+
+    fn chunks(&self, len: usize, elem_size: usize) -> Vec<Range<usize>> {
+        // the kernels index with `int`
+        let chunk = (self.chunk_bytes / elem_size).clamp(1, i32::MAX as usize);
+        (0..len)
+            .step_by(chunk)
+            .map(|start| start..(start + chunk).min(len))
+            .collect()
+    }
+}
+
+/// `CL_DEVICE_MAX_MEM_ALLOC_SIZE` of the device in bytes.
+pub fn max_mem_alloc_size(device: &Device) -> ocl::Result<u64> {
+    match device.info(DeviceInfo::MaxMemAllocSize)? {
+        DeviceInfoResult::MaxMemAllocSize(size) => Ok(size),
+        other => Err(format!("unexpected device info: {other:?}").into()),
+    }
+}
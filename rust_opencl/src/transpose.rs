@@ -0,0 +1,190 @@
+use std::time::Instant;
+
+use ocl::{Buffer, Kernel, Program, Queue};
+
+use crate::element::{check_support, element_define, with_extensions, Element};
+use crate::error::check_len;
+use crate::{program, watch};
+
+/// Tile width and height of the transpose kernel.
+pub const TILE_DIM: usize = 16;
+
+/// Out-of-place transpose of a row-major `rows x cols` matrix.
+///
+/// The kernel stages each 16x16 tile in `__local` memory (padded by one column), so both
+/// the global reads and the global writes are coalesced.
+pub struct Transpose {
+    program: Program,
+    queue: Queue,
+}
+
+impl Transpose {
+    pub fn new<T: Element>(queue: &Queue) -> ocl::Result<Self> {
+        check_support::<T>(&queue.device())?;
+        let program = program::build(
+            queue,
+            "kernels/transpose.cl",
+            &with_extensions(
+                &watch::source(
+                    "kernels/transpose.cl",
+                    include_str!("../kernels/transpose.cl"),
+                ),
+                &[T::EXTENSION],
+            ),
+            &element_define::<T>(),
+        )?;
+
+        Ok(Self {
+            program,
+            queue: queue.clone(),
+        })
+    }
+
+    /// Enqueue the transpose of `input` (rows x cols) into `output` (cols x rows).
+    pub fn enqueue<T: Element>(
+        &self,
+        input: &Buffer<T>,
+        output: &Buffer<T>,
+        rows: usize,
+        cols: usize,
+    ) -> ocl::Result<()> {
+        self.enqueue_kernel("transpose", input, output, rows, cols)
+    }
+
+    /// Enqueue a plain copy of `input` into `output` with the same work shape.
+    pub fn enqueue_copy<T: Element>(
+        &self,
+        input: &Buffer<T>,
+        output: &Buffer<T>,
+        rows: usize,
+        cols: usize,
+    ) -> ocl::Result<()> {
+        self.enqueue_kernel("copy", input, output, rows, cols)
+    }
+
+    fn enqueue_kernel<T: Element>(
+        &self,
+        name: &str,
+        input: &Buffer<T>,
+        output: &Buffer<T>,
+        rows: usize,
+        cols: usize,
+    ) -> ocl::Result<()> {
+        // the global size has to be a multiple of the tile size
+        let global_x = cols.div_ceil(TILE_DIM) * TILE_DIM;
+        let global_y = rows.div_ceil(TILE_DIM) * TILE_DIM;
+
+        let kernel = Kernel::builder()
+            .program(&self.program)
+            .name(name)
+            .queue(self.queue.clone())
+            .global_work_size((global_x, global_y))
+            .local_work_size((TILE_DIM, TILE_DIM))
+            .arg(input)
+            .arg(output)
+            .arg(&(rows as i32))
+            .arg(&(cols as i32))
+            .build()?;
+
+        unsafe {
+            kernel.enq()?;
+        }
+        Ok(())
+    }
+}
+
+/// Transpose a row-major `rows x cols` matrix on the device.
+pub fn transpose<T: Element>(
+    queue: &Queue,
+    input: &[T],
+    rows: usize,
+    cols: usize,
+) -> ocl::Result<Vec<T>> {
+    check_len("matrix size", rows * cols, input.len())?;
+
+    let transpose = Transpose::new::<T>(queue)?;
+    let (buff_in, buff_out) = create_buffers(queue, input)?;
+    transpose.enqueue(&buff_in, &buff_out, rows, cols)?;
+
+    let mut out = vec![T::default(); input.len()];
+    buff_out.read(&mut out).enq()?;
+    Ok(out)
+}
+
+/// Effective bandwidth of the transpose compared with a plain copy kernel.
+#[derive(Debug, Clone, Copy)]
+pub struct BandwidthReport {
+    /// Bytes read plus bytes written by one launch.
+    pub bytes: u64,
+    /// Average time of one copy launch in seconds.
+    pub copy_secs: f64,
+    /// Average time of one transpose launch in seconds.
+    pub transpose_secs: f64,
+}
+
+impl BandwidthReport {
+    pub fn copy_gbps(&self) -> f64 {
+        self.bytes as f64 / self.copy_secs / 1e9
+    }
+
+    pub fn transpose_gbps(&self) -> f64 {
+        self.bytes as f64 / self.transpose_secs / 1e9
+    }
+}
+
+/// Time `iterations` launches of the copy and transpose kernels on a `rows x cols` matrix.
+pub fn measure_bandwidth<T: Element>(
+    queue: &Queue,
+    rows: usize,
+    cols: usize,
+    iterations: u32,
+) -> ocl::Result<BandwidthReport> {
+    let transpose = Transpose::new::<T>(queue)?;
+    let input = vec![T::default(); rows * cols];
+    let (buff_in, buff_out) = create_buffers(queue, &input)?;
+
+    let time = |copy: bool| -> ocl::Result<f64> {
+        let launch = || {
+            if copy {
+                transpose.enqueue_copy(&buff_in, &buff_out, rows, cols)
+            } else {
+                transpose.enqueue(&buff_in, &buff_out, rows, cols)
+            }
+        };
+
+        // warm up once before timing
+        launch()?;
+        queue.finish()?;
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            launch()?;
+        }
+        queue.finish()?;
+        Ok(start.elapsed().as_secs_f64() / iterations.max(1) as f64)
+    };
+
+    let copy_secs = time(true)?;
+    let transpose_secs = time(false)?;
+
+    Ok(BandwidthReport {
+        bytes: 2 * std::mem::size_of_val(input.as_slice()) as u64,
+        copy_secs,
+        transpose_secs,
+    })
+}
+
+fn create_buffers<T: Element>(queue: &Queue, input: &[T]) -> ocl::Result<(Buffer<T>, Buffer<T>)> {
+    let buff_in = Buffer::<T>::builder()
+        .queue(queue.clone())
+        .len(input.len())
+        .copy_host_slice(input)
+        .build()?;
+
+    let buff_out = Buffer::<T>::builder()
+        .queue(queue.clone())
+        .len(input.len())
+        .build()?;
+
+    Ok((buff_in, buff_out))
+}
@@ -0,0 +1,166 @@
+//! Local work-size auto-tuning.
+//!
+//! The tuner times candidate local work sizes of a kernel on its device and caches the
+//! fastest one on disk, keyed by device name, kernel and global work size. The candidates
+//! stay within `CL_KERNEL_WORK_GROUP_SIZE` and the device's work-item sizes, and divide the
+//! global work size as OpenCL 1.x requires.
+
+use std::time::{Duration, Instant};
+
+use ocl::enums::{DeviceInfo, DeviceInfoResult, KernelWorkGroupInfo, KernelWorkGroupInfoResult};
+use ocl::{Kernel, SpatialDims};
+
+pub use lab_common::tune::{cache_key, CACHE_ENV};
+
+/// The smallest work-group worth trying, unless the global size is smaller.
+const MIN_WORK_ITEMS: usize = 16;
+
+/// Tuned local work sizes, shared with the other backend.
+pub type TuneCache = lab_common::tune::TuneCache<usize>;
+
+/// Power-of-two local sizes dividing `global` with at most `max_work_group` work-items and
+/// at most `max_items` per dimension. A 1D global size has `global[1] == 1`.
+pub fn local_size_candidates(
+    global: [usize; 2],
+    max_work_group: usize,
+    max_items: [usize; 2],
+) -> Vec<[usize; 2]> {
+    let powers = |max: usize| (0..).map(|i| 1usize << i).take_while(move |&n| n <= max);
+    let min_items = MIN_WORK_ITEMS.min(global[0] * global[1]);
+    let mut candidates = Vec::new();
+    for x in powers(max_items[0].min(global[0])) {
+        for y in powers(max_items[1].min(global[1])) {
+            if global[0] % x == 0
+                && global[1] % y == 0
+                && (min_items..=max_work_group).contains(&(x * y))
+            {
+                candidates.push([x, y]);
+            }
+        }
+    }
+    candidates
+}
+
+/// Picks local work sizes from the cache, or by timing the candidates.
+pub struct Tuner {
+    cache: TuneCache,
+    iterations: u32,
+}
+
+impl Tuner {
+    pub fn new(cache: TuneCache) -> Self {
+        Self {
+            cache,
+            iterations: 10,
+        }
+    }
+
+    /// Launches timed per candidate.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Set the default local work size of `kernel` (named `name` in the cache) to the cached
+    /// size for its device and global work size, else to the fastest candidate, which is
+    /// then cached. `None` when no candidate divides the global work size, e.g. an odd image
+    /// width; the driver then picks the local work size. The kernel must have a default queue
+    /// and global work size, and its arguments must be set since the candidates are launched.
+    pub fn tune_kernel(
+        &mut self,
+        kernel: &mut Kernel,
+        name: &str,
+    ) -> ocl::Result<Option<[usize; 2]>> {
+        let queue = kernel
+            .default_queue()
+            .ok_or_else(|| "the kernel has no default queue".to_string())?
+            .clone();
+        let device = queue.device();
+        let lens = kernel.default_global_work_size().to_lens()?;
+        let global = [lens[0], lens[1].max(1)];
+        let key = cache_key(
+            &device.name()?,
+            name,
+            &format!("{}x{}", global[0], global[1]),
+        );
+
+        let size = match self.cache.get(&key) {
+            Some(size) => size,
+            None => {
+                let max_work_group =
+                    match kernel.wg_info(device, KernelWorkGroupInfo::WorkGroupSize)? {
+                        KernelWorkGroupInfoResult::WorkGroupSize(size) => size,
+                        other => return Err(format!("unexpected kernel info: {other:?}").into()),
+                    };
+                let max_items = match device.info(DeviceInfo::MaxWorkItemSizes)? {
+                    DeviceInfoResult::MaxWorkItemSizes(sizes) => {
+                        [sizes[0], sizes.get(1).copied().unwrap_or(1)]
+                    }
+                    other => return Err(format!("unexpected device info: {other:?}").into()),
+                };
+                let one_dim = lens[1] <= 1;
+
+                let mut best: Option<([usize; 2], Duration)> = None;
+                for candidate in local_size_candidates(global, max_work_group, max_items) {
+                    let local = if one_dim {
+                        SpatialDims::One(candidate[0])
+                    } else {
+                        SpatialDims::Two(candidate[0], candidate[1])
+                    };
+                    // warm up once before timing
+                    unsafe { kernel.cmd().local_work_size(local).enq()? };
+                    queue.finish()?;
+
+                    let start = Instant::now();
+                    for _ in 0..self.iterations {
+                        unsafe { kernel.cmd().local_work_size(local).enq()? };
+                    }
+                    queue.finish()?;
+                    let elapsed = start.elapsed() / self.iterations;
+                    log::debug!("{key}: {candidate:?} took {elapsed:?}");
+                    if best.is_none_or(|(_, fastest)| elapsed < fastest) {
+                        best = Some((candidate, elapsed));
+                    }
+                }
+
+                let Some((size, elapsed)) = best else {
+                    log::info!(
+                        "{key}: no local work size divides the global size, left to the driver"
+                    );
+                    kernel.set_default_local_work_size(SpatialDims::Unspecified);
+                    return Ok(None);
+                };
+                log::info!("{key}: tuned to {size:?} ({elapsed:?})");
+                self.cache.insert(key, size);
+                self.cache.save().map_err(|err| err.to_string())?;
+                size
+            }
+        };
+
+        kernel.set_default_local_work_size(if lens[1] <= 1 {
+            SpatialDims::One(size[0])
+        } else {
+            SpatialDims::Two(size[0], size[1])
+        });
+        Ok(Some(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::local_size_candidates;
+
+    #[test]
+    pub fn test_tune_helpers() {
+        let candidates = local_size_candidates([1024, 768], 256, [1024, 1024]);
+        assert!(candidates.contains(&[16, 16]));
+        assert!(candidates.contains(&[256, 1]));
+        assert!(!candidates.contains(&[32, 16]));
+        assert!(!candidates.contains(&[2, 2]));
+
+        // only divisors of the global size
+        assert_eq!(local_size_candidates([48, 1], 256, [1024, 1]), [[16, 1]]);
+        // none for a prime size, which is left to the driver
+        assert!(local_size_candidates([1021, 1021], 256, [1024, 1024]).is_empty());
+    }
+}
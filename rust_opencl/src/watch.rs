@@ -0,0 +1,47 @@
+//! Hot reload of the kernels during development.
+//!
+//! The kernels are embedded at compile time. With a source directory set, by
+//! [`SOURCE_DIR`] or the `OCL_KERNEL_DIR` environment variable, [`source`] reads them from
+//! that directory instead, so a program built again picks up the edits. `SOURCE_DIR.run`
+//! sets the directory and reruns a job each time a file in it changes, printing the build
+//! errors instead of exiting:
+//!
+//! ```no_run
+//! # let queue = lab_opencl::device::default_queue(None).unwrap();
+//! # let image = vec![0.0; 64 * 64];
+//! lab_opencl::watch::SOURCE_DIR.run("kernels", || {
+//!     let kernel = lab_opencl::ops::ImageKernel::rotation(&queue, 64, 64, 0.5)?;
+//!     let out = kernel.run(&image)?;
+//!     println!("{}", out.iter().sum::<f32>());
+//!     Ok::<_, ocl::Error>(())
+//! });
+//! ```
+
+use std::borrow::Cow;
+use std::path::Path;
+
+pub use lab_common::watch::{SourceDir, Watcher};
+
+/// Environment variable of the source directory.
+pub const SOURCE_DIR_ENV: &str = "OCL_KERNEL_DIR";
+
+/// The directory the kernels are read from, if any.
+pub static SOURCE_DIR: SourceDir = SourceDir::new(SOURCE_DIR_ENV);
+
+/// The source of the kernel file `name`, e.g. `kernels/rotation.cl`, from the source
+/// directory if there is one, else `embedded`. The file is looked up by its file name.
+///
+/// A file that can not be read falls back to `embedded` with a warning.
+pub fn source(name: &str, embedded: &'static str) -> Cow<'static, str> {
+    let Some(dir) = SOURCE_DIR.get() else {
+        return Cow::Borrowed(embedded);
+    };
+    let path = dir.join(Path::new(name).file_name().unwrap_or_default());
+    match std::fs::read_to_string(&path) {
+        Ok(source) => Cow::Owned(source),
+        Err(err) => {
+            log::warn!("using the embedded {name} - {}: {err}", path.display());
+            Cow::Borrowed(embedded)
+        }
+    }
+}
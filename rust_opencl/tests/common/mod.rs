@@ -0,0 +1,19 @@
+//! Device setup shared by the integration tests.
+
+use lab_opencl::device;
+use ocl::Queue;
+
+/// A queue on the selected device (a GPU, else a CPU implementation such as PoCL), `None` to
+/// skip the test when there is none. Logs the device so CI output shows what was tested.
+pub fn queue() -> Option<Queue> {
+    match device::default_queue(None) {
+        Ok(queue) => {
+            eprintln!("using OpenCL device: {}", device::describe(&queue));
+            Some(queue)
+        }
+        Err(err) => {
+            eprintln!("skipped: no OpenCL device available ({err})");
+            None
+        }
+    }
+}
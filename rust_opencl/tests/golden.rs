@@ -0,0 +1,129 @@
+//! Runs the kernels and compares them with the CPU references.
+
+mod common;
+
+use lab_opencl::element::F16;
+use lab_opencl::reference::{self, ErrorStats};
+use lab_opencl::{ops, utils::generate_gaussian_kernel};
+
+const COLS: u32 = 45;
+const ROWS: u32 = 31;
+
+/// A deterministic single channel test image with values in [0, 1].
+fn test_image() -> Vec<f32> {
+    (0..COLS * ROWS)
+        .map(|i| ((i * 7919) % 256) as f32 / 255.0)
+        .collect()
+}
+
+fn assert_within(op: &str, stats: ErrorStats, max_abs: f32, mean_abs: f32) {
+    assert!(
+        stats.within(max_abs, mean_abs),
+        "{op}: {stats:?} exceeds max_abs {max_abs}, mean_abs {mean_abs}"
+    );
+}
+
+#[test]
+fn vector_add_matches_reference() {
+    let Some(queue) = common::queue() else { return };
+    let a: Vec<f32> = (0..1000).map(|v| v as f32 * 0.5).collect();
+    let b: Vec<f32> = (0..1000).map(|v| (v as f32).sin()).collect();
+
+    let out = ops::vector_add(&queue, &a, &b).unwrap();
+    let stats = ErrorStats::compare(&out, &reference::vector_add(&a, &b));
+    assert_within("vector_add", stats, 0.0, 0.0);
+}
+
+#[test]
+fn convolution_matches_reference() {
+    let Some(queue) = common::queue() else { return };
+    let image = test_image();
+    let filter = generate_gaussian_kernel(2, 1.0);
+
+    let out = ops::convolution(&queue, &image, COLS, ROWS, &filter).unwrap();
+    let expected = reference::convolution(&image, COLS as usize, ROWS as usize, &filter);
+    assert_within(
+        "convolution",
+        ErrorStats::compare(&out, &expected),
+        1e-5,
+        1e-6,
+    );
+}
+
+#[test]
+fn rotation_matches_reference() {
+    let Some(queue) = common::queue() else { return };
+    let image = test_image();
+    let theta = 30f32.to_radians();
+
+    // devices may filter images with 8-bit interpolation weights
+    let out = ops::rotation(&queue, &image, COLS, ROWS, theta).unwrap();
+    let expected = reference::rotation(&image, COLS as usize, ROWS as usize, theta);
+    assert_within("rotation", ErrorStats::compare(&out, &expected), 1e-2, 1e-3);
+}
+
+#[test]
+fn bicubic_rotation_matches_reference() {
+    let Some(queue) = common::queue() else { return };
+    let image = test_image();
+    let theta = 30f32.to_radians();
+
+    let kernel = ops::ImageKernel::bicubic_rotation(&queue, COLS, ROWS, theta).unwrap();
+    let out = kernel.run(&image).unwrap();
+    let expected = reference::bicubic_rotation(&image, COLS as usize, ROWS as usize, theta);
+    assert_within(
+        "bicubic rotation",
+        ErrorStats::compare(&out, &expected),
+        1e-5,
+        1e-6,
+    );
+}
+
+#[test]
+fn histogram_matches_reference() {
+    let Some(queue) = common::queue() else { return };
+    let data: Vec<i32> = (0..100_000).map(|i| (i * 7919) % 256).collect();
+
+    let out = ops::histogram(&queue, &data).unwrap();
+    assert_eq!(out, reference::histogram(&data));
+}
+
+#[test]
+fn integer_and_half_pixels_match_reference() {
+    let Some(queue) = common::queue() else { return };
+    let image = test_image();
+    let filter = generate_gaussian_kernel(2, 1.0);
+    let theta = 30f32.to_radians();
+
+    // 8-bit values, rounded to the nearest integer on the device
+    let scaled: Vec<f32> = image.iter().map(|v| (v * 255.0).round()).collect();
+    let pixels: Vec<u32> = scaled.iter().map(|&v| v as u32).collect();
+    let out = ops::convolution(&queue, &pixels, COLS, ROWS, &filter).unwrap();
+    let out: Vec<f32> = out.into_iter().map(|v| v as f32).collect();
+    let expected = reference::convolution(&scaled, COLS as usize, ROWS as usize, &filter);
+    assert_within(
+        "u32 convolution",
+        ErrorStats::compare(&out, &expected),
+        1.0,
+        0.25,
+    );
+
+    let pixels: Vec<F16> = image.iter().map(|&v| F16::from(v)).collect();
+    let out = ops::rotation(&queue, &pixels, COLS, ROWS, theta).unwrap();
+    let out: Vec<f32> = out.into_iter().map(f32::from).collect();
+    let expected = reference::rotation(&image, COLS as usize, ROWS as usize, theta);
+    assert_within(
+        "f16 rotation",
+        ErrorStats::compare(&out, &expected),
+        2e-3,
+        5e-4,
+    );
+
+    // the float values over [0, 1] fall in the bins of the integers they were scaled from
+    let data: Vec<f32> = (0..100_000)
+        .map(|i| ((i * 7919) % 256) as f32 / 256.0)
+        .collect();
+    let expected =
+        reference::histogram(&(0..100_000).map(|i| (i * 7919) % 256).collect::<Vec<_>>());
+    assert_eq!(ops::histogram(&queue, &data).unwrap(), expected);
+}
@@ -0,0 +1,139 @@
+//! Chains of image kernels, job files and batches give the same results as the separate ops.
+
+mod common;
+
+use lab_opencl::batch::{self, BatchOptions};
+use lab_opencl::job::{self, Job};
+use lab_opencl::pipeline::ImagePipeline;
+use lab_opencl::{ops, reference, utils::generate_gaussian_kernel};
+
+const COLS: u32 = 45;
+const ROWS: u32 = 31;
+
+#[test]
+fn chain_matches_separate_ops() {
+    let Some(queue) = common::queue() else { return };
+    let image: Vec<f32> = (0..COLS * ROWS)
+        .map(|i| ((i * 7919) % 256) as f32 / 255.0)
+        .collect();
+    let filter = generate_gaussian_kernel(2, 1.0);
+    let theta = 30f32.to_radians();
+
+    let mut pipeline = ImagePipeline::new(&queue, COLS, ROWS);
+    pipeline.convolution(&filter).unwrap();
+    let blurred = pipeline.output();
+    pipeline.rotation(theta).unwrap();
+    let rotated = pipeline.output();
+    let histogram = pipeline.histogram().unwrap();
+    let outputs = pipeline.run(&image).unwrap();
+
+    let expected_blurred = ops::convolution(&queue, &image, COLS, ROWS, &filter).unwrap();
+    let expected_rotated = ops::rotation(&queue, &expected_blurred, COLS, ROWS, theta).unwrap();
+    assert_eq!(outputs.image(blurred), expected_blurred);
+    assert_eq!(outputs.image(rotated), expected_rotated);
+    // scaling by the power of two bins is exact, so the bins match the reference
+    assert_eq!(
+        outputs.histogram(histogram),
+        reference::image_histogram(&expected_rotated)
+    );
+
+    // the intermediates are reused across kernels and runs
+    assert_eq!(pipeline.images(), 2);
+    let again = pipeline.run(&image).unwrap();
+    assert_eq!(again, outputs);
+    assert_eq!(pipeline.images(), 2);
+}
+
+#[test]
+fn job_runs_as_a_pipeline() {
+    let Some(queue) = common::queue() else { return };
+    let input = std::env::temp_dir().join(format!("job-{}.png", std::process::id()));
+    let pixels: Vec<u8> = (0..COLS * ROWS).map(|i| (i * 7919 % 256) as u8).collect();
+    image::save_buffer(&input, &pixels, COLS, ROWS, image::ColorType::L8).unwrap();
+
+    let job = Job::from_json(&format!(
+        r#"{{"input": {input:?}, "ops": [
+            {{"op": "rotate", "degrees": 30}},
+            {{"op": "histogram", "bins": 16}}
+        ]}}"#
+    ))
+    .unwrap();
+    let output = job::run(&job, &queue);
+    std::fs::remove_file(&input).unwrap();
+    let output = output.unwrap();
+
+    let image: Vec<f32> = pixels.iter().map(|&v| v as f32 / 255.0).collect();
+    let expected = ops::rotation(&queue, &image, COLS, ROWS, 30f32.to_radians()).unwrap();
+    assert_eq!((output.cols, output.rows), (COLS, ROWS));
+    assert_eq!(output.image, expected);
+    assert_eq!(output.histograms.len(), 1);
+    assert_eq!(output.histograms[0].len(), 16);
+    assert_eq!(
+        output.histograms[0].iter().sum::<i32>(),
+        (COLS * ROWS) as i32
+    );
+}
+
+#[test]
+fn batch_matches_single_jobs() {
+    let Some(queue) = common::queue() else { return };
+    let dir = std::env::temp_dir().join(format!("batch-{}", std::process::id()));
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    std::fs::create_dir_all(input_dir.join("sub")).unwrap();
+    // more sizes than the batch keeps pipelines for, sub/c.png may need a dropped one again
+    let files = [
+        ("a.png", COLS, ROWS),
+        ("b.png", ROWS, COLS),
+        ("d.png", 20, 12),
+        ("e.png", 12, 20),
+        ("f.png", 33, 17),
+        ("sub/c.png", COLS, ROWS),
+    ];
+    for (seed, (file, cols, rows)) in files.iter().enumerate() {
+        let pixels: Vec<u8> = (0..cols * rows)
+            .map(|i| ((i + seed as u32) * 7919 % 256) as u8)
+            .collect();
+        image::save_buffer(
+            input_dir.join(file),
+            &pixels,
+            *cols,
+            *rows,
+            image::ColorType::L8,
+        )
+        .unwrap();
+    }
+    std::fs::write(input_dir.join("broken.png"), "not a png").unwrap();
+
+    let job = |input: &std::path::Path| {
+        Job::from_json(&format!(
+            r#"{{"input": {input:?}, "ops": [
+                {{"op": "gaussian_blur", "radius": 2, "sigma": 1.0}},
+                {{"op": "rotate", "degrees": 30}},
+                {{"op": "histogram", "bins": 16}}
+            ]}}"#
+        ))
+        .unwrap()
+    };
+    let options = BatchOptions {
+        threads: 2,
+        in_flight: 2,
+    };
+    let report = batch::run(&queue, &job(&dir).ops, &input_dir, &output_dir, &options).unwrap();
+    assert_eq!(report.processed, files.len());
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, std::path::Path::new("broken.png"));
+
+    for (file, cols, rows) in files {
+        let output = batch::output_path(&output_dir, file.as_ref());
+        let expected = job::run(&job(&input_dir.join(file)), &queue).unwrap();
+        let saved = image::open(&output).unwrap().to_luma8();
+        assert_eq!(saved.dimensions(), (cols, rows));
+        let expected: Vec<u8> = expected.image.iter().map(|v| (v * 255.0) as u8).collect();
+        assert_eq!(saved.into_raw(), expected, "{file}");
+
+        let histograms = std::fs::read_to_string(output.with_extension("histogram.json"));
+        let histograms: Vec<Vec<i32>> = serde_json::from_str(&histograms.unwrap()).unwrap();
+        assert_eq!(histograms[0].iter().sum::<i32>(), (cols * rows) as i32);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
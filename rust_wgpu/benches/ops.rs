@@ -0,0 +1,152 @@
+//! Upload, compute and download times of the ops over vector lengths, image sizes and
+//! filter sizes.
+//!
+//! `cargo bench` runs everything, `cargo bench -- convolution` one op. The results also go
+//! to `target/bench-results/wgpu.{csv,json}`.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, Criterion, Throughput};
+use lab_common::bench::{self, OpGroup};
+use rust_wgpu::ops::{ImageShader, VectorAdd, CHANNELS};
+use rust_wgpu::{generate_gaussian_kernel, WgpuState};
+
+const VECTOR_LENS: [usize; 3] = [1 << 16, 1 << 20, 1 << 22];
+const IMAGE_SIZES: [u32; 3] = [256, 512, 1024];
+const FILTER_RADII: [i32; 3] = [1, 2, 4];
+
+fn state() -> Option<&'static WgpuState> {
+    static STATE: OnceLock<Option<WgpuState>> = OnceLock::new();
+    STATE
+        .get_or_init(|| {
+            let state = pollster::block_on(WgpuState::try_init());
+            match &state {
+                Some(state) => println!("using wgpu adapter: {}", state.describe()),
+                None => println!("skipped: no wgpu adapter available"),
+            }
+            state
+        })
+        .as_ref()
+}
+
+/// Time `iters` runs of `f`, waiting for the device to finish the submitted work.
+fn time_device(state: &WgpuState, iters: u64, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iters {
+        f();
+    }
+    // flush the queued writes as well
+    state.queue.submit(None);
+    state.device.poll(wgpu::Maintain::Wait);
+    start.elapsed()
+}
+
+fn vector_add(c: &mut Criterion) {
+    let Some(state) = state() else { return };
+    let mut group = OpGroup::new(c, "vector_add");
+
+    for len in VECTOR_LENS {
+        let op = VectorAdd::new(state, len).expect("failed to create the shader");
+        let a: Vec<f32> = (0..len).map(|v| v as f32).collect();
+        let bytes = (len * std::mem::size_of::<f32>()) as u64;
+        let size = len.to_string();
+
+        group.phase("upload", &size, Throughput::Bytes(2 * bytes), |iters| {
+            time_device(state, iters, || {
+                op.upload(&a, &a)
+                    .expect("the inputs have the length of the op")
+            })
+        });
+        // two reads and one write per element
+        group.phase("compute", &size, Throughput::Bytes(3 * bytes), |iters| {
+            time_device(state, iters, || op.dispatch())
+        });
+        group.phase("download", &size, Throughput::Bytes(bytes), |iters| {
+            time_device(state, iters, || {
+                pollster::block_on(op.download()).expect("failed to read the output");
+            })
+        });
+    }
+    group.finish();
+}
+
+fn test_image(cols: u32, rows: u32) -> Vec<f32> {
+    (0..cols * rows * CHANNELS as u32)
+        .map(|i| ((i * 7919) % 256) as f32 / 255.0)
+        .collect()
+}
+
+/// Upload and download depend only on the image size, so they are timed on one shader.
+fn image_transfers(group: &mut OpGroup<'_>, state: &WgpuState, op: &ImageShader<'_>) {
+    let (cols, rows) = (op.cols(), op.rows());
+    let image = test_image(cols, rows);
+    let bytes = image.len() as u64 * std::mem::size_of::<f32>() as u64;
+    let size = format!("{cols}x{rows}");
+
+    group.phase("upload", &size, Throughput::Bytes(bytes), |iters| {
+        time_device(state, iters, || {
+            op.upload(&image).expect("the image has the size of the op")
+        })
+    });
+    group.phase("download", &size, Throughput::Bytes(bytes), |iters| {
+        time_device(state, iters, || {
+            pollster::block_on(op.download()).expect("failed to read the output");
+        })
+    });
+}
+
+fn convolution(c: &mut Criterion) {
+    let Some(state) = state() else { return };
+    let mut group = OpGroup::new(c, "convolution");
+
+    for n in IMAGE_SIZES {
+        let pixels = Throughput::Elements((n * n) as u64);
+        for radius in FILTER_RADII {
+            let filter = generate_gaussian_kernel(radius, 1.0);
+            let op = ImageShader::convolution(state, n, n, &filter)
+                .expect("failed to create the shader");
+            if radius == FILTER_RADII[0] {
+                image_transfers(&mut group, state, &op);
+            }
+            let size = format!("{n}x{n} filter {0}x{0}", 2 * radius + 1);
+            group.phase("compute", &size, pixels.clone(), |iters| {
+                time_device(state, iters, || op.dispatch())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn rotation(c: &mut Criterion) {
+    let Some(state) = state() else { return };
+    let mut group = OpGroup::new(c, "rotation");
+
+    for n in IMAGE_SIZES {
+        let op = ImageShader::rotation(state, n, n, 30f32.to_radians())
+            .expect("failed to create the shader");
+        image_transfers(&mut group, state, &op);
+        group.phase(
+            "compute",
+            &format!("{n}x{n}"),
+            Throughput::Elements((n * n) as u64),
+            |iters| time_device(state, iters, || op.dispatch()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = bench::config();
+    targets = vector_add, convolution, rotation
+}
+
+fn main() {
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+
+    if let Some(state) = state() {
+        bench::write_results("wgpu", &state.describe()).expect("failed to write the results");
+    }
+}
@@ -0,0 +1,461 @@
+//! Generates typed Rust bindings for the shaders in `wgsl/`, see `src/shaders.rs`.
+//!
+//! Each shader becomes a module with the workgroup sizes of its entry points, a
+//! `#[repr(C)]` Pod struct for each WGSL struct that has a fixed host layout, and a
+//! `BindGroup<n>` struct per bind group with one field per binding. The shaders are
+//! preprocessed with the default defines first, see `src/preprocess.rs`.
+
+use std::fmt::Write;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "src/preprocess.rs"]
+mod preprocess;
+
+use preprocess::{Defines, Preprocessor};
+
+/// Directory of the sources for `#include`.
+const INCLUDE_DIR: &str = "wgsl/include";
+
+/// Shaders with the element type `T` left to the host are generated with `T = f32`.
+const ELEMENT_ALIAS: &str = "alias T = f32;\n";
+
+fn main() {
+    println!("cargo:rerun-if-changed=wgsl");
+    println!("cargo:rerun-if-changed=src/preprocess.rs");
+    let mut paths: Vec<_> = std::fs::read_dir("wgsl")
+        .expect("failed to read wgsl/")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wgsl"))
+        .collect();
+    paths.sort();
+
+    let mut out = generate_includes();
+    for path in &paths {
+        out += &generate_module(path);
+    }
+    let out_path = Path::new(&std::env::var("OUT_DIR").unwrap()).join("shaders.rs");
+    std::fs::write(out_path, out).expect("failed to write the shader bindings");
+}
+
+/// The sources of the include directory, by file name.
+fn generate_includes() -> String {
+    let mut includes: Vec<_> = std::fs::read_dir(INCLUDE_DIR)
+        .map(|entries| entries.map(|entry| entry.unwrap().path()).collect())
+        .unwrap_or_default();
+    includes.sort();
+
+    let mut out = String::new();
+    writeln!(out, "/// The sources of `{INCLUDE_DIR}/`, by file name.").unwrap();
+    writeln!(out, "pub const INCLUDES: &[(&str, &str)] = &[").unwrap();
+    for path in includes {
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let absolute = std::fs::canonicalize(&path).unwrap();
+        writeln!(
+            out,
+            "    ({name:?}, include_str!({:?})),",
+            absolute.display().to_string()
+        )
+        .unwrap();
+    }
+    writeln!(out, "];\n").unwrap();
+    out
+}
+
+fn generate_module(path: &Path) -> String {
+    let file = path.display().to_string();
+    let name = path.file_stem().unwrap().to_str().unwrap();
+    // the bindings are generated for the default defines
+    let source = Preprocessor::new()
+        .include_dir(INCLUDE_DIR)
+        .run(
+            &file,
+            &std::fs::read_to_string(path).unwrap(),
+            &Defines::new(),
+        )
+        .unwrap_or_else(|err| panic!("{err}"));
+    let (module, templated) = match naga::front::wgsl::parse_str(&source) {
+        Ok(module) => (module, false),
+        Err(err) => match naga::front::wgsl::parse_str(&format!("{ELEMENT_ALIAS}{source}")) {
+            Ok(module) => (module, true),
+            Err(_) => panic!("{}", err.emit_to_string_with_path(&source, &file)),
+        },
+    };
+
+    let mut out = String::new();
+    writeln!(out, "/// Bindings of `wgsl/{name}.wgsl`.").unwrap();
+    if templated {
+        writeln!(out, "///\n/// The shader leaves the element type `T` to the host; the structs are laid out with `T = f32`.").unwrap();
+    }
+    writeln!(out, "pub mod {} {{", ident(name)).unwrap();
+    writeln!(out, "    /// The source before preprocessing.").unwrap();
+    let absolute = std::fs::canonicalize(path).unwrap();
+    writeln!(
+        out,
+        "    pub const SOURCE: &str = include_str!({:?});",
+        absolute.display().to_string()
+    )
+    .unwrap();
+
+    for entry_point in &module.entry_points {
+        if entry_point.stage != naga::ShaderStage::Compute {
+            continue;
+        }
+        let constant = if entry_point.name == "main" {
+            "WORKGROUP_SIZE".to_string()
+        } else {
+            format!("{}_WORKGROUP_SIZE", entry_point.name.to_uppercase())
+        };
+        writeln!(out, "    /// `@workgroup_size` of `{}`.", entry_point.name).unwrap();
+        writeln!(
+            out,
+            "    pub const {constant}: [u32; 3] = {:?};",
+            entry_point.workgroup_size
+        )
+        .unwrap();
+    }
+
+    let mut structs = Vec::new();
+    for (handle, ty) in module.types.iter() {
+        let naga::TypeInner::Struct { ref members, span } = ty.inner else {
+            continue;
+        };
+        let Some(name) = &ty.name else { continue };
+        match generate_struct(&module, name, members, span, &structs) {
+            Ok(code) => {
+                out += &code;
+                structs.push(handle);
+            }
+            Err(reason) => writeln!(out, "    // `{name}` has no host struct: {reason}").unwrap(),
+        }
+    }
+
+    let stages: std::collections::BTreeSet<_> = module
+        .entry_points
+        .iter()
+        .map(|entry_point| match entry_point.stage {
+            naga::ShaderStage::Vertex => "wgpu::ShaderStages::VERTEX",
+            naga::ShaderStage::Fragment => "wgpu::ShaderStages::FRAGMENT",
+            naga::ShaderStage::Compute => "wgpu::ShaderStages::COMPUTE",
+        })
+        .collect();
+    let visibility = stages
+        .into_iter()
+        .map(str::to_string)
+        .reduce(|stages, stage| format!("{stages}.union({stage})"))
+        .unwrap_or("wgpu::ShaderStages::NONE".to_string());
+
+    let mut bindings: Vec<_> = module
+        .global_variables
+        .iter()
+        .filter_map(|(_, var)| Some((var.binding.clone()?, var)))
+        .collect();
+    bindings.sort_by_key(|(binding, _)| (binding.group, binding.binding));
+    let mut groups: Vec<u32> = bindings.iter().map(|(binding, _)| binding.group).collect();
+    groups.dedup();
+    for group in groups {
+        let vars: Vec<_> = bindings
+            .iter()
+            .filter(|(binding, _)| binding.group == group)
+            .map(|(binding, var)| (binding.binding, *var))
+            .collect();
+        out += &generate_bind_group(&module, group, &vars, &visibility);
+    }
+
+    out += "}\n\n";
+    out
+}
+
+/// A Pod struct with explicit padding, so its layout is the WGSL host layout.
+fn generate_struct(
+    module: &naga::Module,
+    name: &str,
+    members: &[naga::StructMember],
+    span: u32,
+    structs: &[naga::Handle<naga::Type>],
+) -> Result<String, String> {
+    let mut fields = String::new();
+    let mut params = Vec::new();
+    let mut offset = 0;
+    let mut pads = 0;
+    for member in members {
+        let member_name = member.name.clone().unwrap_or_default();
+        let (ty, size) = rust_type(module, member.ty, structs)
+            .ok_or_else(|| format!("member `{member_name}` has no fixed host type"))?;
+        if member.offset > offset {
+            writeln!(
+                fields,
+                "        _pad{pads}: [u8; {}],",
+                member.offset - offset
+            )
+            .unwrap();
+            pads += 1;
+        }
+        writeln!(fields, "        pub {}: {ty},", ident(&member_name)).unwrap();
+        params.push((ident(&member_name), ty));
+        offset = member.offset + size;
+    }
+    if span > offset {
+        writeln!(fields, "        _pad{pads}: [u8; {}],", span - offset).unwrap();
+        pads += 1;
+    }
+
+    let mut out = String::new();
+    writeln!(out, "    #[repr(C)]").unwrap();
+    writeln!(
+        out,
+        "    #[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]"
+    )
+    .unwrap();
+    writeln!(out, "    pub struct {name} {{\n{fields}    }}").unwrap();
+    writeln!(
+        out,
+        "    const _: () = assert!(std::mem::size_of::<{name}>() == {span});"
+    )
+    .unwrap();
+    if pads > 0 {
+        let args: Vec<_> = params
+            .iter()
+            .map(|(name, ty)| format!("{name}: {ty}"))
+            .collect();
+        let names: Vec<_> = params.iter().map(|(name, _)| name.clone()).collect();
+        writeln!(out, "    impl {name} {{").unwrap();
+        if params.len() > 7 {
+            writeln!(out, "        #[allow(clippy::too_many_arguments)]").unwrap();
+        }
+        writeln!(out, "        pub fn new({}) -> Self {{", args.join(", ")).unwrap();
+        writeln!(
+            out,
+            "            Self {{ {}, ..bytemuck::Zeroable::zeroed() }}",
+            names.join(", ")
+        )
+        .unwrap();
+        writeln!(out, "        }}\n    }}").unwrap();
+    }
+    Ok(out)
+}
+
+/// The Rust type and its size for a type with a fixed host layout.
+fn rust_type(
+    module: &naga::Module,
+    ty: naga::Handle<naga::Type>,
+    structs: &[naga::Handle<naga::Type>],
+) -> Option<(String, u32)> {
+    let scalar = |kind, width| match (kind, width) {
+        (naga::ScalarKind::Float, 4) => Some("f32"),
+        (naga::ScalarKind::Float, 8) => Some("f64"),
+        (naga::ScalarKind::Sint, 4) => Some("i32"),
+        (naga::ScalarKind::Uint, 4) => Some("u32"),
+        _ => None,
+    };
+
+    match module.types[ty].inner {
+        naga::TypeInner::Scalar { kind, width } | naga::TypeInner::Atomic { kind, width } => {
+            Some((scalar(kind, width)?.to_string(), width as u32))
+        }
+        naga::TypeInner::Vector { size, kind, width } => Some((
+            format!("[{}; {}]", scalar(kind, width)?, size as u32),
+            width as u32 * size as u32,
+        )),
+        naga::TypeInner::Matrix {
+            columns,
+            rows,
+            width,
+        } => {
+            // columns are aligned like vectors, vec3 columns take four components
+            let rows = if rows == naga::VectorSize::Tri {
+                4
+            } else {
+                rows as u32
+            };
+            Some((
+                format!(
+                    "[[{}; {rows}]; {}]",
+                    scalar(naga::ScalarKind::Float, width)?,
+                    columns as u32
+                ),
+                width as u32 * rows * columns as u32,
+            ))
+        }
+        naga::TypeInner::Array {
+            base,
+            size: naga::ArraySize::Constant(count),
+            stride,
+        } => {
+            let (base, size) = rust_type(module, base, structs)?;
+            (size == stride).then(|| (format!("[{base}; {count}]"), stride * count.get()))
+        }
+        naga::TypeInner::Struct { span, .. } if structs.contains(&ty) => {
+            Some((module.types[ty].name.clone()?, span))
+        }
+        _ => None,
+    }
+}
+
+/// A struct with one field per binding of `group`, and its layout.
+fn generate_bind_group(
+    module: &naga::Module,
+    group: u32,
+    vars: &[(u32, &naga::GlobalVariable)],
+    visibility: &str,
+) -> String {
+    let mut fields = String::new();
+    let mut layout_entries = String::new();
+    let mut entries = String::new();
+    for &(binding, var) in vars {
+        let name = ident(var.name.as_deref().unwrap_or("binding"));
+        let (field, ty, resource) = binding_type(module, var);
+        writeln!(fields, "        /// `@binding({binding})`").unwrap();
+        writeln!(fields, "        pub {name}: {field},").unwrap();
+        writeln!(
+            layout_entries,
+            "                wgpu::BindGroupLayoutEntry {{ binding: {binding}, visibility: {visibility}, ty: {ty}, count: None }},"
+        )
+        .unwrap();
+        let resource = resource.replace("{}", &format!("self.{name}"));
+        writeln!(
+            entries,
+            "                wgpu::BindGroupEntry {{ binding: {binding}, resource: {resource} }},"
+        )
+        .unwrap();
+    }
+
+    let count = vars.len();
+    let mut out = String::new();
+    writeln!(out, "    /// Resources of `@group({group})`.").unwrap();
+    writeln!(
+        out,
+        "    pub struct BindGroup{group}<'a> {{\n{fields}    }}"
+    )
+    .unwrap();
+    writeln!(out, "    impl<'a> BindGroup{group}<'a> {{").unwrap();
+    writeln!(out, "        pub const GROUP: u32 = {group};").unwrap();
+    writeln!(
+        out,
+        "        pub fn layout_entries() -> [wgpu::BindGroupLayoutEntry; {count}] {{"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "            [\n{layout_entries}            ]\n        }}"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "        pub fn create_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {{"
+    )
+    .unwrap();
+    writeln!(out, "            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {{ label: None, entries: &Self::layout_entries() }})").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(
+        out,
+        "        pub fn entries(&self) -> [wgpu::BindGroupEntry<'a>; {count}] {{"
+    )
+    .unwrap();
+    writeln!(out, "            [\n{entries}            ]\n        }}").unwrap();
+    writeln!(out, "        pub fn create(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {{").unwrap();
+    writeln!(out, "            device.create_bind_group(&wgpu::BindGroupDescriptor {{ label: None, layout, entries: &self.entries() }})").unwrap();
+    writeln!(out, "        }}\n    }}").unwrap();
+    out
+}
+
+/// The field type, the layout entry type and the resource of a binding, where `{}` stands for
+/// the field.
+fn binding_type(module: &naga::Module, var: &naga::GlobalVariable) -> (String, String, String) {
+    let buffer = |ty: &str| {
+        // the size with one element for runtime-sized arrays
+        let size = module.types[var.ty].inner.size(module.to_ctx());
+        (
+            "wgpu::BufferBinding<'a>".to_string(),
+            format!("wgpu::BindingType::Buffer {{ ty: {ty}, has_dynamic_offset: false, min_binding_size: std::num::NonZeroU64::new({size}) }}"),
+            "wgpu::BindingResource::Buffer({}.clone())".to_string(),
+        )
+    };
+    let view = |ty: String| {
+        (
+            "&'a wgpu::TextureView".to_string(),
+            ty,
+            "wgpu::BindingResource::TextureView({})".to_string(),
+        )
+    };
+
+    match (var.space, &module.types[var.ty].inner) {
+        (naga::AddressSpace::Uniform, _) => buffer("wgpu::BufferBindingType::Uniform"),
+        (naga::AddressSpace::Storage { access }, _) => buffer(&format!(
+            "wgpu::BufferBindingType::Storage {{ read_only: {} }}",
+            !access.contains(naga::StorageAccess::STORE)
+        )),
+        (_, naga::TypeInner::Sampler { comparison }) => (
+            "&'a wgpu::Sampler".to_string(),
+            format!(
+                "wgpu::BindingType::Sampler(wgpu::SamplerBindingType::{})",
+                if *comparison {
+                    "Comparison"
+                } else {
+                    "NonFiltering"
+                }
+            ),
+            "wgpu::BindingResource::Sampler({})".to_string(),
+        ),
+        (
+            _,
+            naga::TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            },
+        ) => {
+            let view_dimension = match (dim, arrayed) {
+                (naga::ImageDimension::D1, _) => "D1",
+                (naga::ImageDimension::D2, false) => "D2",
+                (naga::ImageDimension::D2, true) => "D2Array",
+                (naga::ImageDimension::D3, _) => "D3",
+                (naga::ImageDimension::Cube, false) => "Cube",
+                (naga::ImageDimension::Cube, true) => "CubeArray",
+            };
+            view(match class {
+                naga::ImageClass::Sampled { kind, multi } => format!(
+                    "wgpu::BindingType::Texture {{ sample_type: wgpu::TextureSampleType::{}, view_dimension: wgpu::TextureViewDimension::{view_dimension}, multisampled: {multi} }}",
+                    match kind {
+                        naga::ScalarKind::Sint => "Sint",
+                        naga::ScalarKind::Uint => "Uint",
+                        _ => "Float { filterable: false }",
+                    }
+                ),
+                naga::ImageClass::Depth { multi } => format!(
+                    "wgpu::BindingType::Texture {{ sample_type: wgpu::TextureSampleType::Depth, view_dimension: wgpu::TextureViewDimension::{view_dimension}, multisampled: {multi} }}"
+                ),
+                naga::ImageClass::Storage { format, access } => format!(
+                    // the storage format names are the same in naga and wgpu
+                    "wgpu::BindingType::StorageTexture {{ access: wgpu::StorageTextureAccess::{}, format: wgpu::TextureFormat::{format:?}, view_dimension: wgpu::TextureViewDimension::{view_dimension} }}",
+                    if !access.contains(naga::StorageAccess::LOAD) {
+                        "WriteOnly"
+                    } else if !access.contains(naga::StorageAccess::STORE) {
+                        "ReadOnly"
+                    } else {
+                        "ReadWrite"
+                    }
+                ),
+            })
+        }
+        _ => panic!(
+            "unsupported binding `{}`",
+            var.name.as_deref().unwrap_or_default()
+        ),
+    }
+}
+
+/// A Rust identifier for a WGSL name.
+fn ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "static", "struct", "trait", "true", "type", "unsafe",
+        "use", "where", "while", "yield",
+    ];
+    match name {
+        "self" | "Self" | "super" | "crate" => format!("{name}_"),
+        _ if KEYWORDS.contains(&name) => format!("r#{name}"),
+        _ => name.to_string(),
+    }
+}
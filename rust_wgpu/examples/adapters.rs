@@ -1,25 +1,20 @@
-#![allow(dead_code)]
-use wgpu::{Adapter, InstanceDescriptor};
+//! Prints the capabilities of every wgpu adapter: `cargo run --example adapters [-- --json]`.
+//!
+//! `WGPU_BACKEND` restricts the backends; see `rust_wgpu::capabilities` for the fields.
 
-async fn run() {
-    let instance = wgpu::Instance::new(InstanceDescriptor::default());
-
-    let adapters: Vec<Adapter> = instance.enumerate_adapters(wgpu::Backends::all()).collect();
-    for adapter in adapters.into_iter() {
-        let info = adapter.get_info();
-        log::info!("Adapter name : {}", info.name);
-        log::info!("\tdevice_type : {:?}", info.device_type);
-        log::info!("\tbackend : {:?}", info.backend);
-        log::info!("\tdriver : {:?}", info.driver);
-        log::info!("\tdriver_info: {:?}", info.driver_info);
-
-        let feature = adapter.features();
-        log::info!("\tfeatures : {:?}", feature);
-    }
-}
+use rust_wgpu::capabilities;
 
 fn main() {
     dotenv::dotenv().ok();
     env_logger::init();
-    pollster::block_on(run());
+
+    let json = std::env::args().skip(1).any(|arg| arg == "--json");
+    let adapters = capabilities::report();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&adapters).unwrap());
+        return;
+    }
+    for adapter in &adapters {
+        println!("{adapter}\n");
+    }
 }
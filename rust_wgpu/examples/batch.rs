@@ -0,0 +1,59 @@
+//! Runs the ops of a job file on every image of a directory:
+//! `cargo run --example batch -- jobs/blur_rotate.toml IN_DIR OUT_DIR [--in-flight N]`.
+//!
+//! The `input` and `output` of the job are ignored; see `rust_wgpu::batch`.
+
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+
+use rust_wgpu::batch::{self, BatchOptions};
+use rust_wgpu::job::{Backend, Job};
+use rust_wgpu::WgpuState;
+
+const USAGE: &str = "usage: batch <job.toml|job.json> <input dir> <output dir> [--in-flight N]";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = BatchOptions::default();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--in-flight" => {
+                options.in_flight = args.next().and_then(|n| n.parse().ok()).ok_or(USAGE)?
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let [job, input_dir, output_dir] = paths[..] else {
+        return Err(USAGE.into());
+    };
+    let job = Job::load(job)?;
+    job.check_backend(Backend::Wgpu)?;
+
+    let init_wgpu = pollster::block_on(WgpuState::init()).expect("Failed to initialize the wgpu");
+    log::info!("Adapter : {}", init_wgpu.describe());
+
+    let start = Instant::now();
+    let report = batch::run(
+        &init_wgpu,
+        &job.ops,
+        Path::new(input_dir),
+        Path::new(output_dir),
+        &options,
+    )?;
+    println!(
+        "{} images in {:?}, {} in flight",
+        report.processed,
+        start.elapsed(),
+        options.in_flight
+    );
+    for (path, err) in &report.failed {
+        println!("failed {}: {err}", path.display());
+    }
+    Ok(())
+}
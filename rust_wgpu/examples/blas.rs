@@ -0,0 +1,32 @@
+use rust_wgpu::{blas::Blas, buffer::GpuBuffer, WgpuState};
+
+const VECTOR_SIZE: usize = 1_000_000;
+
+async fn run() {
+    let init_wgpu = WgpuState::init()
+        .await
+        .expect("Failed to initialize the wgpu");
+
+    let x: Vec<f32> = (0..VECTOR_SIZE).map(|v| (v % 100) as f32 * 0.01).collect();
+    let y: Vec<f32> = vec![1.0; VECTOR_SIZE];
+
+    let x = GpuBuffer::from_slice(&init_wgpu, &x).expect("failed to upload the vector");
+    let y = GpuBuffer::from_slice(&init_wgpu, &y).expect("failed to upload the vector");
+    let blas = Blas::new(&init_wgpu).expect("failed to build the BLAS pipelines");
+
+    // y = 2x + y, then y = 0.5y
+    blas.axpy(2.0, &x, &y)
+        .expect("x and y have the same length");
+    blas.scal(0.5, &y);
+
+    log::info!("dot(x, y) : {:?}", blas.dot(&x, &y).await);
+    log::info!("nrm2(y)   : {:?}", blas.nrm2(&y).await);
+    log::info!("asum(x)   : {:?}", blas.asum(&x).await);
+    log::info!("iamax(x)  : {:?}", blas.iamax(&x).await);
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    pollster::block_on(run());
+}
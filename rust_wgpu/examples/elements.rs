@@ -0,0 +1,49 @@
+use half::f16;
+use rust_wgpu::{blas::Blas, buffer::GpuBuffer, map::MapKernels, WgpuState};
+
+async fn run() {
+    let init_wgpu = WgpuState::init()
+        .await
+        .expect("Failed to initialize the wgpu");
+    let kernels = MapKernels::new(&init_wgpu);
+
+    // i32 -> i32
+    let x: Vec<i32> = (-8..8).collect();
+    let x = GpuBuffer::from_slice(&init_wgpu, &x).expect("failed to upload the vector");
+    let y: GpuBuffer<i32> = kernels
+        .map(&x, "a * a - 3")
+        .expect("invalid map expression");
+    log::info!("i32 : {:?}", y.read(&init_wgpu).await.unwrap());
+
+    // u32 -> f32
+    let x: Vec<u32> = (0..16).collect();
+    let x = GpuBuffer::from_slice(&init_wgpu, &x).expect("failed to upload the vector");
+    let y: GpuBuffer<f32> = kernels
+        .map(&x, "sqrt(f32(a))")
+        .expect("invalid map expression");
+    log::info!("u32 -> f32 : {:?}", y.read(&init_wgpu).await.unwrap());
+
+    // f16, stored packed and computed in f32
+    let x: Vec<f16> = (0..15).map(|v| f16::from_f32(v as f32 * 0.5)).collect();
+    let x = GpuBuffer::from_slice(&init_wgpu, &x).expect("failed to upload the vector");
+    let y: GpuBuffer<f16> = kernels
+        .map(&x, "a * 2.0 + 0.25")
+        .expect("invalid map expression");
+    log::info!("f16 : {:?}", y.read(&init_wgpu).await.unwrap());
+
+    // f64 needs SHADER_F64
+    if init_wgpu.supports::<f64>() {
+        let blas = Blas::<f64>::new(&init_wgpu).expect("failed to build the BLAS pipelines");
+        let x: Vec<f64> = (0..1000).map(|v| v as f64 * 0.001).collect();
+        let x = GpuBuffer::from_slice(&init_wgpu, &x).expect("failed to upload the vector");
+        log::info!("f64 dot : {:?}", blas.dot(&x, &x).await);
+    } else {
+        log::info!("f64 : not supported by the adapter");
+    }
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    pollster::block_on(run());
+}
@@ -0,0 +1,39 @@
+//! Runs an image processing job file: `cargo run --example job -- jobs/blur_rotate.toml`.
+//!
+//! See `lab_common::job` for the format.
+
+use std::error::Error;
+use std::time::Instant;
+
+use rust_wgpu::job::{self, Job};
+use rust_wgpu::WgpuState;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let path = std::env::args()
+        .nth(1)
+        .ok_or("usage: job <job.toml|job.json>")?;
+    let job = Job::load(&path)?;
+
+    let init_wgpu = pollster::block_on(WgpuState::init()).expect("Failed to initialize the wgpu");
+    log::info!("Adapter : {}", init_wgpu.describe());
+
+    let start = Instant::now();
+    let output = pollster::block_on(job::run(&job, &init_wgpu))?;
+    println!(
+        "{path}: {} ops on {}x{} in {:?}",
+        job.ops.len(),
+        output.cols,
+        output.rows,
+        start.elapsed()
+    );
+    for (index, histogram) in output.histograms.iter().enumerate() {
+        println!("histogram {index}: {histogram:?}");
+    }
+    if let Some(out_path) = &job.output {
+        println!("saved {}", out_path.display());
+    }
+    Ok(())
+}
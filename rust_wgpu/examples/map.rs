@@ -0,0 +1,41 @@
+use rust_wgpu::{buffer::GpuBuffer, map::MapKernels, WgpuState};
+
+async fn run() {
+    let init_wgpu = WgpuState::init()
+        .await
+        .expect("Failed to initialize the wgpu");
+
+    let x: Vec<f32> = (0..1024).map(|v| v as f32).collect();
+    let y: Vec<f32> = (0..1024).map(|v| v as f32 * 0.01).collect();
+    let x = GpuBuffer::from_slice(&init_wgpu, &x).expect("failed to upload the vector");
+    let y = GpuBuffer::from_slice(&init_wgpu, &y).expect("failed to upload the vector");
+
+    let kernels = MapKernels::new(&init_wgpu);
+
+    // f32 -> f32 with two inputs
+    let z: GpuBuffer<f32> = kernels
+        .map2(&x, &y, "a * 2.0 + sin(b)")
+        .expect("invalid map expression");
+    // f32 -> u32 with one input, the element index is available as `i`
+    let classes: GpuBuffer<u32> = kernels
+        .map(&x, "(u32(a) + i / 2u) % 3u")
+        .expect("invalid map expression");
+    // same expression again, served from the cache
+    let z2: GpuBuffer<f32> = kernels
+        .map2(&z, &y, "a * 2.0 + sin(b)")
+        .expect("invalid map expression");
+
+    log::info!("z      : {:?}", &z.read(&init_wgpu).await.unwrap()[..8]);
+    log::info!(
+        "classes: {:?}",
+        &classes.read(&init_wgpu).await.unwrap()[..8]
+    );
+    log::info!("z2     : {:?}", &z2.read(&init_wgpu).await.unwrap()[..8]);
+    log::info!("compiled shaders : {}", kernels.cached());
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    pollster::block_on(run());
+}
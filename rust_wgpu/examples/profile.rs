@@ -0,0 +1,60 @@
+use rust_wgpu::{
+    buffer::GpuBuffer, profile::Profiler, trace::Trace, transpose::Transpose, WgpuState,
+};
+
+const ROWS: u32 = 1024;
+const COLS: u32 = 1024;
+
+async fn run() {
+    let init_wgpu = WgpuState::init()
+        .await
+        .expect("Failed to initialize the wgpu");
+    let device = &init_wgpu.device;
+
+    let input: Vec<f32> = (0..ROWS * COLS).map(|v| v as f32).collect();
+    let input = GpuBuffer::from_slice(&init_wgpu, &input).expect("failed to upload the vector");
+    let output =
+        GpuBuffer::<f32>::zeros(&init_wgpu, input.len()).expect("failed to allocate the vector");
+    let copy =
+        GpuBuffer::<f32>::zeros(&init_wgpu, input.len()).expect("failed to allocate the vector");
+    let transpose =
+        Transpose::new::<f32>(&init_wgpu).expect("failed to build the transpose pipelines");
+
+    let mut profiler = Profiler::new(&init_wgpu, 8);
+    log::info!("GPU timestamps : {}", profiler.uses_timestamps());
+
+    // 3 of the 8 submissions the profiler has room for
+    profiler
+        .dispatch("copy kernel", |encoder| {
+            transpose.encode_copy(device, encoder, input.buffer(), output.buffer(), ROWS, COLS)
+        })
+        .unwrap();
+    profiler
+        .dispatch("transpose kernel", |encoder| {
+            transpose.encode(device, encoder, input.buffer(), output.buffer(), ROWS, COLS)
+        })
+        .unwrap();
+    profiler
+        .copy("buffer copy", |encoder| {
+            encoder.copy_buffer_to_buffer(output.buffer(), 0, copy.buffer(), 0, output.size())
+        })
+        .unwrap();
+
+    let report = profiler.report().await.expect("failed to read the timings");
+    log::info!("Profile ({ROWS} x {COLS} f32)\n{report}");
+
+    // TRACE=trace.json writes the timeline
+    if let Ok(path) = std::env::var("TRACE") {
+        Trace::new()
+            .add_queue("queue", &report)
+            .write(&path)
+            .expect("failed to write the trace");
+        log::info!("Trace written to {path}");
+    }
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    pollster::block_on(run());
+}
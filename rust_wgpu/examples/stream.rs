@@ -0,0 +1,38 @@
+use rust_wgpu::{blas::Blas, buffer::GpuBuffer, stream::Streamer, WgpuState};
+
+// 40M elements: 160MB per vector, above the default 128MiB storage binding limit
+const VECTOR_SIZE: usize = 40_000_000;
+// stream the host vectors in 64MB chunks
+const CHUNK_BYTES: usize = 64 << 20;
+
+async fn run() {
+    let init_wgpu = WgpuState::init()
+        .await
+        .expect("Failed to initialize the wgpu");
+
+    let x: Vec<f32> = (0..VECTOR_SIZE).map(|v| (v % 100) as f32 * 0.01).collect();
+    let y: Vec<f32> = vec![1.0; VECTOR_SIZE];
+
+    // device-resident vectors bigger than one binding are processed window by window
+    let blas = Blas::new(&init_wgpu).expect("failed to build the BLAS pipelines");
+    let buff_x = GpuBuffer::from_slice(&init_wgpu, &x).expect("failed to upload the vector");
+    log::info!("device asum(x) : {:?}", blas.asum(&buff_x).await);
+    drop(buff_x);
+
+    // host vectors are streamed through the device chunk by chunk
+    let streamer = Streamer::new(&init_wgpu)
+        .expect("failed to build the BLAS pipelines")
+        .with_chunk_bytes(CHUNK_BYTES);
+    log::info!("stream dot(x, y) : {:?}", streamer.dot(&x, &y).await);
+    log::info!("stream nrm2(x)   : {:?}", streamer.nrm2(&x).await);
+    log::info!("stream iamax(x)  : {:?}", streamer.iamax(&x).await);
+
+    let z: Option<Vec<f32>> = streamer.zip(&[&x, &y], "a + b").await;
+    log::info!("stream a + b     : {:?}", z.map(|z| z[VECTOR_SIZE - 1]));
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    pollster::block_on(run());
+}
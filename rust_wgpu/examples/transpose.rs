@@ -0,0 +1,33 @@
+use rust_wgpu::{transpose::measure_bandwidth, transpose::transpose, WgpuState};
+
+const ROWS: u32 = 1000;
+const COLS: u32 = 1500;
+const ITERATIONS: u32 = 20;
+
+async fn run() {
+    let init_wgpu = WgpuState::init()
+        .await
+        .expect("Failed to initialize the wgpu");
+
+    // check the result against the host transpose
+    let input: Vec<f32> = (0..ROWS * COLS).map(|v| v as f32).collect();
+    if let Some(out) = transpose(&init_wgpu, &input, ROWS, COLS).await {
+        let matched = (0..ROWS).all(|r| {
+            (0..COLS).all(|c| out[(c * ROWS + r) as usize] == input[(r * COLS + c) as usize])
+        });
+        log::info!("Transpose ({ROWS} x {COLS}) matched : {matched}");
+    }
+
+    let report = measure_bandwidth::<f32>(&init_wgpu, 2048, 2048, ITERATIONS)
+        .await
+        .expect("failed to build the transpose pipelines");
+    log::info!("Bandwidth (2048 x 2048 f32)");
+    log::info!("\tcopy      : {:.2} GB/s", report.copy_gbps());
+    log::info!("\ttranspose : {:.2} GB/s", report.transpose_gbps());
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    pollster::block_on(run());
+}
@@ -0,0 +1,46 @@
+use rust_wgpu::ops::{ImageShader, CHANNELS};
+use rust_wgpu::tune::{TuneCache, Tuner};
+use rust_wgpu::{generate_gaussian_kernel, WgpuState};
+
+const COLS: u32 = 1024;
+const ROWS: u32 = 1024;
+
+async fn run() {
+    let init_wgpu = WgpuState::init()
+        .await
+        .expect("Failed to initialize the wgpu");
+    log::info!("Adapter : {}", init_wgpu.describe());
+
+    let cache = TuneCache::open_default().expect("failed to read the tune cache");
+    let mut tuner = Tuner::new(cache);
+    let image = vec![0.5f32; (COLS * ROWS) as usize * CHANNELS];
+    let filter = generate_gaussian_kernel(2, 1.0);
+
+    let mut shaders = [
+        (
+            "convolution 5x5",
+            ImageShader::convolution(&init_wgpu, COLS, ROWS, &filter)
+                .expect("failed to create the shader"),
+        ),
+        (
+            "rotation",
+            ImageShader::rotation(&init_wgpu, COLS, ROWS, 0.5)
+                .expect("failed to create the shader"),
+        ),
+    ];
+    for (name, shader) in &mut shaders {
+        shader
+            .upload(&image)
+            .expect("the image has the size of the shader");
+        let size = shader
+            .tune(&mut tuner)
+            .expect("failed to write the tune cache");
+        log::info!("{name} ({COLS} x {ROWS}) : workgroup size {size:?}");
+    }
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    pollster::block_on(run());
+}
@@ -0,0 +1,48 @@
+//! Reruns an image shader each time a file in `wgsl/` changes, for editing shaders without
+//! restarting: `cargo run --example watch -- [rotation|convolution]`.
+//!
+//! Each run reads the shader from `wgsl/`, saves the output image and prints how long the
+//! dispatch took, or prints the shader error.
+
+use std::error::Error;
+use std::time::Instant;
+
+use image::GenericImageView;
+use rust_wgpu::ops::ImageShader;
+use rust_wgpu::{generate_gaussian_kernel, save_img, watch, WgpuState};
+
+const IMAGE_PATH: &str = "data/cat.png";
+const SIZE: u32 = 512;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let job = std::env::args().nth(1).unwrap_or("rotation".to_string());
+    let img = image::open(IMAGE_PATH)?.resize(SIZE, SIZE, image::imageops::FilterType::Triangle);
+    let (cols, rows) = img.dimensions();
+    let image = img.to_rgba32f().into_raw();
+    let out_path = format!("data/cat_{job}_watch_out.png");
+
+    let init_wgpu = pollster::block_on(WgpuState::init()).expect("Failed to initialize the wgpu");
+    log::info!("Adapter : {}", init_wgpu.describe());
+
+    watch::SOURCE_DIR.run("wgsl", || -> Result<(), Box<dyn Error>> {
+        let shader = match job.as_str() {
+            "rotation" => ImageShader::rotation(&init_wgpu, cols, rows, 0.5)?,
+            "convolution" => {
+                ImageShader::convolution(&init_wgpu, cols, rows, &generate_gaussian_kernel(2, 1.0))?
+            }
+            _ => {
+                return Err(format!("unknown job `{job}`, expected rotation or convolution").into())
+            }
+        };
+        let start = Instant::now();
+        let out = pollster::block_on(shader.run(&image)).ok_or("failed to read the output")?;
+        init_wgpu.check_device()?;
+        let elapsed = start.elapsed();
+        save_img(&out_path, &out, cols, rows)?;
+        println!("{job}: {elapsed:?}, saved {out_path}");
+        Ok(())
+    })
+}
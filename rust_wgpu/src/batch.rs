@@ -0,0 +1,176 @@
+//! Runs the ops of a job on every image of a directory.
+//!
+//! [`run`] decodes the images on a few threads and keeps up to `in_flight` of them submitted
+//! to the device. Images of the same size share one pipeline and its pooled textures, which
+//! the queue reuses in submission order, but each submission reads back into staging
+//! buffers of its own, so the downloads of one image overlap the uploads and passes of the
+//! next ones. The results are encoded on the same threads and saved as PNG under the same
+//! relative path of the output directory, with `.png` appended (see [`output_path`]); the
+//! histograms of an image, if the job has any, go next to it, e.g.
+//! `sub/a.jpg.histogram.json` for `sub/a.jpg`.
+//!
+//! ```no_run
+//! # let state = pollster::block_on(rust_wgpu::WgpuState::init()).unwrap();
+//! use rust_wgpu::batch::{self, BatchOptions};
+//! use rust_wgpu::job::Job;
+//!
+//! let job = Job::load("jobs/blur_rotate.toml")?;
+//! let report = batch::run(&state, &job.ops, "photos".as_ref(), "out".as_ref(), &BatchOptions::default())?;
+//! println!("{} images, {} failed", report.processed, report.failed.len());
+//! # Ok::<_, rust_wgpu::job::JobError>(())
+//! ```
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Mutex;
+
+use lab_common::lru::Lru;
+
+use crate::job::{JobError, JobOutput, JobPipeline, Op};
+use crate::pipeline::Submission;
+use crate::{save_img, WgpuState};
+
+// the options, the listing and the output paths are shared with the other backend
+pub use lab_common::batch::{images, output_path, BatchOptions, BatchReport};
+
+/// Pipelines a batch keeps for images of different sizes, or `in_flight` if more.
+const MAX_PIPELINES: usize = 4;
+
+/// A decoded image, `cols x rows` RGBA.
+type Decoded = Result<(u32, u32, Vec<f32>), String>;
+
+/// Run `ops` on the images under `input_dir` and save the results under `output_dir`.
+///
+/// An image that can not be read or saved is reported and skipped; a device error stops
+/// the batch. Images of different sizes get a pipeline each; the least recently used one
+/// is dropped beyond `MAX_PIPELINES` sizes. The `ops` are those of a
+/// [`Job`](crate::job::Job) that passed [`validate`](crate::job::Job::validate).
+pub fn run(
+    state: &WgpuState,
+    ops: &[Op],
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &BatchOptions,
+) -> Result<BatchReport, JobError> {
+    let files = images(input_dir, output_dir);
+    let next = AtomicUsize::new(0);
+    let processed = AtomicUsize::new(0);
+    let failed = Mutex::new(Vec::new());
+    let (decoded_sender, decoded) = sync_channel::<(PathBuf, Decoded)>(options.in_flight);
+    let (done, done_receiver) = sync_channel::<(PathBuf, JobOutput)>(options.in_flight);
+    let done_receiver = Mutex::new(done_receiver);
+
+    let result = std::thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            let (files, next, sender) = (&files, &next, decoded_sender.clone());
+            scope.spawn(move || {
+                while let Some(relative) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let decoded = decode(&input_dir.join(relative));
+                    // the receiver is gone when the batch stopped
+                    if sender.send((relative.clone(), decoded)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let (done_receiver, processed, failed) = (&done_receiver, &processed, &failed);
+            scope.spawn(move || loop {
+                let Ok((relative, output)) = done_receiver.lock().unwrap().recv() else {
+                    break;
+                };
+                match save(output_dir, &relative, &output) {
+                    Ok(()) => {
+                        processed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(err) => failed.lock().unwrap().push((relative, err)),
+                }
+            });
+        }
+        drop(decoded_sender);
+        submit_all(state, ops, decoded, done, options.in_flight.max(1), &failed)
+    });
+
+    result?;
+    let mut failed = failed.into_inner().unwrap();
+    failed.sort();
+    Ok(BatchReport {
+        processed: processed.into_inner(),
+        failed,
+    })
+}
+
+fn decode(path: &Path) -> Decoded {
+    let image = image::open(path).map_err(|err| err.to_string())?;
+    let (cols, rows) = (image.width(), image.height());
+    Ok((cols, rows, image.to_rgba32f().into_raw()))
+}
+
+fn save(output_dir: &Path, relative: &Path, output: &JobOutput) -> Result<(), String> {
+    lab_common::batch::save(output_dir, relative, &output.histograms, |path| {
+        save_img(
+            &path.to_string_lossy(),
+            &output.image,
+            output.cols,
+            output.rows,
+        )
+        .map_err(|err| err.to_string())
+    })
+}
+
+/// Submit the decoded images, finishing the oldest submission once `in_flight` are pending,
+/// and pass the results to the encoders.
+fn submit_all(
+    state: &WgpuState,
+    ops: &[Op],
+    decoded: Receiver<(PathBuf, Decoded)>,
+    done: SyncSender<(PathBuf, JobOutput)>,
+    in_flight: usize,
+    failed: &Mutex<Vec<(PathBuf, String)>>,
+) -> Result<(), JobError> {
+    // the pending submissions are of the last `in_flight` images, whose pipelines are kept
+    // as finishing does not mark them used
+    let mut pipelines = Lru::new(MAX_PIPELINES.max(in_flight));
+    let mut pending = Pending::new();
+
+    for (relative, decoded) in decoded {
+        let (cols, rows, image) = match decoded {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                failed.lock().unwrap().push((relative, err));
+                continue;
+            }
+        };
+        if pending.len() >= in_flight {
+            finish_oldest(&mut pipelines, &mut pending, &done)?;
+        }
+        let pipeline = pipelines
+            .get_or_try_insert_with((cols, rows), || JobPipeline::new(state, ops, cols, rows))
+            .map_err(JobError::device)?;
+        let submission = pipeline.pipeline.submit(&image).map_err(JobError::device)?;
+        pending.push_back((relative, (cols, rows), submission));
+    }
+    while !pending.is_empty() {
+        finish_oldest(&mut pipelines, &mut pending, &done)?;
+    }
+    Ok(())
+}
+
+type Pending = VecDeque<(PathBuf, (u32, u32), Submission)>;
+
+fn finish_oldest(
+    pipelines: &mut Lru<(u32, u32), JobPipeline>,
+    pending: &mut Pending,
+    done: &SyncSender<(PathBuf, JobOutput)>,
+) -> Result<(), JobError> {
+    let Some((relative, size, submission)) = pending.pop_front() else {
+        return Ok(());
+    };
+    let pipeline = pipelines.peek_mut(&size).unwrap();
+    let outputs =
+        pollster::block_on(pipeline.pipeline.finish(submission)).map_err(JobError::device)?;
+    // the encoders only stop when the batch does
+    done.send((relative, pipeline.output(&outputs))).ok();
+    Ok(())
+}
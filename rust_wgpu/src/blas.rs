@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+
+use crate::buffer::GpuBuffer;
+use crate::element::{with_element, Float};
+use crate::error::{check_len, log_error, Error, Result};
+use crate::{grid_stride_workgroups, watch, WgpuState};
+
+/// Workgroup size of the BLAS shader.
+pub const WORKGROUP_SIZE: u32 = 256;
+/// Number of workgroups of the first reduction stage, i.e. the length of the partials.
+const REDUCE_WORKGROUPS: u32 = 256;
+
+const ENTRY_POINTS: [&str; 12] = [
+    "axpy",
+    "scal",
+    "mul",
+    "copy",
+    "dot_partial",
+    "sum_partial",
+    "asum_partial",
+    "sumsq_partial",
+    "sum_final",
+    "sqrt_final",
+    "iamax_partial",
+    "iamax_final",
+];
+
+/// Uniform parameters of the shader; `alpha` comes first so `T = f64` needs no padding.
+#[derive(Clone, Copy)]
+struct Params<T> {
+    alpha: T,
+    n: u32,
+    offset: u32,
+}
+
+impl<T: Float> Params<T> {
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = bytemuck::bytes_of(&self.alpha).to_vec();
+        bytes.extend_from_slice(bytemuck::bytes_of(&self.n));
+        bytes.extend_from_slice(bytemuck::bytes_of(&self.offset));
+        bytes
+    }
+}
+
+/// BLAS level-1 operations on device-resident float vectors, `f32` unless specified.
+///
+/// `Blas::<f64>` needs a device with `SHADER_F64`.
+///
+/// Every call records and submits its own command buffer; submissions run in order on the
+/// queue, so the calls can be chained without reading back in between. The reductions keep
+/// the partial sums on the device and only the final scalar is read back.
+///
+/// Vectors larger than `max_storage_buffer_binding_size` are processed window by window, and
+/// the dispatches loop over the elements, so any buffer the device can allocate is accepted.
+pub struct Blas<'a, T: Float = f32> {
+    state: &'a WgpuState,
+    pipelines: HashMap<&'static str, wgpu::ComputePipeline>,
+    element: PhantomData<T>,
+}
+
+/// A binding of the BLAS shader and the vector bound to it.
+type Binding<'b, T> = (u32, &'b GpuBuffer<T>);
+
+impl<'a, T: Float> Blas<'a, T> {
+    /// Build the pipelines of every entry point; an error when the device does not support
+    /// `T` or the shader fails to compile.
+    pub fn new(state: &'a WgpuState) -> Result<Self> {
+        if !state.supports::<T>() {
+            return Err(Error::Argument(format!(
+                "the device does not support {} in shaders",
+                T::WGSL_TYPE
+            )));
+        }
+        let shader = state.create_shader_module(
+            "BLAS-1 Shader",
+            &with_element::<T>(&watch::source(
+                "blas1.wgsl",
+                include_str!("../wgsl/blas1.wgsl"),
+            ))?,
+        )?;
+
+        // each entry point uses a different subset of the bindings,
+        // so the layouts are derived from the shader.
+        let pipelines = ENTRY_POINTS
+            .iter()
+            .map(|&entry_point| {
+                let pipeline = state.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: None,
+                    module: &shader,
+                    entry_point,
+                })?;
+                Ok((entry_point, pipeline))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            state,
+            pipelines,
+            element: PhantomData,
+        })
+    }
+
+    /// y = alpha * x + y
+    pub fn axpy(&self, alpha: T, x: &GpuBuffer<T>, y: &GpuBuffer<T>) -> Result<()> {
+        check_len("vector length", y.len(), x.len())?;
+        self.elementwise("axpy", alpha, &[(0, x), (2, y)]);
+        Ok(())
+    }
+
+    /// x = alpha * x
+    pub fn scal(&self, alpha: T, x: &GpuBuffer<T>) {
+        self.elementwise("scal", alpha, &[(2, x)]);
+    }
+
+    /// out = a * b, element-wise. `out` must not alias `a` or `b`.
+    pub fn mul(&self, a: &GpuBuffer<T>, b: &GpuBuffer<T>, out: &GpuBuffer<T>) -> Result<()> {
+        check_len("vector length", a.len(), b.len())?;
+        check_len("vector length", a.len(), out.len())?;
+        self.elementwise("mul", T::zeroed(), &[(0, a), (1, b), (2, out)]);
+        Ok(())
+    }
+
+    /// y = x
+    pub fn copy(&self, x: &GpuBuffer<T>, y: &GpuBuffer<T>) -> Result<()> {
+        check_len("vector length", y.len(), x.len())?;
+        self.elementwise("copy", T::zeroed(), &[(0, x), (2, y)]);
+        Ok(())
+    }
+
+    /// Dot product of `x` and `y`, left on the device as a one-element buffer.
+    pub fn dot_device(&self, x: &GpuBuffer<T>, y: &GpuBuffer<T>) -> Result<GpuBuffer<T>> {
+        check_len("vector length", x.len(), y.len())?;
+        self.reduce_sum("dot_partial", "sum_final", &[(0, x), (1, y)])
+    }
+
+    /// Sum of the elements of `x`, left on the device as a one-element buffer.
+    pub fn sum_device(&self, x: &GpuBuffer<T>) -> Result<GpuBuffer<T>> {
+        self.reduce_sum("sum_partial", "sum_final", &[(0, x)])
+    }
+
+    /// Euclidean norm of `x`, left on the device as a one-element buffer.
+    pub fn nrm2_device(&self, x: &GpuBuffer<T>) -> Result<GpuBuffer<T>> {
+        self.reduce_sum("sumsq_partial", "sqrt_final", &[(0, x)])
+    }
+
+    /// Sum of the absolute values of `x`, left on the device as a one-element buffer.
+    pub fn asum_device(&self, x: &GpuBuffer<T>) -> Result<GpuBuffer<T>> {
+        self.reduce_sum("asum_partial", "sum_final", &[(0, x)])
+    }
+
+    /// Index of the first element with the largest absolute value, left on the device.
+    pub fn iamax_device(&self, x: &GpuBuffer<T>) -> Result<GpuBuffer<u32>> {
+        let device = &self.state.device;
+        let windows = x.windows(&device.limits());
+        let num_partials = windows.len() * REDUCE_WORKGROUPS as usize;
+        let partials = GpuBuffer::<T>::zeros(self.state, num_partials)?;
+        let partial_idx = GpuBuffer::<u32>::zeros(self.state, num_partials)?;
+        let result_idx = GpuBuffer::<u32>::zeros(self.state, 1)?;
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for (i, window) in windows.into_iter().enumerate() {
+            let partial_range = partial_window(i);
+            let params = Params {
+                n: window.len() as u32,
+                alpha: T::zeroed(),
+                offset: window.start as u32,
+            };
+            self.encode(
+                &mut encoder,
+                "iamax_partial",
+                params,
+                REDUCE_WORKGROUPS,
+                vec![
+                    (0, x.binding(window)),
+                    (4, partials.binding(partial_range.clone())),
+                    (5, partial_idx.binding(partial_range)),
+                ],
+            );
+        }
+        self.encode(
+            &mut encoder,
+            "iamax_final",
+            params(num_partials),
+            1,
+            vec![
+                (4, partials.binding(0..num_partials)),
+                (5, partial_idx.binding(0..num_partials)),
+                (7, result_idx.binding(0..1)),
+            ],
+        );
+        self.state.queue.submit(Some(encoder.finish()));
+
+        Ok(result_idx)
+    }
+
+    /// Dot product of `x` and `y`.
+    pub async fn dot(&self, x: &GpuBuffer<T>, y: &GpuBuffer<T>) -> Option<T> {
+        let dot = self.dot_device(x, y).map_err(log_error).ok()?;
+        read_scalar(self.state, dot).await
+    }
+
+    /// Sum of the elements of `x`.
+    pub async fn sum(&self, x: &GpuBuffer<T>) -> Option<T> {
+        let sum = self.sum_device(x).map_err(log_error).ok()?;
+        read_scalar(self.state, sum).await
+    }
+
+    /// Euclidean norm of `x`.
+    pub async fn nrm2(&self, x: &GpuBuffer<T>) -> Option<T> {
+        let nrm2 = self.nrm2_device(x).map_err(log_error).ok()?;
+        read_scalar(self.state, nrm2).await
+    }
+
+    /// Sum of the absolute values of `x`.
+    pub async fn asum(&self, x: &GpuBuffer<T>) -> Option<T> {
+        let asum = self.asum_device(x).map_err(log_error).ok()?;
+        read_scalar(self.state, asum).await
+    }
+
+    /// Index of the first element with the largest absolute value, `None` for an empty vector.
+    pub async fn iamax(&self, x: &GpuBuffer<T>) -> Option<u32> {
+        if x.is_empty() {
+            return None;
+        }
+        let iamax = self.iamax_device(x).map_err(log_error).ok()?;
+        read_scalar(self.state, iamax).await
+    }
+
+    fn elementwise(&self, entry_point: &str, alpha: T, vectors: &[Binding<T>]) {
+        let device = &self.state.device;
+        let windows = vectors[0].1.windows(&device.limits());
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for window in windows {
+            let params = Params {
+                n: window.len() as u32,
+                alpha,
+                offset: window.start as u32,
+            };
+            let bindings = vectors
+                .iter()
+                .map(|&(binding, vector)| (binding, vector.binding(window.clone())))
+                .collect();
+            self.encode(
+                &mut encoder,
+                entry_point,
+                params,
+                grid_stride_workgroups(window.len(), WORKGROUP_SIZE),
+                bindings,
+            );
+        }
+        self.state.queue.submit(Some(encoder.finish()));
+    }
+
+    fn reduce_sum(
+        &self,
+        partial_entry: &str,
+        final_entry: &str,
+        vectors: &[Binding<T>],
+    ) -> Result<GpuBuffer<T>> {
+        let device = &self.state.device;
+        let windows = vectors[0].1.windows(&device.limits());
+        let num_partials = windows.len() * REDUCE_WORKGROUPS as usize;
+        let partials = GpuBuffer::<T>::zeros(self.state, num_partials)?;
+        let result = GpuBuffer::<T>::zeros(self.state, 1)?;
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for (i, window) in windows.into_iter().enumerate() {
+            let params = Params {
+                n: window.len() as u32,
+                alpha: T::zeroed(),
+                offset: window.start as u32,
+            };
+            let mut bindings: Vec<_> = vectors
+                .iter()
+                .map(|&(binding, vector)| (binding, vector.binding(window.clone())))
+                .collect();
+            bindings.push((4, partials.binding(partial_window(i))));
+            self.encode(
+                &mut encoder,
+                partial_entry,
+                params,
+                REDUCE_WORKGROUPS,
+                bindings,
+            );
+        }
+        self.encode(
+            &mut encoder,
+            final_entry,
+            params(num_partials),
+            1,
+            vec![
+                (4, partials.binding(0..num_partials)),
+                (6, result.binding(0..1)),
+            ],
+        );
+        self.state.queue.submit(Some(encoder.finish()));
+
+        Ok(result)
+    }
+
+    fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        entry_point: &str,
+        params: Params<T>,
+        workgroups: u32,
+        bindings: Vec<(u32, wgpu::BindingResource)>,
+    ) {
+        let device = &self.state.device;
+        let pipeline = &self.pipelines[entry_point];
+
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &params.bytes(),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let mut entries: Vec<wgpu::BindGroupEntry> = bindings
+            .into_iter()
+            .map(|(binding, resource)| wgpu::BindGroupEntry { binding, resource })
+            .collect();
+        entries.push(wgpu::BindGroupEntry {
+            binding: 3,
+            resource: params_buffer.as_entire_binding(),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        });
+
+        let mut compute_pass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+    }
+}
+
+/// Parameters of a final reduction stage over `n` partials.
+fn params<T: Float>(n: usize) -> Params<T> {
+    Params {
+        n: n as u32,
+        alpha: T::zeroed(),
+        offset: 0,
+    }
+}
+
+/// Range of the partials written by the first reduction stage of window `i`.
+fn partial_window(i: usize) -> Range<usize> {
+    let len = REDUCE_WORKGROUPS as usize;
+    i * len..(i + 1) * len
+}
+
+async fn read_scalar<T: crate::element::Element>(
+    state: &WgpuState,
+    buffer: GpuBuffer<T>,
+) -> Option<T> {
+    buffer.read(state).await.map(|v| v[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::element::with_element;
+
+    #[test]
+    pub fn test_shader_f64() {
+        let source = with_element::<f64>(include_str!("../wgsl/blas1.wgsl")).unwrap();
+        let module = naga::front::wgsl::parse_str(&source).expect("invalid shader");
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::FLOAT64,
+        )
+        .validate(&module)
+        .expect("invalid f64 shader");
+    }
+}
@@ -0,0 +1,197 @@
+use std::marker::PhantomData;
+use std::num::NonZeroU64;
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+
+use crate::element::Element;
+use crate::error::{check_len, Error, Result};
+use crate::WgpuState;
+
+/// Device-resident storage buffer of `len` elements of type `T`.
+pub struct GpuBuffer<T> {
+    buffer: wgpu::Buffer,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Element> GpuBuffer<T> {
+    const USAGE: wgpu::BufferUsages = wgpu::BufferUsages::STORAGE
+        .union(wgpu::BufferUsages::COPY_SRC)
+        .union(wgpu::BufferUsages::COPY_DST);
+
+    /// Upload the host data into a new buffer; an error when it exceeds the device's
+    /// `max_buffer_size`, see [`crate::stream::Streamer`] for larger vectors.
+    pub fn from_slice(state: &WgpuState, data: &[T]) -> Result<Self> {
+        // wgpu does not allow zero-sized bindings, so keep at least one element.
+        if data.is_empty() {
+            return Self::zeros(state, 0);
+        }
+        check_buffer_size(state, align_size(std::mem::size_of_val(data)))?;
+        // the contents are padded to `COPY_BUFFER_ALIGNMENT` by wgpu
+        let buffer = state.error_scope(|device| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(data),
+                usage: Self::USAGE,
+            })
+        })?;
+
+        Ok(Self {
+            buffer,
+            len: data.len(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Create a zero-initialized buffer; an error when it exceeds the device's
+    /// `max_buffer_size`.
+    pub fn zeros(state: &WgpuState, len: usize) -> Result<Self> {
+        let size = align_size(len.max(1) * std::mem::size_of::<T>());
+        check_buffer_size(state, size)?;
+        let buffer = state.error_scope(|device| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size,
+                usage: Self::USAGE,
+                mapped_at_creation: false,
+            })
+        })?;
+
+        Ok(Self {
+            buffer,
+            len,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size of the elements in bytes.
+    pub fn size(&self) -> wgpu::BufferAddress {
+        (self.len * std::mem::size_of::<T>()) as wgpu::BufferAddress
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Binding of the elements in `range`.
+    pub fn binding(&self, range: Range<usize>) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(self.buffer_binding(range))
+    }
+
+    /// Buffer binding of the elements in `range`, e.g. for the [`shaders`](crate::shaders)
+    /// bind groups.
+    pub fn buffer_binding(&self, range: Range<usize>) -> wgpu::BufferBinding<'_> {
+        let size = std::mem::size_of::<T>();
+        wgpu::BufferBinding {
+            buffer: &self.buffer,
+            offset: (range.start * size) as wgpu::BufferAddress,
+            size: NonZeroU64::new(align_size(range.len().max(1) * size)),
+        }
+    }
+
+    /// Split the elements into windows that fit in one storage binding of the device.
+    pub fn windows(&self, limits: &wgpu::Limits) -> Vec<Range<usize>> {
+        let size = std::mem::size_of::<T>();
+        split_windows(self.len, size, size, limits)
+    }
+
+    /// Overwrite the buffer with the host data, which must have the buffer's length.
+    pub fn write(&self, queue: &wgpu::Queue, data: &[T]) -> Result<()> {
+        check_len("buffer length", self.len, data.len())?;
+        let mut bytes = bytemuck::cast_slice::<T, u8>(data).to_vec();
+        bytes.resize(align_size(bytes.len()) as usize, 0);
+        queue.write_buffer(&self.buffer, 0, &bytes);
+        Ok(())
+    }
+
+    /// Read the buffer back to the host.
+    pub async fn read(&self, state: &WgpuState) -> Option<Vec<T>> {
+        if self.is_empty() {
+            return Some(Vec::new());
+        }
+        let mut data = state
+            .read_buffer(&self.buffer, align_size(self.size() as usize))
+            .await?;
+        data.truncate(self.len);
+        Some(data)
+    }
+}
+
+/// [`Error::Argument`] for buffers the device can't allocate, instead of a validation error.
+fn check_buffer_size(state: &WgpuState, size: wgpu::BufferAddress) -> Result<()> {
+    let max = state.device.limits().max_buffer_size;
+    if size > max {
+        return Err(Error::Argument(format!(
+            "a buffer of {size} bytes exceeds the max_buffer_size of {max} bytes"
+        )));
+    }
+    Ok(())
+}
+
+/// Round a size in bytes up to `COPY_BUFFER_ALIGNMENT`, which also keeps packed 16-bit
+/// elements in whole `u32` words.
+fn align_size(size: usize) -> wgpu::BufferAddress {
+    let align = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+    (size.div_ceil(align) * align) as wgpu::BufferAddress
+}
+
+/// Split `len` elements into windows that fit in one storage binding.
+///
+/// The vectors bound together may have different element sizes: the window length is limited
+/// by the largest element size, and the window offsets stay aligned to
+/// `min_storage_buffer_offset_alignment` for the smallest one.
+pub fn split_windows(
+    len: usize,
+    min_size: usize,
+    max_size: usize,
+    limits: &wgpu::Limits,
+) -> Vec<Range<usize>> {
+    if len == 0 {
+        return std::iter::once(0..0).collect();
+    }
+    let align = (limits.min_storage_buffer_offset_alignment as usize / min_size).max(1);
+    let max_len = limits.max_storage_buffer_binding_size as usize / max_size;
+    let window = (max_len / align * align).max(align);
+
+    (0..len)
+        .step_by(window)
+        .map(|start| start..(start + window).min(len))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_windows;
+
+    #[test]
+    pub fn test_split_windows() {
+        let limits = wgpu::Limits {
+            max_storage_buffer_binding_size: 1024,
+            min_storage_buffer_offset_alignment: 256,
+            ..Default::default()
+        };
+
+        // 4-byte elements: 256 per window
+        let windows = split_windows(600, 4, 4, &limits);
+        assert_eq!(windows, vec![0..256, 256..512, 512..600]);
+
+        // 2-byte and 8-byte elements: 128 per window, offsets aligned for both
+        let windows = split_windows(300, 2, 8, &limits);
+        assert_eq!(windows, vec![0..128, 128..256, 256..300]);
+        assert!(windows.iter().all(|w| (w.start * 2) % 256 == 0));
+
+        // an empty vector still gets one (empty) window
+        let windows = split_windows(0, 4, 4, &limits);
+        assert_eq!(windows.len(), 1);
+        assert!(windows[0].is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+//! What each wgpu adapter can do, as text or JSON, e.g. to pick an adapter for a job.
+//!
+//! ```no_run
+//! for adapter in rust_wgpu::capabilities::report() {
+//!     println!("{adapter}");
+//! }
+//! println!("{}", serde_json::to_string_pretty(&rust_wgpu::capabilities::report()).unwrap());
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::WgpuState;
+
+/// The capabilities of one adapter: its info, every feature it has and all its limits.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AdapterCapabilities {
+    pub name: String,
+    pub vendor: u32,
+    pub device: u32,
+    /// E.g. `DiscreteGpu` or `Cpu`.
+    pub device_type: String,
+    /// E.g. `Vulkan` or `Gl`.
+    pub backend: String,
+    pub driver: String,
+    pub driver_info: String,
+    /// The names of the [`wgpu::Features`], e.g. `SHADER_F64`.
+    pub features: Vec<String>,
+    /// The [`wgpu::Limits`] by field name.
+    pub limits: BTreeMap<&'static str, u64>,
+}
+
+impl AdapterCapabilities {
+    pub fn query(adapter: &wgpu::Adapter) -> Self {
+        let info = adapter.get_info();
+        Self {
+            name: info.name,
+            vendor: info.vendor,
+            device: info.device,
+            device_type: format!("{:?}", info.device_type),
+            backend: format!("{:?}", info.backend),
+            driver: info.driver,
+            driver_info: info.driver_info,
+            features: adapter
+                .features()
+                .iter_names()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            limits: limits(&adapter.limits()),
+        }
+    }
+}
+
+/// The capabilities of the adapters of the backends `WGPU_BACKEND` allows.
+pub fn report() -> Vec<AdapterCapabilities> {
+    WgpuState::enumerate_adapters()
+        .iter()
+        .map(AdapterCapabilities::query)
+        .collect()
+}
+
+/// The fields of `limits` by name.
+pub fn limits(limits: &wgpu::Limits) -> BTreeMap<&'static str, u64> {
+    macro_rules! fields {
+        ($($field:ident),* $(,)?) => {
+            BTreeMap::from([$((stringify!($field), u64::from(limits.$field))),*])
+        };
+    }
+    fields!(
+        max_texture_dimension_1d,
+        max_texture_dimension_2d,
+        max_texture_dimension_3d,
+        max_texture_array_layers,
+        max_bind_groups,
+        max_bindings_per_bind_group,
+        max_dynamic_uniform_buffers_per_pipeline_layout,
+        max_dynamic_storage_buffers_per_pipeline_layout,
+        max_sampled_textures_per_shader_stage,
+        max_samplers_per_shader_stage,
+        max_storage_buffers_per_shader_stage,
+        max_storage_textures_per_shader_stage,
+        max_uniform_buffers_per_shader_stage,
+        max_uniform_buffer_binding_size,
+        max_storage_buffer_binding_size,
+        max_vertex_buffers,
+        max_buffer_size,
+        max_vertex_attributes,
+        max_vertex_buffer_array_stride,
+        min_uniform_buffer_offset_alignment,
+        min_storage_buffer_offset_alignment,
+        max_inter_stage_shader_components,
+        max_compute_workgroup_storage_size,
+        max_compute_invocations_per_workgroup,
+        max_compute_workgroup_size_x,
+        max_compute_workgroup_size_y,
+        max_compute_workgroup_size_z,
+        max_compute_workgroups_per_dimension,
+        max_push_constant_size,
+    )
+}
+
+impl fmt::Display for AdapterCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({}, {})", self.name, self.backend, self.device_type)?;
+        writeln!(
+            f,
+            "  ids        vendor {:#06x}, device {:#06x}",
+            self.vendor, self.device
+        )?;
+        writeln!(f, "  driver     {} {}", self.driver, self.driver_info)?;
+        writeln!(f, "  features   {}", self.features.join(" "))?;
+        write!(f, "  limits")?;
+        for (name, value) in &self.limits {
+            write!(f, "\n    {name:<48} {value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::limits;
+
+    #[test]
+    pub fn test_limits() {
+        let defaults = wgpu::Limits::default();
+        let fields = limits(&defaults);
+        assert_eq!(
+            fields["max_bind_groups"],
+            u64::from(defaults.max_bind_groups)
+        );
+        assert_eq!(fields["max_buffer_size"], defaults.max_buffer_size);
+        assert_eq!(
+            fields["max_compute_invocations_per_workgroup"],
+            u64::from(defaults.max_compute_invocations_per_workgroup)
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::error::{Error, Result};
+use crate::preprocess::Defines;
+
+/// Element types that can be stored in a storage buffer and used by the shaders.
+///
+/// The shaders refer to the element type as `T`; the host prepends `alias T = <WGSL_TYPE>;`
+/// to the source before compiling it.
+///
+/// `half::f16` is stored as packed pairs in `array<u32>` and computed as `f32` through
+/// `unpack2x16float`/`pack2x16float`, because the WGSL front-end of this wgpu version has no
+/// `f16` type. Only the element-wise kernels of [`crate::map`] handle packed types.
+pub trait Element: bytemuck::Pod {
+    /// Name of the WGSL scalar type the shaders compute with.
+    const WGSL_TYPE: &'static str;
+    /// Device features required to use the type in a shader.
+    const FEATURES: wgpu::Features = wgpu::Features::empty();
+    /// Two elements are packed in one `u32` of the storage buffer.
+    const PACKED: bool = false;
+}
+
+impl Element for f32 {
+    const WGSL_TYPE: &'static str = "f32";
+}
+
+impl Element for u32 {
+    const WGSL_TYPE: &'static str = "u32";
+}
+
+impl Element for i32 {
+    const WGSL_TYPE: &'static str = "i32";
+}
+
+impl Element for f64 {
+    const WGSL_TYPE: &'static str = "f64";
+    const FEATURES: wgpu::Features = wgpu::Features::SHADER_F64;
+}
+
+impl Element for half::f16 {
+    const WGSL_TYPE: &'static str = "f32";
+    const PACKED: bool = true;
+}
+
+/// Element types of the image shaders, the channel type of the RGBA textures.
+///
+/// The image shaders read the texels as `vec4<f32>`, compute in `f32` and round to the
+/// nearest value when storing integer texels; [`pixel_defines`] sets the texel type and
+/// storage format of a shader variant. `f64` has no texture format.
+pub trait Pixel: Element {
+    /// Format of the RGBA textures.
+    const FORMAT: wgpu::TextureFormat;
+    /// WGSL sample type of the textures, `f32`, `u32` or `i32`.
+    const TEXEL: &'static str;
+    /// WGSL name of [`Self::FORMAT`] for the storage textures.
+    const STORAGE_FORMAT: &'static str;
+    /// Pixel values the histogram bins span from zero: `[0, 1]` for the float types and the
+    /// 8-bit values `0..256` for the integer ones.
+    const HIST_RANGE: f32;
+}
+
+impl Pixel for f32 {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+    const TEXEL: &'static str = "f32";
+    const STORAGE_FORMAT: &'static str = "rgba32float";
+    const HIST_RANGE: f32 = 1.0;
+}
+
+impl Pixel for half::f16 {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const TEXEL: &'static str = "f32";
+    const STORAGE_FORMAT: &'static str = "rgba16float";
+    const HIST_RANGE: f32 = 1.0;
+}
+
+impl Pixel for u32 {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Uint;
+    const TEXEL: &'static str = "u32";
+    const STORAGE_FORMAT: &'static str = "rgba32uint";
+    const HIST_RANGE: f32 = 256.0;
+}
+
+impl Pixel for i32 {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Sint;
+    const TEXEL: &'static str = "i32";
+    const STORAGE_FORMAT: &'static str = "rgba32sint";
+    const HIST_RANGE: f32 = 256.0;
+}
+
+/// `defines` with the `TEXEL`, `STORAGE_FORMAT` and `HIST_RANGE` of the pixel type for the
+/// image shaders, and `INTEGER` for the integer types.
+pub fn pixel_defines<T: Pixel>(defines: &Defines) -> Defines {
+    let mut defines = defines
+        .clone()
+        .define("TEXEL", T::TEXEL)
+        .define("STORAGE_FORMAT", T::STORAGE_FORMAT)
+        .define("HIST_RANGE", format!("{:?}", T::HIST_RANGE));
+    if T::TEXEL != "f32" {
+        defines.insert("INTEGER", 1);
+    }
+    defines
+}
+
+/// Floating point element types supported by the BLAS shader.
+pub trait Float: Element {
+    /// Widen to `f64` for host-side comparisons.
+    fn to_f64(self) -> f64;
+}
+
+impl Float for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Float for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Prepend the alias of the element type to the shader source.
+///
+/// [`Error::Argument`] for packed types, which need a dedicated shader.
+pub fn with_element<T: Element>(source: &str) -> Result<String> {
+    if T::PACKED {
+        return Err(Error::Argument(format!(
+            "packed {} elements are only supported by the element-wise kernels",
+            std::any::type_name::<T>()
+        )));
+    }
+    Ok(format!("alias T = {};\n{}", T::WGSL_TYPE, source))
+}
@@ -0,0 +1,235 @@
+//! Errors of shader, pipeline and resource creation.
+//!
+//! wgpu reports invalid shaders and resources to the device's uncaptured error handler, which
+//! panics by default. [`WgpuState`](crate::WgpuState) creates them inside error scopes instead
+//! and returns these errors; WGSL is checked with naga first so that shader errors point at a
+//! line and column of the source.
+
+use std::fmt;
+
+use crate::preprocess::{LineMap, PreprocessError};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// WGSL that does not parse or validate.
+    Shader(ShaderError),
+    /// A bind group resource that does not match the shader's declaration of `name`.
+    Binding {
+        name: String,
+        message: String,
+    },
+    /// An invalid pipeline, bind group or resource.
+    Validation(String),
+    /// Arguments that do not fit the op, e.g. vectors of different lengths.
+    Argument(String),
+    OutOfMemory(String),
+    /// The device is gone, e.g. after a driver reset or a GPU timeout. It can't be used again;
+    /// create a new [`WgpuState`](crate::WgpuState) to recover.
+    DeviceLost(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Shader(err) => write!(f, "{err}"),
+            Error::Binding { name, message } => write!(f, "binding `{name}` {message}"),
+            Error::Validation(description) => write!(f, "validation error: {description}"),
+            Error::Argument(description) => write!(f, "invalid argument: {description}"),
+            Error::OutOfMemory(description) => write!(f, "out of memory: {description}"),
+            Error::DeviceLost(description) => write!(f, "device lost: {description}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ShaderError> for Error {
+    fn from(err: ShaderError) -> Self {
+        Error::Shader(err)
+    }
+}
+
+impl From<PreprocessError> for Error {
+    fn from(err: PreprocessError) -> Self {
+        Error::Shader(err.into())
+    }
+}
+
+impl Error {
+    /// Locate a shader error in the file and line of the source before preprocessing.
+    pub fn locate(self, lines: &LineMap) -> Self {
+        match self {
+            Error::Shader(err) => Error::Shader(err.locate(lines)),
+            err => err,
+        }
+    }
+}
+
+impl From<wgpu::Error> for Error {
+    fn from(err: wgpu::Error) -> Self {
+        match err {
+            wgpu::Error::OutOfMemory { source } => Error::OutOfMemory(source.to_string()),
+            wgpu::Error::Validation { description, .. } if is_device_lost(&description) => {
+                Error::DeviceLost(description)
+            }
+            wgpu::Error::Validation { description, .. } => Error::Validation(description),
+        }
+    }
+}
+
+/// [`Error::Argument`] unless `actual == expected`, e.g. for the lengths of two vectors.
+pub(crate) fn check_len(what: &str, expected: usize, actual: usize) -> Result<()> {
+    if actual == expected {
+        return Ok(());
+    }
+    Err(Error::Argument(format!(
+        "{what} mismatch: expected {expected}, got {actual}"
+    )))
+}
+
+pub(crate) fn log_error(err: Error) {
+    log::error!("{err}");
+}
+
+/// wgpu 0.17 has no device lost callback; the loss shows up as `DeviceError::Lost` in the
+/// description of the next failing call.
+pub(crate) fn is_device_lost(description: &str) -> bool {
+    description.contains("device is lost")
+}
+
+/// WGSL that failed to parse or validate, with the location of the first error.
+#[derive(Debug, Clone)]
+pub struct ShaderError {
+    /// Label of the shader, e.g. its file name.
+    pub label: String,
+    pub message: String,
+    /// 1-based line and column, when the error has a location.
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    /// naga's report with the source lines around the error.
+    pub report: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                writeln!(f, "{}:{line}:{column}: {}", self.label, self.message)?
+            }
+            (Some(line), None) => writeln!(f, "{}:{line}: {}", self.label, self.message)?,
+            _ => writeln!(f, "{}: {}", self.label, self.message)?,
+        }
+        write!(f, "{}", self.report.trim_end())
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl ShaderError {
+    fn new(
+        label: &str,
+        message: String,
+        location: Option<naga::SourceLocation>,
+        report: String,
+    ) -> Self {
+        Self {
+            label: label.to_string(),
+            message,
+            line: location.map(|location| location.line_number),
+            column: location.map(|location| location.line_position),
+            report,
+        }
+    }
+
+    /// Move the location from the line of the preprocessed source to the file and line it
+    /// came from; the report still shows the preprocessed source.
+    pub fn locate(mut self, lines: &LineMap) -> Self {
+        if let Some((file, line)) = self.line.and_then(|line| lines.locate(line)) {
+            self.label = file.to_string();
+            self.line = Some(line);
+        }
+        self
+    }
+}
+
+/// A failed directive is located at its line of the file it is in.
+impl From<PreprocessError> for ShaderError {
+    fn from(err: PreprocessError) -> Self {
+        Self {
+            label: err.file,
+            message: err.message,
+            line: Some(err.line),
+            column: None,
+            report: format!("{} | {}", err.line, err.text),
+        }
+    }
+}
+
+/// Parse and validate WGSL `source` with naga.
+///
+/// `features` are the device features the shader may use, e.g. `SHADER_F64` for `f64`.
+pub fn check_wgsl(
+    label: &str,
+    source: &str,
+    features: wgpu::Features,
+) -> std::result::Result<naga::Module, ShaderError> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|err| {
+        ShaderError::new(
+            label,
+            err.message().to_string(),
+            err.location(source),
+            err.emit_to_string_with_path(source, label),
+        )
+    })?;
+
+    let mut capabilities = naga::valid::Capabilities::empty();
+    if features.contains(wgpu::Features::SHADER_F64) {
+        capabilities |= naga::valid::Capabilities::FLOAT64;
+    }
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), capabilities)
+        .validate(&module)
+        .map_err(|err| {
+            ShaderError::new(
+                label,
+                err.as_inner().to_string(),
+                err.location(source),
+                err.emit_to_string_with_path(source, label),
+            )
+        })?;
+    Ok(module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_wgsl, is_device_lost};
+
+    const SHADER: &str = "@group(0) @binding(0) var<storage, read_write> v: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    v[gid.x] = v[gid.x] * 2.0;
+}
+";
+
+    #[test]
+    pub fn test_check_wgsl() {
+        let features = wgpu::Features::empty();
+        assert!(check_wgsl("double.wgsl", SHADER, features).is_ok());
+
+        // a typo is a parse error at the unexpected token
+        let typo = SHADER.replace("v[gid.x] * 2.0", "v[gid.x] * 2.0 +");
+        let err = check_wgsl("double.wgsl", &typo, features).unwrap_err();
+        assert_eq!((err.line, err.column), (Some(5), Some(32)));
+        assert!(err.to_string().starts_with("double.wgsl:5:32: "));
+
+        // a type error is a validation error at the expression
+        let mismatch = SHADER.replace("* 2.0", "* 2u");
+        let err = check_wgsl("double.wgsl", &mismatch, features).unwrap_err();
+        assert_eq!(err.line, Some(5));
+        assert!(err.report.contains("v[gid.x] * 2u"));
+
+        assert!(is_device_lost("Parent device is lost"));
+    }
+}
@@ -0,0 +1,102 @@
+//! Image processing jobs, see [`lab_common::job`] for the file format, run on the device.
+//!
+//! [`run`] runs the ops of a job as one [`ImagePipeline`] on an RGBA image.
+
+use image::GenericImageView;
+
+pub use lab_common::job::{rebin, Backend, Interp, Job, JobError, Op};
+
+use crate::error::Error;
+use crate::pipeline::{HistogramOutput, ImageOutput, ImagePipeline, Outputs, HIST_BINS};
+use crate::{generate_gaussian_kernel, save_img, WgpuState};
+
+// the jobs check their bins against the shared count
+const _: () = assert!(HIST_BINS == lab_common::job::HIST_BINS);
+
+/// The results of a job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobOutput {
+    pub cols: u32,
+    pub rows: u32,
+    /// The RGBA image after the last op, row-major.
+    pub image: Vec<f32>,
+    /// The counts of the histogram ops, in order.
+    pub histograms: Vec<Vec<u32>>,
+}
+
+/// Load the input, run the ops on the device and save the output, if the job has one.
+pub async fn run(job: &Job, state: &WgpuState) -> Result<JobOutput, JobError> {
+    job.validate()?;
+    job.check_backend(Backend::Wgpu)?;
+    let img = job.load_input()?;
+    let (cols, rows) = img.dimensions();
+    let image = img.to_rgba32f().into_raw();
+
+    let mut pipeline = JobPipeline::new(state, &job.ops, cols, rows).map_err(JobError::device)?;
+    let outputs = pipeline
+        .pipeline
+        .run(&image)
+        .await
+        .map_err(JobError::device)?;
+    let output = pipeline.output(&outputs);
+    if let Some(path) = &job.output {
+        save_img(
+            &path.to_string_lossy(),
+            &output.image,
+            output.cols,
+            output.rows,
+        )?;
+    }
+    Ok(output)
+}
+
+/// The ops of a job as an [`ImagePipeline`] on images of one size.
+pub struct JobPipeline<'a> {
+    pub pipeline: ImagePipeline<'a>,
+    /// The image after the last op.
+    image: ImageOutput,
+    histograms: Vec<(HistogramOutput, usize)>,
+}
+
+impl<'a> JobPipeline<'a> {
+    pub fn new(state: &'a WgpuState, ops: &[Op], cols: u32, rows: u32) -> Result<Self, Error> {
+        let mut pipeline = ImagePipeline::new(state, cols, rows);
+        let mut histograms = Vec::new();
+        for op in ops {
+            match *op {
+                Op::GaussianBlur { radius, sigma } => {
+                    pipeline.convolution(&generate_gaussian_kernel(radius as i32, sigma))?;
+                }
+                Op::Rotate { degrees, interp } => match interp {
+                    Interp::Bilinear => {
+                        pipeline.rotation(degrees.to_radians())?;
+                    }
+                    Interp::Bicubic => {
+                        pipeline.bicubic_rotation(degrees.to_radians())?;
+                    }
+                },
+                Op::Histogram { bins } => histograms.push((pipeline.histogram()?, bins)),
+            }
+        }
+        let image = pipeline.output();
+        Ok(Self {
+            pipeline,
+            image,
+            histograms,
+        })
+    }
+
+    /// The results in the `outputs` of a run of the pipeline.
+    pub fn output(&self, outputs: &Outputs) -> JobOutput {
+        JobOutput {
+            cols: self.pipeline.cols(),
+            rows: self.pipeline.rows(),
+            image: outputs.image(self.image).to_vec(),
+            histograms: self
+                .histograms
+                .iter()
+                .map(|&(histogram, bins)| rebin(outputs.histogram(histogram), bins))
+                .collect(),
+        }
+    }
+}
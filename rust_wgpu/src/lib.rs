@@ -1,10 +1,36 @@
+use std::sync::{Arc, Mutex};
+
 use wgpu::InstanceDescriptor;
 
 use image::EncodableLayout;
 
+pub mod batch;
+pub mod blas;
+pub mod buffer;
+pub mod capabilities;
+pub mod element;
+pub mod error;
+pub mod job;
+pub mod map;
+pub mod ops;
+pub mod pipeline;
+pub mod preprocess;
+pub mod profile;
+pub mod reference;
+pub mod reflect;
+pub mod shaders;
+pub mod stream;
+pub mod transpose;
+pub mod tune;
+pub mod variants;
+pub mod watch;
+
+// the trace writer is shared with the other backend
+pub use lab_common::trace;
+
 pub fn save_img(
     path: &str,
-    out: &Vec<f32>,
+    out: &[f32],
     width: u32,
     height: u32,
 ) -> Result<(), image::error::ImageError> {
@@ -16,35 +42,290 @@ pub fn save_img(
 pub struct WgpuState {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
+    /// The adapter the device was created on.
+    pub adapter_info: wgpu::AdapterInfo,
+    /// Set by the uncaptured error handler when the device is lost.
+    lost: Arc<Mutex<Option<String>>>,
 }
 
 impl WgpuState {
     pub async fn init() -> Result<Self, wgpu::RequestDeviceError> {
-        // initialize the instance, adapter and device.
-        let instance = wgpu::Instance::new(InstanceDescriptor::default());
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: None,
-                force_fallback_adapter: false,
-            })
+        let adapter = Self::request_adapter()
+            .await
+            .expect("no wgpu adapter available");
+        let limits = Self::buffer_limits(&adapter);
+        Self::from_adapter(&adapter, limits).await
+    }
+
+    /// Like [`WgpuState::init`], but `None` when no adapter or device is available.
+    pub async fn try_init() -> Option<Self> {
+        let adapter = Self::request_adapter().await?;
+        let limits = Self::buffer_limits(&adapter);
+        Self::from_adapter(&adapter, limits)
             .await
-            .unwrap();
+            .map_err(|err| log::error!("Request device error - {err:?}"))
+            .ok()
+    }
+
+    /// Like [`WgpuState::try_init`], but with the device limits `limits`, e.g. small storage
+    /// bindings so that tests run the window by window paths on small vectors.
+    pub async fn try_init_with_limits(limits: wgpu::Limits) -> Option<Self> {
+        let adapter = Self::request_adapter().await?;
+        Self::from_adapter(&adapter, limits)
+            .await
+            .map_err(|err| log::error!("Request device error - {err:?}"))
+            .ok()
+    }
+
+    /// The adapters of the backends `WGPU_BACKEND` allows, e.g. to list them.
+    pub fn adapters() -> Vec<wgpu::AdapterInfo> {
+        Self::enumerate_adapters()
+            .iter()
+            .map(wgpu::Adapter::get_info)
+            .collect()
+    }
+
+    /// Like [`WgpuState::adapters`], with the adapters themselves to query their limits and
+    /// features.
+    pub fn enumerate_adapters() -> Vec<wgpu::Adapter> {
+        let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all());
+        let instance = wgpu::Instance::new(InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+        instance.enumerate_adapters(backends).collect()
+    }
+
+    /// Find an adapter, falling back to software ones on machines without a GPU.
+    ///
+    /// In order: the adapter named by `WGPU_ADAPTER_NAME`, the default adapter, the fallback
+    /// adapter, then any CPU adapter (e.g. lavapipe or llvmpipe). `WGPU_BACKEND` restricts the
+    /// backends, e.g. `WGPU_BACKEND=vulkan`.
+    async fn request_adapter() -> Option<wgpu::Adapter> {
+        // initialize the instance, adapter and device.
+        let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all());
+        let instance = wgpu::Instance::new(InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+
+        if let Some(adapter) = wgpu::util::initialize_adapter_from_env(&instance, None) {
+            return Some(adapter);
+        }
+        for force_fallback_adapter in [false, true] {
+            let adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                })
+                .await;
+            if adapter.is_some() {
+                return adapter;
+            }
+        }
+        instance
+            .enumerate_adapters(backends)
+            .find(|adapter| adapter.get_info().device_type == wgpu::DeviceType::Cpu)
+    }
+
+    /// The default limits, with the buffer sizes of the adapter: the defaults cap buffers at
+    /// 256 MiB and bindings at 128 MiB.
+    fn buffer_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+        let adapter_limits = adapter.limits();
+        wgpu::Limits {
+            max_buffer_size: adapter_limits.max_buffer_size,
+            max_storage_buffer_binding_size: adapter_limits.max_storage_buffer_binding_size,
+            ..wgpu::Limits::default()
+        }
+    }
+
+    async fn from_adapter(
+        adapter: &wgpu::Adapter,
+        limits: wgpu::Limits,
+    ) -> Result<Self, wgpu::RequestDeviceError> {
+        // enable f64 and timestamps when the adapter has them; f16 is emulated, see
+        // `element::Element`
+        let features =
+            adapter.features() & (wgpu::Features::SHADER_F64 | wgpu::Features::TIMESTAMP_QUERY);
 
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
+                    features,
+                    limits,
                     // limits: wgpu::Limits::downlevel_defaults(),
                 },
                 None,
             )
-            .await
-            .unwrap();
+            .await?;
+
+        // errors outside of an error scope are logged instead of panicking
+        let lost = Arc::new(Mutex::new(None));
+        let handler_lost = lost.clone();
+        device.on_uncaptured_error(Box::new(move |err| {
+            let description = err.to_string();
+            log::error!("wgpu error - {description}");
+            if error::is_device_lost(&description) {
+                *handler_lost.lock().unwrap() = Some(description);
+            }
+        }));
+
+        Ok(Self {
+            device,
+            queue,
+            adapter_info: adapter.get_info(),
+            lost,
+        })
+    }
+
+    /// Name, backend and type of the adapter, e.g. for test logs.
+    pub fn describe(&self) -> String {
+        let info = &self.adapter_info;
+        format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type)
+    }
+
+    /// [`Error::DeviceLost`](error::Error::DeviceLost) once the device is lost.
+    pub fn check_device(&self) -> error::Result<()> {
+        match self.lost.lock().unwrap().as_ref() {
+            Some(description) => Err(error::Error::DeviceLost(description.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Run `create` in validation and out-of-memory error scopes, returning the first error
+    /// it caused instead of passing it to the uncaptured error handler.
+    pub fn error_scope<T>(&self, create: impl FnOnce(&wgpu::Device) -> T) -> error::Result<T> {
+        self.check_device()?;
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let value = create(&self.device);
+        let validation = pollster::block_on(self.device.pop_error_scope());
+        let out_of_memory = pollster::block_on(self.device.pop_error_scope());
+
+        match validation.or(out_of_memory) {
+            Some(err) => {
+                let err = error::Error::from(err);
+                if let error::Error::DeviceLost(description) = &err {
+                    *self.lost.lock().unwrap() = Some(description.clone());
+                }
+                Err(err)
+            }
+            None => Ok(value),
+        }
+    }
+
+    /// Create a shader module from WGSL, checked with naga first so that errors point at the
+    /// line and column of the source. `label` names the shader in errors.
+    pub fn create_shader_module(
+        &self,
+        label: &str,
+        source: &str,
+    ) -> error::Result<wgpu::ShaderModule> {
+        error::check_wgsl(label, source, self.device.features())?;
+        self.error_scope(|device| {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            })
+        })
+    }
+
+    /// Create a compute pipeline; a layout that does not match the shader is an error.
+    pub fn create_compute_pipeline(
+        &self,
+        descriptor: &wgpu::ComputePipelineDescriptor,
+    ) -> error::Result<wgpu::ComputePipeline> {
+        self.error_scope(|device| device.create_compute_pipeline(descriptor))
+    }
+
+    /// Whether the device can run shaders on elements of type `T`.
+    pub fn supports<T: element::Element>(&self) -> bool {
+        self.device.features().contains(T::FEATURES)
+    }
+
+    /// Copy `size` bytes of the buffer into a staging buffer and read them back to the host.
+    ///
+    /// The buffer needs `COPY_SRC` usage.
+    pub async fn read_buffer<T: bytemuck::Pod>(
+        &self,
+        buffer: &wgpu::Buffer,
+        size: wgpu::BufferAddress,
+    ) -> Option<Vec<T>> {
+        let read_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(buffer, 0, &read_buffer, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let read_buffer_slice = read_buffer.slice(..);
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        read_buffer_slice.map_async(wgpu::MapMode::Read, |result| {
+            sender.send(result).ok();
+        });
+
+        self.device.poll(wgpu::Maintain::Wait);
+        let recv = receiver.await.expect("failed to communication");
+        match recv {
+            Ok(_) => {
+                let data = read_buffer_slice.get_mapped_range();
+                let result = bytemuck::cast_slice(&data).to_vec();
+                drop(data);
+                read_buffer.unmap();
+                Some(result)
+            }
+            Err(err) => {
+                log::error!("Buffer MapRead error - {err:?}");
+                None
+            }
+        }
+    }
+}
+
+/// Default limit of workgroups in one dimension of a dispatch.
+pub const MAX_WORKGROUPS: u32 = 65535;
+
+/// Number of workgroups for a grid-stride loop over `n` elements.
+///
+/// The shaders loop with a stride of the whole dispatch, so any length fits in one dispatch.
+pub fn grid_stride_workgroups(n: usize, workgroup_size: u32) -> u32 {
+    n.div_ceil(workgroup_size as usize)
+        .clamp(1, MAX_WORKGROUPS as usize) as u32
+}
+
+/// Layout entry for a storage buffer visible to the compute stage.
+pub fn storage_buffer_entry(binding: u32, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
 
-        Ok(Self { device, queue })
+/// Layout entry for a uniform buffer visible to the compute stage.
+pub fn uniform_buffer_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
     }
 }
 
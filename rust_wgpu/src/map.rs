@@ -0,0 +1,274 @@
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+
+use crate::buffer::{split_windows, GpuBuffer};
+use crate::element::Element;
+use crate::error::{check_len, Error, Result};
+use crate::{grid_stride_workgroups, WgpuState};
+
+/// Workgroup size of the generated shaders.
+pub const WORKGROUP_SIZE: u32 = 256;
+
+/// Names of the inputs inside an expression, in argument order.
+pub const INPUT_NAMES: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+
+/// Element-wise kernels generated from WGSL expression snippets.
+///
+/// The expression sees the input elements as `a`, `b`, `c`, ... and the element index as `i`,
+/// and its value is converted to the output type, e.g. `map2(&x, &y, "a * 2.0 + sin(b)")`.
+/// Each generated shader is compiled once and cached by its types, arity and expression; an
+/// expression that is not valid WGSL is a [`ShaderError`](crate::error::ShaderError).
+/// Vectors larger than the storage binding limit are processed window by window.
+pub struct MapKernels<'a> {
+    state: &'a WgpuState,
+    cache: RefCell<HashMap<String, wgpu::ComputePipeline>>,
+}
+
+impl<'a> MapKernels<'a> {
+    pub fn new(state: &'a WgpuState) -> Self {
+        Self {
+            state,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// `out[i] = expr(a[i])`
+    pub fn map<T: Element, O: Element>(
+        &self,
+        a: &GpuBuffer<T>,
+        expr: &str,
+    ) -> Result<GpuBuffer<O>> {
+        self.zip(&[a], expr)
+    }
+
+    /// `out[i] = expr(a[i], b[i])`
+    pub fn map2<T: Element, O: Element>(
+        &self,
+        a: &GpuBuffer<T>,
+        b: &GpuBuffer<T>,
+        expr: &str,
+    ) -> Result<GpuBuffer<O>> {
+        self.zip(&[a, b], expr)
+    }
+
+    /// `out[i] = expr(inputs[0][i], inputs[1][i], ...)` for up to 8 inputs of the same length.
+    pub fn zip<T: Element, O: Element>(
+        &self,
+        inputs: &[&GpuBuffer<T>],
+        expr: &str,
+    ) -> Result<GpuBuffer<O>> {
+        check_arity(inputs.len())?;
+        let len = inputs[0].len();
+        for input in inputs {
+            check_len("vector length", len, input.len())?;
+        }
+        if !(self.state.supports::<T>() && self.state.supports::<O>()) {
+            return Err(Error::Argument(format!(
+                "the device does not support {} or {} in shaders",
+                T::WGSL_TYPE,
+                O::WGSL_TYPE
+            )));
+        }
+
+        let device = &self.state.device;
+        let out = GpuBuffer::<O>::zeros(self.state, len)?;
+
+        let key = format!(
+            "{}:{}:{}:{}:{}:{}",
+            T::WGSL_TYPE,
+            T::PACKED,
+            O::WGSL_TYPE,
+            O::PACKED,
+            inputs.len(),
+            expr
+        );
+        let mut cache = self.cache.borrow_mut();
+        let pipeline = match cache.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let source = generate_shader::<T, O>(inputs.len(), expr);
+                let label = format!("map `{expr}`");
+                let shader = self.state.create_shader_module(&label, &source)?;
+                entry.insert(self.state.create_compute_pipeline(
+                    &wgpu::ComputePipelineDescriptor {
+                        label: Some("Map Pipeline"),
+                        layout: None,
+                        module: &shader,
+                        entry_point: "main",
+                    },
+                )?)
+            }
+        };
+
+        // bind the vectors window by window when they exceed the binding limit
+        let (t_size, o_size) = (std::mem::size_of::<T>(), std::mem::size_of::<O>());
+        let windows = split_windows(
+            len,
+            t_size.min(o_size),
+            t_size.max(o_size),
+            &device.limits(),
+        );
+        let arity = inputs.len() as u32;
+        // a packed output is written a pair per invocation
+        let lanes = if O::PACKED { 2 } else { 1 };
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for window in windows {
+            let params = [window.len() as u32, window.start as u32];
+            let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(&params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+            let mut entries: Vec<wgpu::BindGroupEntry> = inputs
+                .iter()
+                .enumerate()
+                .map(|(binding, input)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource: input.binding(window.clone()),
+                })
+                .collect();
+            entries.push(wgpu::BindGroupEntry {
+                binding: arity,
+                resource: out.binding(window.clone()),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: arity + 1,
+                resource: params_buffer.as_entire_binding(),
+            });
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            });
+
+            let mut compute_pass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                grid_stride_workgroups(window.len().div_ceil(lanes), WORKGROUP_SIZE),
+                1,
+                1,
+            );
+        }
+        self.state.queue.submit(Some(encoder.finish()));
+
+        Ok(out)
+    }
+
+    /// Number of compiled shaders in the cache.
+    pub fn cached(&self) -> usize {
+        self.cache.borrow().len()
+    }
+}
+
+/// [`Error::Argument`] unless there are between 1 and 8 inputs, one per name of [`INPUT_NAMES`].
+pub(crate) fn check_arity(arity: usize) -> Result<()> {
+    if arity == 0 || arity > INPUT_NAMES.len() {
+        return Err(Error::Argument(format!(
+            "arity must be between 1 and {}",
+            INPUT_NAMES.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Splice the expression into the element-wise shader template.
+///
+/// Inputs are bound at `0..arity`, the output at `arity` and the length and offset of the
+/// window at `arity + 1`; the expression's `i` counts from the start of the vectors.
+/// Packed inputs are unpacked on load; a packed output is written a pair per invocation.
+pub fn generate_shader<T: Element, O: Element>(arity: usize, expr: &str) -> String {
+    let storage = |packed: bool, alias: &'static str| if packed { "u32" } else { alias };
+    let mut source = format!(
+        "alias T = {};\nalias O = {};\n\n",
+        T::WGSL_TYPE,
+        O::WGSL_TYPE
+    );
+
+    for (binding, name) in INPUT_NAMES[..arity].iter().enumerate() {
+        source += &format!(
+            "@group(0) @binding({binding}) var<storage, read> in_{name}: array<{}>;\n",
+            storage(T::PACKED, "T")
+        );
+    }
+    source += &format!(
+        "@group(0) @binding({arity}) var<storage, read_write> out: array<{}>;\n\n\
+         struct Params {{\n    len: u32,\n    offset: u32,\n}}\n\
+         @group(0) @binding({}) var<uniform> params: Params;\n\n",
+        storage(O::PACKED, "O"),
+        arity + 1
+    );
+
+    // the expression sees the inputs and the index `i`; `w` indexes the window
+    source += "fn compute(w: u32) -> O {\n    let i = params.offset + w;\n";
+    for name in &INPUT_NAMES[..arity] {
+        if T::PACKED {
+            source += &format!(
+                "    let {name}_pair = unpack2x16float(in_{name}[w / 2u]);\n\
+                 \x20   let {name} = select({name}_pair.x, {name}_pair.y, w % 2u == 1u);\n"
+            );
+        } else {
+            source += &format!("    let {name} = in_{name}[w];\n");
+        }
+    }
+    source += &format!("    return O({expr});\n}}\n\n");
+
+    source += &format!(
+        "@compute @workgroup_size({WORKGROUP_SIZE})\n\
+         fn main(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {{\n"
+    );
+    if O::PACKED {
+        source += &format!(
+            "    for (var p = gid.x; p < (params.len + 1u) / 2u; p += groups.x * {WORKGROUP_SIZE}u) {{\n\
+             \x20       let w = p * 2u;\n\
+             \x20       var hi = 0.0;\n\
+             \x20       if w + 1u < params.len {{\n\
+             \x20           hi = compute(w + 1u);\n\
+             \x20       }}\n\
+             \x20       out[p] = pack2x16float(vec2<f32>(compute(w), hi));\n\
+             \x20   }}\n}}\n"
+        );
+    } else {
+        source += &format!(
+            "    for (var w = gid.x; w < params.len; w += groups.x * {WORKGROUP_SIZE}u) {{\n\
+             \x20       out[w] = compute(w);\n\
+             \x20   }}\n}}\n"
+        );
+    }
+
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_shader;
+
+    fn parse(source: &str) -> naga::Module {
+        naga::front::wgsl::parse_str(source).expect("invalid generated shader")
+    }
+
+    #[test]
+    pub fn test_generate_shader() {
+        let source = generate_shader::<f32, u32>(2, "a * 2.0 + sin(b)");
+        let module = parse(&source);
+        assert_eq!(module.global_variables.len(), 4);
+        assert!(source.contains("return O(a * 2.0 + sin(b));"));
+    }
+
+    #[test]
+    pub fn test_generate_packed_shader() {
+        let source = generate_shader::<half::f16, half::f16>(1, "a * 2.0");
+        parse(&source);
+        assert!(source.contains("var<storage, read> in_a: array<u32>;"));
+        assert!(source.contains("var<storage, read_write> out: array<u32>;"));
+        assert!(source.contains("pack2x16float"));
+    }
+}
@@ -0,0 +1,642 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use wgpu::util::DeviceExt;
+
+use crate::buffer::GpuBuffer;
+use crate::element::{pixel_defines, Pixel};
+use crate::error::{check_len, log_error, Result};
+use crate::preprocess::Defines;
+use crate::reflect::ShaderLayout;
+use crate::shaders::{self, rotation, vectoradd};
+use crate::tune::{cache_key, workgroup_candidates, Tuner};
+use crate::variants::ShaderVariants;
+use crate::{grid_stride_workgroups, watch, WgpuState};
+
+/// Workgroup size of the vector add shader.
+const VECTOR_ADD_WORKGROUP_SIZE: u32 = vectoradd::WORKGROUP_SIZE[0];
+/// Workgroup size of the image shaders as written, the same in all of them.
+const IMAGE_WORKGROUP_SIZE: [u32; 2] = [rotation::WORKGROUP_SIZE[0], rotation::WORKGROUP_SIZE[1]];
+/// RGBA channels of the image textures.
+pub const CHANNELS: usize = 4;
+
+/// c = a + b with the vector add shader, on device buffers of a fixed length.
+///
+/// Uploading, dispatching and downloading are separate steps so they can be timed apart.
+/// Buffers larger than one storage binding are bound window by window.
+pub struct VectorAdd<'a> {
+    state: &'a WgpuState,
+    pipeline: wgpu::ComputePipeline,
+    /// One bind group per window, with the window length.
+    bind_groups: Vec<(wgpu::BindGroup, usize)>,
+    a: GpuBuffer<f32>,
+    b: GpuBuffer<f32>,
+    c: GpuBuffer<f32>,
+}
+
+impl<'a> VectorAdd<'a> {
+    pub fn new(state: &'a WgpuState, len: usize) -> Result<Self> {
+        let device = &state.device;
+        let a = GpuBuffer::<f32>::zeros(state, len)?;
+        let b = GpuBuffer::<f32>::zeros(state, len)?;
+        let c = GpuBuffer::<f32>::zeros(state, len)?;
+
+        let bind_group_layout = vectoradd::BindGroup0::create_layout(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = state.create_shader_module(
+            "vectoradd.wgsl",
+            &watch::source("vectoradd.wgsl", vectoradd::SOURCE),
+        )?;
+        let pipeline = create_pipeline(state, "vectoradd.wgsl", &pipeline_layout, &shader)?;
+        let bind_groups = state.error_scope(|device| {
+            c.windows(&device.limits())
+                .into_iter()
+                .map(|window| {
+                    let bind_group = vectoradd::BindGroup0 {
+                        in_a: a.buffer_binding(window.clone()),
+                        in_b: b.buffer_binding(window.clone()),
+                        out: c.buffer_binding(window.clone()),
+                    }
+                    .create(device, &bind_group_layout);
+                    (bind_group, window.len())
+                })
+                .collect()
+        })?;
+
+        Ok(Self {
+            state,
+            pipeline,
+            bind_groups,
+            a,
+            b,
+            c,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.c.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.c.is_empty()
+    }
+
+    /// Write the inputs; the copies run with the next submission.
+    pub fn upload(&self, a: &[f32], b: &[f32]) -> Result<()> {
+        self.a.write(&self.state.queue, a)?;
+        self.b.write(&self.state.queue, b)
+    }
+
+    pub fn dispatch(&self) {
+        let mut encoder = self
+            .state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut compute_pass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            compute_pass.set_pipeline(&self.pipeline);
+            for (bind_group, len) in &self.bind_groups {
+                compute_pass.set_bind_group(0, bind_group, &[]);
+                compute_pass.dispatch_workgroups(
+                    grid_stride_workgroups(*len, VECTOR_ADD_WORKGROUP_SIZE),
+                    1,
+                    1,
+                );
+            }
+        }
+        self.state.queue.submit(Some(encoder.finish()));
+    }
+
+    pub async fn download(&self) -> Option<Vec<f32>> {
+        self.c.read(self.state).await
+    }
+}
+
+/// c = a + b with the vector add shader.
+pub async fn vector_add(state: &WgpuState, a: &[f32], b: &[f32]) -> Option<Vec<f32>> {
+    let op = VectorAdd::new(state, a.len()).map_err(log_error).ok()?;
+    op.upload(a, b).map_err(log_error).ok()?;
+    op.dispatch();
+    op.download().await
+}
+
+/// The pipeline of an image shader and its resources besides the images, for RGBA images
+/// (`cols x rows`) of a fixed size and pixel type, `f32` unless specified. The shader reads
+/// the input texture `input_img` and writes the output storage texture `output_img`, bound
+/// by [`ImagePass::bind_group`] to any pair of textures of that size and type.
+///
+/// The bind group layout is reflected from the shader. The shader is preprocessed with
+/// `WORKGROUP_X`/`WORKGROUP_Y`, the [`pixel_defines`] and its own defines, one variant per
+/// workgroup size.
+pub struct ImagePass<'a, T: Pixel = f32> {
+    state: &'a WgpuState,
+    name: String,
+    file: &'static str,
+    source: Cow<'static, str>,
+    defines: Defines,
+    variants: ShaderVariants<'a>,
+    layout: ShaderLayout,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::ComputePipeline,
+    workgroup_size: [u32; 2],
+    resources: Vec<(&'static str, wgpu::Buffer)>,
+    cols: u32,
+    rows: u32,
+    pixel: PhantomData<T>,
+}
+
+impl<'a, T: Pixel> ImagePass<'a, T> {
+    /// Convolution with a square filter; pixels outside of the image are skipped, i.e. the
+    /// image is padded with zeros.
+    pub fn convolution(state: &'a WgpuState, cols: u32, rows: u32, filter: &[f32]) -> Result<Self> {
+        let filter_buffer = state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(filter),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+
+        let filter_size = (filter.len() as f64).sqrt() as usize;
+        Self::new(
+            state,
+            format!("convolution {filter_size}x{filter_size}"),
+            ("convolution.wgsl", shaders::convolution::SOURCE),
+            Defines::new().define("FILTER_SIZE", filter_size),
+            cols,
+            rows,
+            vec![("kernel", filter_buffer)],
+        )
+    }
+
+    /// Rotation by `theta` radians around the image center.
+    ///
+    /// Each output pixel interpolates bilinearly between the input pixels around its source
+    /// location; pixels outside of the image read as zero.
+    pub fn rotation(state: &'a WgpuState, cols: u32, rows: u32, theta: f32) -> Result<Self> {
+        Self::rotate(state, "rotation", Defines::new(), cols, rows, theta)
+    }
+
+    /// Rotation like [`ImagePass::rotation`], interpolating bicubically instead: a
+    /// Catmull-Rom spline through the 4x4 pixels around the source location.
+    pub fn bicubic_rotation(
+        state: &'a WgpuState,
+        cols: u32,
+        rows: u32,
+        theta: f32,
+    ) -> Result<Self> {
+        let defines = Defines::new().define("BICUBIC", 1);
+        Self::rotate(state, "bicubic rotation", defines, cols, rows, theta)
+    }
+
+    /// The rotation shader preprocessed with `defines`, which pick the interpolation.
+    fn rotate(
+        state: &'a WgpuState,
+        name: &str,
+        defines: Defines,
+        cols: u32,
+        rows: u32,
+        theta: f32,
+    ) -> Result<Self> {
+        let device = &state.device;
+        let img_size_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&[cols, rows]),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let theta_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&[theta]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        Self::new(
+            state,
+            name.to_string(),
+            ("rotation.wgsl", rotation::SOURCE),
+            defines,
+            cols,
+            rows,
+            vec![("img_size", img_size_buffer), ("theta", theta_buffer)],
+        )
+    }
+
+    /// `shader` is the file name and the embedded source, see [`watch::source`];
+    /// `resources` are the shader's buffers besides the images, by name.
+    fn new(
+        state: &'a WgpuState,
+        name: String,
+        (file, source): (&'static str, &'static str),
+        defines: Defines,
+        cols: u32,
+        rows: u32,
+        resources: Vec<(&'static str, wgpu::Buffer)>,
+    ) -> Result<Self> {
+        let defines = pixel_defines::<T>(&defines);
+        let variants = ShaderVariants::new(state, shaders::preprocessor());
+        let source = watch::source(file, source);
+        let variant = variants.get(
+            file,
+            &source,
+            &workgroup_defines(&defines, IMAGE_WORKGROUP_SIZE),
+        )?;
+        let layout = ShaderLayout::from_wgsl(&name, &variant.source)?;
+        let (mut bind_group_layouts, pipeline_layout) = layout.pipeline_layout(&state.device);
+        let pipeline = create_pipeline(state, &name, &pipeline_layout, &variant.module)?;
+
+        Ok(Self {
+            state,
+            name,
+            file,
+            source,
+            defines,
+            variants,
+            layout,
+            bind_group_layout: bind_group_layouts.remove(0),
+            pipeline_layout,
+            pipeline,
+            workgroup_size: IMAGE_WORKGROUP_SIZE,
+            resources,
+            cols,
+            rows,
+            pixel: PhantomData,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn workgroup_size(&self) -> [u32; 2] {
+        self.workgroup_size
+    }
+
+    /// Rebuild the pipeline from the variant of the shader with another `@workgroup_size`,
+    /// compiled once per size. The bind groups stay valid.
+    pub fn set_workgroup_size(&mut self, size: [u32; 2]) -> Result<()> {
+        if size == self.workgroup_size {
+            return Ok(());
+        }
+        let defines = workgroup_defines(&self.defines, size);
+        let variant = self.variants.get(self.file, &self.source, &defines)?;
+        self.pipeline = create_pipeline(
+            self.state,
+            &self.name,
+            &self.pipeline_layout,
+            &variant.module,
+        )?;
+        self.workgroup_size = size;
+        Ok(())
+    }
+
+    /// The bind group reading `input` and writing `output`, views of `cols x rows` textures
+    /// of the pixel type's [`FORMAT`](Pixel::FORMAT).
+    pub fn bind_group(
+        &self,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) -> Result<wgpu::BindGroup> {
+        let mut resources: Vec<_> = self
+            .resources
+            .iter()
+            .map(|(name, buffer)| (*name, buffer.as_entire_binding()))
+            .collect();
+        resources.push(("input_img", wgpu::BindingResource::TextureView(input)));
+        resources.push(("output_img", wgpu::BindingResource::TextureView(output)));
+        self.layout
+            .bind_group(self.state, &self.bind_group_layout, 0, &resources)
+    }
+
+    /// Record a compute pass over the image with `bind_group` into `encoder`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, bind_group: &wgpu::BindGroup) {
+        let mut compute_pass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            self.cols.div_ceil(self.workgroup_size[0]),
+            self.rows.div_ceil(self.workgroup_size[1]),
+            1,
+        );
+    }
+}
+
+/// An [`ImagePass`] with its own input and output textures.
+///
+/// Uploading, dispatching and downloading are separate steps so they can be timed apart.
+pub struct ImageShader<'a, T: Pixel = f32> {
+    pass: ImagePass<'a, T>,
+    bind_group: wgpu::BindGroup,
+    input: wgpu::Texture,
+    output: wgpu::Texture,
+    read_buffer: wgpu::Buffer,
+}
+
+impl<'a, T: Pixel> ImageShader<'a, T> {
+    /// Convolution with a square filter, see [`ImagePass::convolution`].
+    pub fn convolution(state: &'a WgpuState, cols: u32, rows: u32, filter: &[f32]) -> Result<Self> {
+        Self::new(ImagePass::convolution(state, cols, rows, filter)?)
+    }
+
+    /// Rotation by `theta` radians around the image center, see [`ImagePass::rotation`].
+    pub fn rotation(state: &'a WgpuState, cols: u32, rows: u32, theta: f32) -> Result<Self> {
+        Self::new(ImagePass::rotation(state, cols, rows, theta)?)
+    }
+
+    /// Bicubic rotation, see [`ImagePass::bicubic_rotation`].
+    pub fn bicubic_rotation(
+        state: &'a WgpuState,
+        cols: u32,
+        rows: u32,
+        theta: f32,
+    ) -> Result<Self> {
+        Self::new(ImagePass::bicubic_rotation(state, cols, rows, theta)?)
+    }
+
+    pub fn new(pass: ImagePass<'a, T>) -> Result<Self> {
+        let device = &pass.state.device;
+        let input = create_image_texture::<T>(
+            device,
+            pass.cols,
+            pass.rows,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
+        let output = create_image_texture::<T>(
+            device,
+            pass.cols,
+            pass.rows,
+            wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+        );
+        let bind_group = pass.bind_group(
+            &input.create_view(&wgpu::TextureViewDescriptor::default()),
+            &output.create_view(&wgpu::TextureViewDescriptor::default()),
+        )?;
+
+        let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: image_buffer_size::<T>(pass.cols, pass.rows),
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            pass,
+            bind_group,
+            input,
+            output,
+            read_buffer,
+        })
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.pass.cols
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.pass.rows
+    }
+
+    pub fn workgroup_size(&self) -> [u32; 2] {
+        self.pass.workgroup_size
+    }
+
+    /// See [`ImagePass::set_workgroup_size`].
+    pub fn set_workgroup_size(&mut self, size: [u32; 2]) -> Result<()> {
+        self.pass.set_workgroup_size(size)
+    }
+
+    /// Switch to the fastest workgroup size for this shader and image size on the adapter,
+    /// from the tuner's cache or by timing the candidates within the device limits.
+    pub fn tune(&mut self, tuner: &mut Tuner) -> std::io::Result<[u32; 2]> {
+        let state = self.pass.state;
+        let key = cache_key(
+            &state.adapter_info.name,
+            &self.pass.name,
+            &format!("{}x{} {:?}", self.cols(), self.rows(), T::FORMAT),
+        );
+        let candidates = workgroup_candidates(&state.device.limits());
+        let size = tuner.tune(key, &candidates, |candidate, iterations| {
+            if let Err(err) = self.set_workgroup_size(candidate) {
+                log::warn!("{}: skipping {candidate:?} - {err}", self.pass.name);
+                return std::time::Duration::MAX;
+            }
+            // the first dispatch also waits for the pipeline
+            self.dispatch();
+            state.device.poll(wgpu::Maintain::Wait);
+
+            let start = std::time::Instant::now();
+            for _ in 0..iterations {
+                self.dispatch();
+            }
+            state.device.poll(wgpu::Maintain::Wait);
+            start.elapsed() / iterations
+        })?;
+
+        self.set_workgroup_size(size.unwrap_or(IMAGE_WORKGROUP_SIZE))
+            .map_err(std::io::Error::other)?;
+        Ok(self.workgroup_size())
+    }
+
+    /// Write the input image; the copy runs with the next submission.
+    pub fn upload(&self, image: &[T]) -> Result<()> {
+        write_image(&self.pass.state.queue, &self.input, image)
+    }
+
+    pub fn dispatch(&self) {
+        let state = self.pass.state;
+        let mut encoder = state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.pass.encode(&mut encoder, &self.bind_group);
+        state.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Copy the output texture back to the host.
+    pub async fn download(&self) -> Option<Vec<T>> {
+        let state = self.pass.state;
+        let mut encoder = state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        copy_image_to_buffer::<T>(&mut encoder, &self.output, &self.read_buffer);
+        state.queue.submit(Some(encoder.finish()));
+
+        let padded: Vec<T> = state
+            .read_buffer(&self.read_buffer, self.read_buffer.size())
+            .await?;
+        Some(unpad_image(&padded, self.cols()))
+    }
+
+    /// Upload, dispatch and download.
+    pub async fn run(&self, image: &[T]) -> Option<Vec<T>> {
+        self.upload(image).map_err(log_error).ok()?;
+        self.dispatch();
+        self.download().await
+    }
+}
+
+/// Convolve an RGBA image (`cols x rows`, row-major) with a square filter.
+///
+/// Pixels outside of the image are skipped, i.e. the image is padded with zeros.
+pub async fn convolution<T: Pixel>(
+    state: &WgpuState,
+    image: &[T],
+    cols: u32,
+    rows: u32,
+    filter: &[f32],
+) -> Option<Vec<T>> {
+    ImageShader::convolution(state, cols, rows, filter)
+        .map_err(log_error)
+        .ok()?
+        .run(image)
+        .await
+}
+
+/// Rotate an RGBA image (`cols x rows`, row-major) by `theta` radians around its center.
+///
+/// Each output pixel interpolates bilinearly between the input pixels around its source
+/// location; pixels outside of the image read as zero.
+pub async fn rotation<T: Pixel>(
+    state: &WgpuState,
+    image: &[T],
+    cols: u32,
+    rows: u32,
+    theta: f32,
+) -> Option<Vec<T>> {
+    ImageShader::rotation(state, cols, rows, theta)
+        .map_err(log_error)
+        .ok()?
+        .run(image)
+        .await
+}
+
+/// Bytes of one row of a `cols` wide RGBA image of the pixel type, without padding.
+fn row_bytes<T: Pixel>(cols: u32) -> u32 {
+    cols * (CHANNELS * std::mem::size_of::<T>()) as u32
+}
+
+/// Rows of a texture copy into a buffer are aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+fn padded_row_bytes<T: Pixel>(cols: u32) -> u32 {
+    row_bytes::<T>(cols).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
+
+/// A `cols x rows` RGBA texture of the pixel type's format.
+pub(crate) fn create_image_texture<T: Pixel>(
+    device: &wgpu::Device,
+    cols: u32,
+    rows: u32,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: cols,
+            height: rows,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: T::FORMAT,
+        usage,
+        view_formats: &[],
+    })
+}
+
+/// Write an RGBA image of the texture's size into `texture`; the copy runs with the next
+/// submission.
+pub(crate) fn write_image<T: Pixel>(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    image: &[T],
+) -> Result<()> {
+    let size = texture.size();
+    check_len(
+        "image size",
+        (size.width * size.height) as usize * CHANNELS,
+        image.len(),
+    )?;
+    queue.write_texture(
+        texture.as_image_copy(),
+        bytemuck::cast_slice(image),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(row_bytes::<T>(size.width)),
+            rows_per_image: Some(size.height),
+        },
+        size,
+    );
+    Ok(())
+}
+
+/// Size of a buffer holding a `cols x rows` image copied by [`copy_image_to_buffer`].
+pub(crate) fn image_buffer_size<T: Pixel>(cols: u32, rows: u32) -> wgpu::BufferAddress {
+    (padded_row_bytes::<T>(cols) * rows) as wgpu::BufferAddress
+}
+
+/// Record the copy of `texture` into `buffer`, with padded rows.
+pub(crate) fn copy_image_to_buffer<T: Pixel>(
+    encoder: &mut wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+    buffer: &wgpu::Buffer,
+) {
+    let size = texture.size();
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes::<T>(size.width)),
+                rows_per_image: Some(size.height),
+            },
+        },
+        size,
+    );
+}
+
+/// The image without the row padding of [`copy_image_to_buffer`].
+pub(crate) fn unpad_image<T: Pixel>(padded: &[T], cols: u32) -> Vec<T> {
+    let padded_row_len = padded_row_bytes::<T>(cols) as usize / std::mem::size_of::<T>();
+    let row_len = cols as usize * CHANNELS;
+    padded
+        .chunks_exact(padded_row_len)
+        .flat_map(|row| &row[..row_len])
+        .copied()
+        .collect()
+}
+
+/// `defines` with the `WORKGROUP_X`/`WORKGROUP_Y` of the image shaders.
+fn workgroup_defines(defines: &Defines, size: [u32; 2]) -> Defines {
+    defines
+        .clone()
+        .define("WORKGROUP_X", size[0])
+        .define("WORKGROUP_Y", size[1])
+}
+
+pub(crate) fn create_pipeline(
+    state: &WgpuState,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+) -> Result<wgpu::ComputePipeline> {
+    state.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        module,
+        entry_point: "main",
+    })
+}
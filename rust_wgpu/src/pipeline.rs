@@ -0,0 +1,427 @@
+//! Chains of image shaders that keep the images on the device.
+//!
+//! An [`ImagePipeline`] records its passes, e.g. blur -> rotate -> histogram, into one
+//! command buffer. The intermediate textures come from a [`TexturePool`], so a chain of any
+//! length uses two textures, and only the marked outputs are copied back to the host:
+//!
+//! ```no_run
+//! # let state = pollster::block_on(rust_wgpu::WgpuState::init()).unwrap();
+//! # let (image, filter) = (vec![0.0; 64 * 64 * 4], rust_wgpu::generate_gaussian_kernel(2, 1.0));
+//! use rust_wgpu::pipeline::ImagePipeline;
+//!
+//! let mut pipeline = ImagePipeline::new(&state, 64, 64);
+//! pipeline.convolution(&filter)?.rotation(0.5)?;
+//! let rotated = pipeline.output();
+//! let histogram = pipeline.histogram()?;
+//!
+//! let outputs = pollster::block_on(pipeline.run(&image))?;
+//! println!("{} pixels, {:?}", outputs.image(rotated).len() / 4, outputs.histogram(histogram));
+//! # Ok::<_, rust_wgpu::error::Error>(())
+//! ```
+
+use crate::buffer::GpuBuffer;
+use crate::element::{pixel_defines, Pixel};
+use crate::error::{Error, Result};
+use crate::ops::{
+    copy_image_to_buffer, create_image_texture, create_pipeline, image_buffer_size, unpad_image,
+    write_image, ImagePass,
+};
+use crate::preprocess::Defines;
+use crate::reflect::ShaderLayout;
+use crate::shaders::{self, histogram};
+use crate::{watch, WgpuState};
+
+/// Bins of the histogram pass, over luma values in `[0, HIST_RANGE]` of the pixel type, see
+/// [`Pixel::HIST_RANGE`].
+pub const HIST_BINS: usize = 256;
+// the shader clears and adds one bin per invocation of a workgroup
+const _: () =
+    assert!(HIST_BINS == (histogram::WORKGROUP_SIZE[0] * histogram::WORKGROUP_SIZE[1]) as usize);
+
+/// An image output of a pipeline, see [`ImagePipeline::output`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageOutput(usize);
+
+/// A histogram output of a pipeline, see [`ImagePipeline::histogram`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistogramOutput(usize);
+
+/// The outputs of one run of a pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outputs<T: Pixel = f32> {
+    images: Vec<Vec<T>>,
+    histograms: Vec<Vec<u32>>,
+}
+
+impl<T: Pixel> Outputs<T> {
+    /// The RGBA image at `output`, row-major.
+    pub fn image(&self, output: ImageOutput) -> &[T] {
+        &self.images[output.0]
+    }
+
+    pub fn histogram(&self, output: HistogramOutput) -> &[u32] {
+        &self.histograms[output.0]
+    }
+}
+
+/// Intermediate textures, reused once the pass reading them is recorded.
+///
+/// The passes of a command buffer run in order, so a texture can be written again by a later
+/// pass of the same command buffer.
+#[derive(Default)]
+pub struct TexturePool {
+    free: Vec<wgpu::Texture>,
+    created: usize,
+}
+
+impl TexturePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A free `cols x rows` RGBA texture of the pixel type, or a new one. The textures can be
+    /// sampled, stored to and copied both ways.
+    pub fn acquire<T: Pixel>(
+        &mut self,
+        device: &wgpu::Device,
+        cols: u32,
+        rows: u32,
+    ) -> wgpu::Texture {
+        let free = self.free.iter().position(|texture| {
+            let size = texture.size();
+            (size.width, size.height, texture.format()) == (cols, rows, T::FORMAT)
+        });
+        if let Some(index) = free {
+            return self.free.swap_remove(index);
+        }
+        self.created += 1;
+        create_image_texture::<T>(
+            device,
+            cols,
+            rows,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+        )
+    }
+
+    pub fn release(&mut self, texture: wgpu::Texture) {
+        self.free.push(texture);
+    }
+
+    /// Number of textures created so far.
+    pub fn created(&self) -> usize {
+        self.created
+    }
+}
+
+enum Step<'a, T: Pixel> {
+    Pass(Box<ImagePass<'a, T>>),
+    /// Copy the current image into a staging buffer.
+    Output,
+    /// Count the current image into `bins` and copy them into a staging buffer.
+    Histogram(GpuBuffer<u32>),
+}
+
+/// A run of a pipeline on the device, see [`ImagePipeline::submit`].
+pub struct Submission {
+    index: wgpu::SubmissionIndex,
+    /// One buffer per output, in step order.
+    staging: Vec<wgpu::Buffer>,
+    mapped: Vec<MapReceiver>,
+}
+
+type MapReceiver =
+    futures_channel::oneshot::Receiver<std::result::Result<(), wgpu::BufferAsyncError>>;
+
+/// The pipeline of the histogram shader, shared by the histogram steps. The bind group
+/// layout is reflected from the variant for the pixel type.
+struct HistogramPipeline {
+    layout: ShaderLayout,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+/// A chain of image passes on RGBA images (`cols x rows`, row-major) of a fixed size and
+/// pixel type, `f32` unless specified.
+pub struct ImagePipeline<'a, T: Pixel = f32> {
+    state: &'a WgpuState,
+    cols: u32,
+    rows: u32,
+    steps: Vec<Step<'a, T>>,
+    images: usize,
+    histograms: usize,
+    histogram_pipeline: Option<HistogramPipeline>,
+    pool: TexturePool,
+    /// Staging buffers of the finished submissions.
+    staging: Vec<Vec<wgpu::Buffer>>,
+}
+
+impl<'a, T: Pixel> ImagePipeline<'a, T> {
+    pub fn new(state: &'a WgpuState, cols: u32, rows: u32) -> Self {
+        Self {
+            state,
+            cols,
+            rows,
+            steps: Vec::new(),
+            images: 0,
+            histograms: 0,
+            histogram_pipeline: None,
+            pool: TexturePool::new(),
+            staging: Vec::new(),
+        }
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Append a pass on the current image, which becomes the pass's output; an error when the
+    /// pass is for another image size.
+    pub fn pass(&mut self, pass: ImagePass<'a, T>) -> Result<&mut Self> {
+        if (pass.cols(), pass.rows()) != (self.cols, self.rows) {
+            return Err(Error::Argument(format!(
+                "image size mismatch in {}: expected {}x{}, got {}x{}",
+                pass.name(),
+                self.cols,
+                self.rows,
+                pass.cols(),
+                pass.rows()
+            )));
+        }
+        self.steps.push(Step::Pass(Box::new(pass)));
+        Ok(self)
+    }
+
+    /// Append a convolution, see [`ImagePass::convolution`].
+    pub fn convolution(&mut self, filter: &[f32]) -> Result<&mut Self> {
+        let pass = ImagePass::convolution(self.state, self.cols, self.rows, filter)?;
+        self.pass(pass)
+    }
+
+    /// Append a rotation, see [`ImagePass::rotation`].
+    pub fn rotation(&mut self, theta: f32) -> Result<&mut Self> {
+        let pass = ImagePass::rotation(self.state, self.cols, self.rows, theta)?;
+        self.pass(pass)
+    }
+
+    /// Append a bicubic rotation, see [`ImagePass::bicubic_rotation`].
+    pub fn bicubic_rotation(&mut self, theta: f32) -> Result<&mut Self> {
+        let pass = ImagePass::bicubic_rotation(self.state, self.cols, self.rows, theta)?;
+        self.pass(pass)
+    }
+
+    /// Download the current image; the passes appended later do not change it.
+    pub fn output(&mut self) -> ImageOutput {
+        self.steps.push(Step::Output);
+        self.images += 1;
+        ImageOutput(self.images - 1)
+    }
+
+    /// Count the luma of the current image into [`HIST_BINS`] bins over
+    /// `[0, HIST_RANGE]` and download the bins.
+    pub fn histogram(&mut self) -> Result<HistogramOutput> {
+        if self.histogram_pipeline.is_none() {
+            let (source, lines) = shaders::preprocessor().run_mapped(
+                "histogram.wgsl",
+                &watch::source("histogram.wgsl", histogram::SOURCE),
+                &pixel_defines::<T>(&Defines::new()),
+            )?;
+            let shader = self
+                .state
+                .create_shader_module("histogram.wgsl", &source)
+                .map_err(|err| err.locate(&lines))?;
+            let layout = ShaderLayout::from_wgsl("histogram", &source)?;
+            let (mut bind_group_layouts, pipeline_layout) =
+                layout.pipeline_layout(&self.state.device);
+            let pipeline = create_pipeline(self.state, "histogram", &pipeline_layout, &shader)?;
+            self.histogram_pipeline = Some(HistogramPipeline {
+                layout,
+                bind_group_layout: bind_group_layouts.remove(0),
+                pipeline,
+            });
+        }
+
+        let bins = GpuBuffer::zeros(self.state, HIST_BINS)?;
+        self.steps.push(Step::Histogram(bins));
+        self.histograms += 1;
+        Ok(HistogramOutput(self.histograms - 1))
+    }
+
+    /// Number of textures the runs so far have allocated.
+    pub fn textures(&self) -> usize {
+        self.pool.created()
+    }
+
+    /// Upload `image`, record all steps into one command buffer and download the outputs.
+    pub async fn run(&mut self, image: &[T]) -> Result<Outputs<T>> {
+        let submission = self.submit(image)?;
+        self.finish(submission).await
+    }
+
+    /// Upload `image` and submit all steps as one command buffer, without waiting for them.
+    ///
+    /// Each submission copies its outputs into staging buffers of its own, so several can be
+    /// in flight and the uploads and downloads of one image overlap the passes of another.
+    pub fn submit(&mut self, image: &[T]) -> Result<Submission> {
+        let device = &self.state.device;
+        let view = |texture: &wgpu::Texture| texture.create_view(&Default::default());
+        let mut current = self.pool.acquire::<T>(device, self.cols, self.rows);
+        if let Err(err) = write_image(&self.state.queue, &current, image) {
+            self.pool.release(current);
+            return Err(err);
+        }
+        let staging = match self.staging.pop() {
+            Some(staging) => staging,
+            None => self.staging_buffers(),
+        };
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let mut outputs = staging.iter();
+        for step in &self.steps {
+            match step {
+                Step::Pass(pass) => {
+                    let output = self.pool.acquire::<T>(device, self.cols, self.rows);
+                    let bind_group = pass.bind_group(&view(&current), &view(&output))?;
+                    pass.encode(&mut encoder, &bind_group);
+                    self.pool.release(std::mem::replace(&mut current, output));
+                }
+                Step::Output => {
+                    copy_image_to_buffer::<T>(&mut encoder, &current, outputs.next().unwrap())
+                }
+                Step::Histogram(bins) => {
+                    let histogram = self.histogram_pipeline.as_ref().unwrap();
+                    let bind_group = histogram.layout.bind_group(
+                        self.state,
+                        &histogram.bind_group_layout,
+                        0,
+                        &[
+                            (
+                                "input_img",
+                                wgpu::BindingResource::TextureView(&view(&current)),
+                            ),
+                            (
+                                "bins",
+                                wgpu::BindingResource::Buffer(bins.buffer_binding(0..HIST_BINS)),
+                            ),
+                        ],
+                    )?;
+
+                    encoder.clear_buffer(bins.buffer(), 0, None);
+                    {
+                        let mut compute_pass = encoder
+                            .begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+                        compute_pass.set_pipeline(&histogram.pipeline);
+                        compute_pass.set_bind_group(0, &bind_group, &[]);
+                        compute_pass.dispatch_workgroups(
+                            self.cols.div_ceil(histogram::WORKGROUP_SIZE[0]),
+                            self.rows.div_ceil(histogram::WORKGROUP_SIZE[1]),
+                            1,
+                        );
+                    }
+                    let staging = outputs.next().unwrap();
+                    encoder.copy_buffer_to_buffer(bins.buffer(), 0, staging, 0, staging.size());
+                }
+            }
+        }
+        self.pool.release(current);
+        let index = self.state.queue.submit(Some(encoder.finish()));
+
+        let mapped = staging.iter().map(map_async).collect();
+        Ok(Submission {
+            index,
+            staging,
+            mapped,
+        })
+    }
+
+    /// Wait for `submission` and download its outputs.
+    pub async fn finish(&mut self, submission: Submission) -> Result<Outputs<T>> {
+        let Submission {
+            index,
+            staging,
+            mapped,
+        } = submission;
+        self.state
+            .device
+            .poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+        let Some(mut data) = read_mapped(&staging, mapped).await else {
+            self.state.check_device()?;
+            return Err(Error::DeviceLost(
+                "failed to map the pipeline outputs".to_string(),
+            ));
+        };
+        self.staging.push(staging);
+
+        let mut outputs = Outputs {
+            images: Vec::with_capacity(self.images),
+            histograms: Vec::with_capacity(self.histograms),
+        };
+        for (step, data) in self
+            .steps
+            .iter()
+            .filter(|step| !matches!(step, Step::Pass(_)))
+            .zip(data.drain(..))
+        {
+            match step {
+                Step::Output => {
+                    let padded: &[T] = bytemuck::cast_slice(&data);
+                    outputs.images.push(unpad_image(padded, self.cols));
+                }
+                _ => outputs.histograms.push(data[..HIST_BINS].to_vec()),
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// A staging buffer for each output step.
+    fn staging_buffers(&self) -> Vec<wgpu::Buffer> {
+        self.steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Pass(_) => None,
+                Step::Output => Some(image_buffer_size::<T>(self.cols, self.rows)),
+                Step::Histogram(bins) => Some(bins.size()),
+            })
+            .map(|size| {
+                self.state.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: None,
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect()
+    }
+}
+
+fn map_async(buffer: &wgpu::Buffer) -> MapReceiver {
+    let (sender, receiver) = futures_channel::oneshot::channel();
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |result| {
+        sender.send(result).ok();
+    });
+    receiver
+}
+
+/// Read the mapped staging buffers as `u32`s and unmap them.
+async fn read_mapped(buffers: &[wgpu::Buffer], mapped: Vec<MapReceiver>) -> Option<Vec<Vec<u32>>> {
+    let mut data = Vec::with_capacity(buffers.len());
+    for (buffer, receiver) in buffers.iter().zip(mapped) {
+        let result = receiver.await;
+        if !matches!(result, Ok(Ok(()))) {
+            log::error!("failed to map a pipeline output: {result:?}");
+            return None;
+        }
+        let mapped = buffer.slice(..).get_mapped_range();
+        data.push(bytemuck::cast_slice(&mapped).to_vec());
+        drop(mapped);
+        buffer.unmap();
+    }
+    Some(data)
+}
@@ -0,0 +1,400 @@
+//! A small preprocessor for WGSL.
+//!
+//! Directives start a line with `#`:
+//!
+//! - `#include "file.wgsl"` inserts another source, registered with
+//!   [`Preprocessor::source`] or found in one of the include directories;
+//! - `#define NAME value` and `#undef NAME`;
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`.
+//!
+//! `{{NAME}}` anywhere else is replaced by the value of `NAME`. The defines work like the
+//! `-D NAME=value` build options of OpenCL and can be written as such, see
+//! [`Defines::from_options`]. Directives and skipped lines become empty lines and an include
+//! becomes the lines of its file, so [`Preprocessor::run_mapped`] keeps a [`LineMap`] from
+//! the lines of the output back to the file and line they came from.
+//!
+//! This file only uses `std`, `build.rs` compiles it too.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Includes nested deeper than this are taken for a cycle.
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+/// Names and values of the defines, in name order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Defines(BTreeMap<String, String>);
+
+impl Defines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse OpenCL style options, e.g. `-D FILTER_SIZE=5 -DCHANNELS=4 -D USE_F16`.
+    ///
+    /// A define without a value is `1`, as in OpenCL; other options are ignored.
+    pub fn from_options(options: &str) -> Self {
+        let mut defines = Self::new();
+        let mut words = options.split_whitespace();
+        while let Some(word) = words.next() {
+            let Some(define) = word.strip_prefix("-D") else {
+                continue;
+            };
+            let define = if define.is_empty() {
+                words.next().unwrap_or_default()
+            } else {
+                define
+            };
+            match define.split_once('=') {
+                Some((name, value)) => defines.insert(name, value),
+                None if !define.is_empty() => defines.insert(define, "1"),
+                None => {}
+            }
+        }
+        defines
+    }
+
+    /// The defines as OpenCL build options.
+    pub fn to_options(&self) -> String {
+        self.0
+            .iter()
+            .map(|(name, value)| format!("-D {name}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Add a define, e.g. `Defines::new().define("FILTER_SIZE", 5)`.
+    pub fn define(mut self, name: &str, value: impl fmt::Display) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    pub fn insert(&mut self, name: &str, value: impl fmt::Display) {
+        self.0.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.0.remove(name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+}
+
+/// A directive or substitution that failed, with its file and 1-based line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreprocessError {
+    pub file: String,
+    pub line: u32,
+    pub message: String,
+    /// The line of the source.
+    pub text: String,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// The file and 1-based line of each line of a preprocessed source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineMap {
+    files: Vec<String>,
+    /// The index into `files` and the line, by line of the output.
+    lines: Vec<(usize, u32)>,
+}
+
+impl LineMap {
+    /// The file and line that the 1-based `line` of the output came from.
+    pub fn locate(&self, line: u32) -> Option<(&str, u32)> {
+        let &(file, line) = self.lines.get((line as usize).checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+
+    fn file(&mut self, name: &str) -> usize {
+        match self.files.iter().position(|file| file == name) {
+            Some(file) => file,
+            None => {
+                self.files.push(name.to_string());
+                self.files.len() - 1
+            }
+        }
+    }
+}
+
+/// Resolves the includes and runs the directives.
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessor {
+    sources: HashMap<String, String>,
+    include_dirs: Vec<PathBuf>,
+}
+
+/// State of one `#ifdef`/`#ifndef` block.
+struct Conditional {
+    /// Whether the lines of the current branch are kept.
+    active: bool,
+    /// Whether the enclosing lines are kept.
+    parent_active: bool,
+    in_else: bool,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an include by name, e.g. a source embedded with `include_str!`.
+    pub fn source(mut self, name: &str, source: &str) -> Self {
+        self.sources.insert(name.to_string(), source.to_string());
+        self
+    }
+
+    /// Look for the includes that are not registered in `dir`.
+    pub fn include_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.include_dirs.push(dir.into());
+        self
+    }
+
+    /// Preprocess `source`, named `name` in errors.
+    pub fn run(
+        &self,
+        name: &str,
+        source: &str,
+        defines: &Defines,
+    ) -> Result<String, PreprocessError> {
+        self.run_mapped(name, source, defines).map(|(out, _)| out)
+    }
+
+    /// Preprocess `source` like [`run`](Self::run), with the file and line of each line of
+    /// the output.
+    pub fn run_mapped(
+        &self,
+        name: &str,
+        source: &str,
+        defines: &Defines,
+    ) -> Result<(String, LineMap), PreprocessError> {
+        let mut defines = defines.clone();
+        let mut out = String::with_capacity(source.len());
+        let mut lines = LineMap::default();
+        self.expand(name, source, &mut defines, &mut out, &mut lines, 0)?;
+        Ok((out, lines))
+    }
+
+    fn expand(
+        &self,
+        name: &str,
+        source: &str,
+        defines: &mut Defines,
+        out: &mut String,
+        lines: &mut LineMap,
+        depth: usize,
+    ) -> Result<(), PreprocessError> {
+        let file = lines.file(name);
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        let mut last_line = 0;
+        for (index, text) in source.lines().enumerate() {
+            last_line = index + 1;
+            let error = |message: String| PreprocessError {
+                file: name.to_string(),
+                line: (index + 1) as u32,
+                message,
+                text: text.to_string(),
+            };
+            let active = conditionals
+                .last()
+                .is_none_or(|conditional| conditional.active);
+
+            let Some(directive) = text.trim_start().strip_prefix('#') else {
+                if active {
+                    *out += &substitute(text, defines).map_err(error)?;
+                }
+                *out += "\n";
+                lines.lines.push((file, last_line as u32));
+                continue;
+            };
+            let (keyword, argument) = directive
+                .trim()
+                .split_once(char::is_whitespace)
+                .map_or((directive.trim(), ""), |(keyword, argument)| {
+                    (keyword, argument.trim())
+                });
+            let argument_name = || match argument {
+                "" => Err(error(format!("#{keyword} needs a name"))),
+                _ => Ok(argument),
+            };
+
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let defined = defines.contains(argument_name()?);
+                    conditionals.push(Conditional {
+                        active: active && defined == (keyword == "ifdef"),
+                        parent_active: active,
+                        in_else: false,
+                    });
+                }
+                "else" => match conditionals.last_mut() {
+                    Some(conditional) if !conditional.in_else => {
+                        conditional.active = conditional.parent_active && !conditional.active;
+                        conditional.in_else = true;
+                    }
+                    _ => return Err(error("#else without #ifdef".to_string())),
+                },
+                "endif" => {
+                    conditionals
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef".to_string()))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) = argument_name()?
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((argument, ""));
+                    let value = substitute(value.trim(), defines).map_err(error)?;
+                    defines.insert(define, value);
+                }
+                "undef" => defines.remove(argument_name()?),
+                "include" => {
+                    let include = argument
+                        .strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                        .ok_or_else(|| error("expected #include \"file\"".to_string()))?;
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(error(format!("#include \"{include}\" nests too deep")));
+                    }
+                    let source = self
+                        .resolve(include)
+                        .ok_or_else(|| error(format!("can not find \"{include}\"")))?;
+                    self.expand(include, &source, defines, out, lines, depth + 1)?;
+                    continue;
+                }
+                _ => return Err(error(format!("unknown directive #{keyword}"))),
+            }
+            *out += "\n";
+            lines.lines.push((file, last_line as u32));
+        }
+
+        if !conditionals.is_empty() {
+            return Err(PreprocessError {
+                file: name.to_string(),
+                line: last_line as u32,
+                message: "missing #endif".to_string(),
+                text: String::new(),
+            });
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, include: &str) -> Option<String> {
+        if let Some(source) = self.sources.get(include) {
+            return Some(source.clone());
+        }
+        self.include_dirs
+            .iter()
+            .find_map(|dir| std::fs::read_to_string(dir.join(include)).ok())
+    }
+}
+
+/// Replace the `{{NAME}}`s of `text` with the values of the defines.
+fn substitute(text: &str, defines: &Defines) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + end].trim();
+        let value = defines
+            .get(name)
+            .ok_or_else(|| format!("`{name}` is not defined"))?;
+        out += &rest[..start];
+        out += value;
+        rest = &rest[start + end + 2..];
+    }
+    Ok(out + rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Defines, Preprocessor};
+
+    #[test]
+    pub fn test_preprocess() {
+        let source = "#include \"common.wgsl\"
+#ifndef SIZE
+#define SIZE 3
+#endif
+#ifdef PACKED
+packed
+#else
+const SIZE: u32 = {{SIZE}}u;
+#endif
+";
+        let preprocessor = Preprocessor::new().source("common.wgsl", "const C: u32 = {{ C }};");
+        let defines = Defines::from_options("-D C=4 -DSIZE=5 -cl-fast-relaxed-math");
+        assert_eq!(defines.to_options(), "-D C=4 -D SIZE=5");
+
+        let out = preprocessor.run("main.wgsl", source, &defines).unwrap();
+        assert_eq!(
+            out.lines()
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>(),
+            ["const C: u32 = 4;", "const SIZE: u32 = 5u;"]
+        );
+        // the directives become empty lines, a one line include keeps the numbers
+        assert_eq!(out.lines().nth(7), Some("const SIZE: u32 = 5u;"));
+
+        let out = preprocessor
+            .run("main.wgsl", source, &defines.clone().define("PACKED", 1))
+            .unwrap();
+        assert!(out.contains("packed") && !out.contains("SIZE"));
+
+        let err = preprocessor
+            .run("main.wgsl", source, &Defines::new())
+            .unwrap_err();
+        assert_eq!((err.file.as_str(), err.line), ("common.wgsl", 1));
+        assert_eq!(err.message, "`C` is not defined");
+
+        let err = preprocessor
+            .run("main.wgsl", "#ifdef A\n", &defines)
+            .unwrap_err();
+        assert_eq!(err.message, "missing #endif");
+    }
+
+    #[test]
+    pub fn test_line_map() {
+        let preprocessor = Preprocessor::new()
+            .source(
+                "a.wgsl",
+                "const A: u32 = 1u;\n#include \"b.wgsl\"\nconst C: u32 = 3u;",
+            )
+            .source("b.wgsl", "const B0: u32 = 2u;\nconst B1: u32 = 2u;");
+        let source = "#include \"a.wgsl\"\nconst MAIN: u32 = 0u;\n";
+
+        let (out, lines) = preprocessor
+            .run_mapped("main.wgsl", source, &Defines::new())
+            .unwrap();
+        // the line after the includes is the 5th of the output, the 2nd of the source
+        assert_eq!(out.lines().nth(4), Some("const MAIN: u32 = 0u;"));
+        assert_eq!(
+            (1..=6).map(|line| lines.locate(line)).collect::<Vec<_>>(),
+            [
+                Some(("a.wgsl", 1)),
+                Some(("b.wgsl", 1)),
+                Some(("b.wgsl", 2)),
+                Some(("a.wgsl", 3)),
+                Some(("main.wgsl", 2)),
+                None,
+            ]
+        );
+        assert_eq!(lines.locate(0), None);
+    }
+}
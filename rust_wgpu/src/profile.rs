@@ -0,0 +1,273 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::trace::{Span, Timeline};
+use crate::WgpuState;
+
+/// Kind of command a profiled submission records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Dispatch,
+    Copy,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 2] = [Stage::Dispatch, Stage::Copy];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Dispatch => "dispatch",
+            Stage::Copy => "copy",
+        }
+    }
+}
+
+/// Times command submissions with GPU timestamps, or on the CPU as a fallback.
+///
+/// With `Features::TIMESTAMP_QUERY` a timestamp is written before and after the commands of
+/// each [`Profiler::submit`] and resolved by [`Profiler::report`]. Without it every submission
+/// is waited for and timed with the host clock, which includes the submit overhead.
+pub struct Profiler<'a> {
+    state: &'a WgpuState,
+    query_set: Option<wgpu::QuerySet>,
+    capacity: u32,
+    epoch: Instant,
+    commands: Vec<(Stage, String)>,
+    cpu_times: Vec<(u64, u64)>,
+}
+
+impl<'a> Profiler<'a> {
+    /// Profile up to `capacity` submissions.
+    pub fn new(state: &'a WgpuState, capacity: u32) -> Self {
+        let query_set = state
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                state.device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("Profiler Timestamps"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: capacity * 2,
+                })
+            });
+
+        Self {
+            state,
+            query_set,
+            capacity,
+            epoch: Instant::now(),
+            commands: Vec::new(),
+            cpu_times: Vec::new(),
+        }
+    }
+
+    /// Whether the timings come from GPU timestamps.
+    pub fn uses_timestamps(&self) -> bool {
+        self.query_set.is_some()
+    }
+
+    /// Record the commands of `record` in their own command buffer, submit and time it.
+    /// [`Error::Argument`] once `capacity` submissions are profiled.
+    pub fn submit(
+        &mut self,
+        stage: Stage,
+        label: &str,
+        record: impl FnOnce(&mut wgpu::CommandEncoder),
+    ) -> Result<()> {
+        if self.commands.len() as u32 >= self.capacity {
+            return Err(Error::Argument(format!(
+                "profiler capacity of {} submissions exceeded",
+                self.capacity
+            )));
+        }
+        let device = &self.state.device;
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        match &self.query_set {
+            Some(query_set) => {
+                let index = self.commands.len() as u32 * 2;
+                encoder.write_timestamp(query_set, index);
+                record(&mut encoder);
+                encoder.write_timestamp(query_set, index + 1);
+                self.state.queue.submit(Some(encoder.finish()));
+            }
+            None => {
+                // wait for the previous work so it is not counted
+                device.poll(wgpu::Maintain::Wait);
+                record(&mut encoder);
+                let start = self.epoch.elapsed().as_nanos() as u64;
+                self.state.queue.submit(Some(encoder.finish()));
+                device.poll(wgpu::Maintain::Wait);
+                let end = self.epoch.elapsed().as_nanos() as u64;
+                self.cpu_times.push((start, end));
+            }
+        }
+        self.commands.push((stage, label.to_string()));
+        Ok(())
+    }
+
+    /// Submit and time compute passes.
+    pub fn dispatch(
+        &mut self,
+        label: &str,
+        record: impl FnOnce(&mut wgpu::CommandEncoder),
+    ) -> Result<()> {
+        self.submit(Stage::Dispatch, label, record)
+    }
+
+    /// Submit and time buffer copies.
+    pub fn copy(
+        &mut self,
+        label: &str,
+        record: impl FnOnce(&mut wgpu::CommandEncoder),
+    ) -> Result<()> {
+        self.submit(Stage::Copy, label, record)
+    }
+
+    /// Wait for the profiled submissions and collect their timings.
+    pub async fn report(&self) -> Option<ProfileReport> {
+        let times = match &self.query_set {
+            Some(query_set) => self.resolve(query_set).await?,
+            None => self.cpu_times.clone(),
+        };
+
+        let commands = self
+            .commands
+            .iter()
+            .zip(times)
+            .map(|((stage, label), (start_ns, end_ns))| CommandTiming {
+                stage: *stage,
+                label: label.clone(),
+                start_ns,
+                end_ns,
+            })
+            .collect();
+
+        Some(ProfileReport {
+            commands,
+            timestamps: self.uses_timestamps(),
+        })
+    }
+
+    /// Read back the timestamps as nanoseconds since the first one.
+    async fn resolve(&self, query_set: &wgpu::QuerySet) -> Option<Vec<(u64, u64)>> {
+        if self.commands.is_empty() {
+            return Some(Vec::new());
+        }
+        let device = &self.state.device;
+        let count = self.commands.len() as u32 * 2;
+        let size = count as wgpu::BufferAddress * std::mem::size_of::<u64>() as wgpu::BufferAddress;
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Profiler Resolve"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.resolve_query_set(query_set, 0..count, &resolve_buffer, 0);
+        self.state.queue.submit(Some(encoder.finish()));
+
+        let ticks = self.state.read_buffer::<u64>(&resolve_buffer, size).await?;
+        let period = self.state.queue.get_timestamp_period() as f64;
+        let to_ns = |tick: u64| (tick.saturating_sub(ticks[0]) as f64 * period) as u64;
+        Some(
+            ticks
+                .chunks_exact(2)
+                .map(|pair| (to_ns(pair[0]), to_ns(pair[1])))
+                .collect(),
+        )
+    }
+}
+
+/// Start and end of one submission in nanoseconds since the profiler's origin.
+#[derive(Debug, Clone)]
+pub struct CommandTiming {
+    pub stage: Stage,
+    pub label: String,
+    pub start_ns: u64,
+    pub end_ns: u64,
+}
+
+impl CommandTiming {
+    pub fn duration(&self) -> Duration {
+        Duration::from_nanos(self.end_ns.saturating_sub(self.start_ns))
+    }
+}
+
+/// Timings of the profiled submissions, in submission order.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub commands: Vec<CommandTiming>,
+    /// The timings come from GPU timestamps rather than the host clock.
+    pub timestamps: bool,
+}
+
+impl ProfileReport {
+    /// Total time of the submissions of a stage.
+    pub fn stage_total(&self, stage: Stage) -> Duration {
+        self.commands
+            .iter()
+            .filter(|command| command.stage == stage)
+            .map(CommandTiming::duration)
+            .sum()
+    }
+
+    /// Total time of all submissions.
+    pub fn total(&self) -> Duration {
+        self.commands.iter().map(CommandTiming::duration).sum()
+    }
+}
+
+impl Timeline for ProfileReport {
+    fn spans(&self) -> Vec<Span<'_>> {
+        self.commands
+            .iter()
+            .map(|command| Span {
+                label: &command.label,
+                category: command.stage.name(),
+                queued_ns: None,
+                start_ns: command.start_ns,
+                end_ns: command.end_ns,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1e3;
+        let clock = if self.timestamps { "gpu" } else { "cpu" };
+
+        writeln!(
+            f,
+            "{:<10}{:<24}{:>12}",
+            "stage",
+            "command",
+            format!("{clock} (ms)")
+        )?;
+        for command in &self.commands {
+            writeln!(
+                f,
+                "{:<10}{:<24}{:>12.3}",
+                command.stage.name(),
+                command.label,
+                ms(command.duration())
+            )?;
+        }
+        writeln!(f, "{}", "-".repeat(46))?;
+        for stage in Stage::ALL {
+            writeln!(
+                f,
+                "{:<10}{:<24}{:>12.3}",
+                stage.name(),
+                "total",
+                ms(self.stage_total(stage))
+            )?;
+        }
+        write!(f, "{:<34}{:>12.3}", "total", ms(self.total()))
+    }
+}
@@ -0,0 +1,248 @@
+//! CPU reference implementations of the shaders, for golden comparisons.
+//!
+//! They follow the shaders' conventions (border handling, interpolation) rather than an
+//! ideal definition, so the results only differ by floating point rounding. With the `rayon`
+//! feature the image operations run in parallel over rows.
+
+/// Apply `f(row, out_row)` to every row of `out`, in parallel with the `rayon` feature.
+fn for_each_row(out: &mut [f32], row_len: usize, f: impl Fn(usize, &mut [f32]) + Sync + Send) {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        out.par_chunks_mut(row_len)
+            .enumerate()
+            .for_each(|(y, row)| f(y, row));
+    }
+    #[cfg(not(feature = "rayon"))]
+    out.chunks_mut(row_len)
+        .enumerate()
+        .for_each(|(y, row)| f(y, row));
+}
+
+/// c = a + b
+pub fn vector_add(a: &[f32], b: &[f32]) -> Vec<f32> {
+    a.iter().zip(b).map(|(a, b)| a + b).collect()
+}
+
+/// Dot product of `x` and `y`, accumulated in `f64`.
+pub fn dot(x: &[f32], y: &[f32]) -> f32 {
+    x.iter()
+        .zip(y)
+        .map(|(x, y)| *x as f64 * *y as f64)
+        .sum::<f64>() as f32
+}
+
+/// Euclidean norm of `x`.
+pub fn nrm2(x: &[f32]) -> f32 {
+    dot(x, x).sqrt()
+}
+
+/// Sum of the absolute values of `x`.
+pub fn asum(x: &[f32]) -> f32 {
+    x.iter().map(|v| v.abs() as f64).sum::<f64>() as f32
+}
+
+/// Index of the first element with the largest absolute value, `None` for an empty vector.
+pub fn iamax(x: &[f32]) -> Option<usize> {
+    x.iter()
+        .enumerate()
+        .fold(None, |best: Option<(usize, f32)>, (i, v)| match best {
+            Some((_, best_value)) if best_value >= v.abs() => best,
+            _ => Some((i, v.abs())),
+        })
+        .map(|(i, _)| i)
+}
+
+/// Transpose of a row-major `rows x cols` matrix.
+pub fn transpose<T: Copy>(input: &[T], rows: usize, cols: usize) -> Vec<T> {
+    (0..cols * rows)
+        .map(|i| input[(i % rows) * cols + i / rows])
+        .collect()
+}
+
+/// Convolve a `cols x rows` image of `channels` interleaved channels with a square filter;
+/// pixels outside of the image are skipped (zero padding).
+pub fn convolution(
+    image: &[f32],
+    cols: usize,
+    rows: usize,
+    channels: usize,
+    filter: &[f32],
+) -> Vec<f32> {
+    let filter_size = (filter.len() as f64).sqrt() as usize;
+    let half = (filter_size / 2) as isize;
+    let mut out = vec![0.0; image.len()];
+
+    for_each_row(&mut out, cols * channels, |y, out_row| {
+        for x in 0..cols {
+            for i in -half..=half {
+                for j in -half..=half {
+                    let (px, py) = (x as isize + j, y as isize + i);
+                    if px < 0 || px >= cols as isize || py < 0 || py >= rows as isize {
+                        continue;
+                    }
+                    let weight = filter[((i + half) * filter_size as isize + j + half) as usize];
+                    let pixel = (py as usize * cols + px as usize) * channels;
+                    for c in 0..channels {
+                        out_row[x * channels + c] += image[pixel + c] * weight;
+                    }
+                }
+            }
+        }
+    });
+    out
+}
+
+/// Rotate a `cols x rows` image of `channels` interleaved channels by `theta` radians around
+/// its center, interpolating bilinearly; pixels outside of the image read as zero.
+///
+/// Like the shader, the source location is split with a truncating cast and `fract`.
+pub fn rotation(image: &[f32], cols: usize, rows: usize, channels: usize, theta: f32) -> Vec<f32> {
+    let (x0, y0) = (cols as f32 / 2.0, rows as f32 / 2.0);
+    let (sin_theta, cos_theta) = theta.sin_cos();
+    let texel = |x: i32, y: i32, c: usize| {
+        if x < 0 || x >= cols as i32 || y < 0 || y >= rows as i32 {
+            0.0
+        } else {
+            image[(y as usize * cols + x as usize) * channels + c]
+        }
+    };
+
+    let mut out = vec![0.0; image.len()];
+    for_each_row(&mut out, cols * channels, |y, out_row| {
+        for x in 0..cols {
+            let (x_, y_) = (x as f32 - x0, y as f32 - y0);
+            let u = x_ * cos_theta - y_ * sin_theta + x0;
+            let v = x_ * sin_theta + y_ * cos_theta + y0;
+            let (u_int, v_int) = (u as i32, v as i32);
+            let (u_frac, v_frac) = (u - u.floor(), v - v.floor());
+
+            for c in 0..channels {
+                let interp_u0 =
+                    texel(u_int, v_int, c) * (1.0 - u_frac) + texel(u_int + 1, v_int, c) * u_frac;
+                let interp_u1 = texel(u_int, v_int + 1, c) * (1.0 - u_frac)
+                    + texel(u_int + 1, v_int + 1, c) * u_frac;
+                out_row[x * channels + c] = interp_u0 * (1.0 - v_frac) + interp_u1 * v_frac;
+            }
+        }
+    });
+    out
+}
+
+/// Rotate like [`rotation`], interpolating with a Catmull-Rom spline through the 4x4 pixels
+/// around the source location, like the shader with `BICUBIC`.
+///
+/// Unlike the bilinear shader, the source location is split with `floor`.
+pub fn bicubic_rotation(
+    image: &[f32],
+    cols: usize,
+    rows: usize,
+    channels: usize,
+    theta: f32,
+) -> Vec<f32> {
+    let (x0, y0) = (cols as f32 / 2.0, rows as f32 / 2.0);
+    let (sin_theta, cos_theta) = theta.sin_cos();
+    let texel = |x: i32, y: i32, c: usize| {
+        if x < 0 || x >= cols as i32 || y < 0 || y >= rows as i32 {
+            0.0
+        } else {
+            image[(y as usize * cols + x as usize) * channels + c]
+        }
+    };
+
+    let mut out = vec![0.0; image.len()];
+    for_each_row(&mut out, cols * channels, |y, out_row| {
+        for x in 0..cols {
+            let (x_, y_) = (x as f32 - x0, y as f32 - y0);
+            let u = x_ * cos_theta - y_ * sin_theta + x0;
+            let v = x_ * sin_theta + y_ * cos_theta + y0;
+            let (u_int, v_int) = (u.floor() as i32, v.floor() as i32);
+            let (wx, wy) = (cubic_weights(u - u.floor()), cubic_weights(v - v.floor()));
+
+            for c in 0..channels {
+                out_row[x * channels + c] = (0..4)
+                    .map(|j| {
+                        let row: f32 = (0..4)
+                            .map(|i| wx[i] * texel(u_int + i as i32 - 1, v_int + j as i32 - 1, c))
+                            .sum();
+                        wy[j] * row
+                    })
+                    .sum();
+            }
+        }
+    });
+    out
+}
+
+/// The Catmull-Rom weights of the four pixels around a location `t` past the second one.
+fn cubic_weights(t: f32) -> [f32; 4] {
+    [
+        ((-0.5 * t + 1.0) * t - 0.5) * t,
+        (1.5 * t - 2.5) * t * t + 1.0,
+        ((-1.5 * t + 2.0) * t + 0.5) * t,
+        (0.5 * t - 0.5) * t * t,
+    ]
+}
+
+/// Histogram of the luma of a `channels` interleaved RGB(A) image in `bins` bins over
+/// `[0, 1]`, like the histogram shader; values outside of the range go to the end bins.
+pub fn histogram(image: &[f32], channels: usize, bins: usize) -> Vec<u32> {
+    let mut histogram = vec![0; bins];
+    for pixel in image.chunks_exact(channels) {
+        let luma = pixel[0] * 0.299 + pixel[1] * 0.587 + pixel[2] * 0.114;
+        let bin = (luma * bins as f32).clamp(0.0, (bins - 1) as f32) as usize;
+        histogram[bin] += 1;
+    }
+    histogram
+}
+
+/// Element-wise error of a result against its reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorStats {
+    pub max_abs: f32,
+    pub mean_abs: f32,
+}
+
+impl ErrorStats {
+    /// Compare `actual` with `expected`, which must have the same length.
+    pub fn compare(actual: &[f32], expected: &[f32]) -> Self {
+        assert_eq!(actual.len(), expected.len(), "length mismatch");
+        let (max_abs, sum_abs) = actual
+            .iter()
+            .zip(expected)
+            .map(|(a, e)| (a - e).abs())
+            .fold((0f32, 0f64), |(max, sum), diff| {
+                (max.max(diff), sum + diff as f64)
+            });
+        Self {
+            max_abs,
+            mean_abs: (sum_abs / actual.len().max(1) as f64) as f32,
+        }
+    }
+
+    /// Whether both errors are within the tolerances.
+    pub fn within(&self, max_abs: f32, mean_abs: f32) -> bool {
+        self.max_abs <= max_abs && self.mean_abs <= mean_abs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bicubic_rotation, convolution, rotation, ErrorStats};
+
+    #[test]
+    pub fn test_reference_identities() {
+        let image: Vec<f32> = (0..6 * 4 * 2).map(|v| v as f32).collect();
+
+        // a centered one-hot filter and a zero rotation copy the image
+        let mut filter = vec![0.0; 9];
+        filter[4] = 1.0;
+        assert_eq!(convolution(&image, 6, 4, 2, &filter), image);
+        assert_eq!(rotation(&image, 6, 4, 2, 0.0), image);
+        assert_eq!(bicubic_rotation(&image, 6, 4, 2, 0.0), image);
+
+        let stats = ErrorStats::compare(&[1.0, 2.0], &[1.5, 2.0]);
+        assert_eq!(stats.max_abs, 0.5);
+        assert_eq!(stats.mean_abs, 0.25);
+    }
+}
@@ -0,0 +1,452 @@
+//! Bind group layouts derived from the `@group`/`@binding` declarations of a WGSL shader.
+//!
+//! The layout entries (binding types, texture formats, access modes and minimum buffer sizes)
+//! are read from the naga module instead of being written by hand next to the shader, and the
+//! resources of a bind group are checked against them by binding name.
+
+use std::num::NonZeroU64;
+
+use crate::error::{self, Error, ShaderError};
+use crate::WgpuState;
+
+/// A resource variable of the shader.
+#[derive(Debug, Clone)]
+pub struct ShaderBinding {
+    pub name: String,
+    pub group: u32,
+    pub entry: wgpu::BindGroupLayoutEntry,
+}
+
+impl ShaderBinding {
+    /// Where the variable is declared, e.g. "@group(0) @binding(3)".
+    pub fn location(&self) -> String {
+        format!("@group({}) @binding({})", self.group, self.entry.binding)
+    }
+
+    fn mismatch(&self, message: String) -> Error {
+        Error::Binding {
+            name: self.name.clone(),
+            message: format!("({}) {message}", self.location()),
+        }
+    }
+}
+
+/// The resource bindings of a shader, by group and binding.
+#[derive(Debug, Clone)]
+pub struct ShaderLayout {
+    bindings: Vec<ShaderBinding>,
+}
+
+impl ShaderLayout {
+    /// Reflect the resource variables of a parsed module.
+    ///
+    /// Bindings are visible to the stages of the module's entry points. Float textures are
+    /// unfilterable and samplers non-filtering, as the shaders read texels with `textureLoad`.
+    pub fn new(module: &naga::Module) -> Self {
+        let visibility =
+            module
+                .entry_points
+                .iter()
+                .fold(wgpu::ShaderStages::NONE, |stages, entry_point| {
+                    stages
+                        | match entry_point.stage {
+                            naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                            naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                            naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+                        }
+                });
+
+        let mut bindings: Vec<ShaderBinding> = module
+            .global_variables
+            .iter()
+            .filter_map(|(_, var)| {
+                let binding = var.binding.as_ref()?;
+                let (ty, count) = match module.types[var.ty].inner {
+                    naga::TypeInner::BindingArray { base, size } => (base, array_count(size)),
+                    _ => (var.ty, None),
+                };
+                let ty = binding_type(module, var.space, ty)?;
+                Some(ShaderBinding {
+                    name: var.name.clone().unwrap_or_default(),
+                    group: binding.group,
+                    entry: wgpu::BindGroupLayoutEntry {
+                        binding: binding.binding,
+                        visibility,
+                        ty,
+                        count,
+                    },
+                })
+            })
+            .collect();
+        bindings.sort_by_key(|binding| (binding.group, binding.entry.binding));
+        Self { bindings }
+    }
+
+    /// Parse `source` and reflect it; `label` names the shader in errors.
+    pub fn from_wgsl(label: &str, source: &str) -> Result<Self, ShaderError> {
+        // reflection does not depend on the device, allow every shader type
+        let module = error::check_wgsl(label, source, wgpu::Features::all())?;
+        Ok(Self::new(&module))
+    }
+
+    pub fn bindings(&self) -> &[ShaderBinding] {
+        &self.bindings
+    }
+
+    /// The binding named `name`.
+    pub fn binding(&self, name: &str) -> Option<&ShaderBinding> {
+        self.bindings.iter().find(|binding| binding.name == name)
+    }
+
+    /// Number of bind groups, i.e. one more than the highest group in use.
+    pub fn groups(&self) -> u32 {
+        self.bindings.last().map_or(0, |binding| binding.group + 1)
+    }
+
+    /// Layout entries of `group`.
+    pub fn entries(&self, group: u32) -> Vec<wgpu::BindGroupLayoutEntry> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.group == group)
+            .map(|binding| binding.entry)
+            .collect()
+    }
+
+    pub fn bind_group_layout(&self, device: &wgpu::Device, group: u32) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &self.entries(group),
+        })
+    }
+
+    /// The layouts of all groups and a pipeline layout made of them.
+    pub fn pipeline_layout(
+        &self,
+        device: &wgpu::Device,
+    ) -> (Vec<wgpu::BindGroupLayout>, wgpu::PipelineLayout) {
+        let bind_group_layouts: Vec<_> = (0..self.groups())
+            .map(|group| self.bind_group_layout(device, group))
+            .collect();
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            push_constant_ranges: &[],
+        });
+        (bind_group_layouts, pipeline_layout)
+    }
+
+    /// Create a bind group of `group` from resources given by binding name.
+    ///
+    /// Each binding of the group needs one resource of the declared kind; buffers need the
+    /// matching usage and at least the minimum binding size. Mismatches are
+    /// [`Error::Binding`] errors naming the binding.
+    pub fn bind_group(
+        &self,
+        state: &WgpuState,
+        layout: &wgpu::BindGroupLayout,
+        group: u32,
+        resources: &[(&str, wgpu::BindingResource<'_>)],
+    ) -> error::Result<wgpu::BindGroup> {
+        let entries = self.check(group, resources)?;
+        state.error_scope(|device| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout,
+                entries: &entries,
+            })
+        })
+    }
+
+    /// Match the resources to the bindings of `group`.
+    fn check<'r>(
+        &self,
+        group: u32,
+        resources: &[(&str, wgpu::BindingResource<'r>)],
+    ) -> error::Result<Vec<wgpu::BindGroupEntry<'r>>> {
+        let mut entries = Vec::with_capacity(resources.len());
+        for (name, resource) in resources {
+            let binding = self
+                .binding(name)
+                .filter(|binding| binding.group == group)
+                .ok_or_else(|| Error::Binding {
+                    name: name.to_string(),
+                    message: format!("is not declared in @group({group}) of the shader"),
+                })?;
+            check_resource(binding, resource)?;
+            entries.push(wgpu::BindGroupEntry {
+                binding: binding.entry.binding,
+                resource: resource.clone(),
+            });
+        }
+
+        if let Some(binding) = self.bindings.iter().find(|binding| {
+            binding.group == group && !resources.iter().any(|(name, _)| *name == binding.name)
+        }) {
+            return Err(binding.mismatch("has no resource".to_string()));
+        }
+        Ok(entries)
+    }
+}
+
+fn array_count(size: naga::ArraySize) -> Option<std::num::NonZeroU32> {
+    match size {
+        naga::ArraySize::Constant(count) => Some(count),
+        naga::ArraySize::Dynamic => None,
+    }
+}
+
+fn binding_type(
+    module: &naga::Module,
+    space: naga::AddressSpace,
+    ty: naga::Handle<naga::Type>,
+) -> Option<wgpu::BindingType> {
+    let buffer = |buffer_ty| {
+        // the size with one element for runtime-sized arrays
+        let size = module.types[ty].inner.size(module.to_ctx());
+        wgpu::BindingType::Buffer {
+            ty: buffer_ty,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(size as u64),
+        }
+    };
+
+    match space {
+        naga::AddressSpace::Uniform => Some(buffer(wgpu::BufferBindingType::Uniform)),
+        naga::AddressSpace::Storage { access } => Some(buffer(wgpu::BufferBindingType::Storage {
+            read_only: !access.contains(naga::StorageAccess::STORE),
+        })),
+        naga::AddressSpace::Handle => match module.types[ty].inner {
+            naga::TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            } => Some(image_binding_type(dim, arrayed, class)),
+            naga::TypeInner::Sampler { comparison } => {
+                Some(wgpu::BindingType::Sampler(if comparison {
+                    wgpu::SamplerBindingType::Comparison
+                } else {
+                    wgpu::SamplerBindingType::NonFiltering
+                }))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn image_binding_type(
+    dim: naga::ImageDimension,
+    arrayed: bool,
+    class: naga::ImageClass,
+) -> wgpu::BindingType {
+    let view_dimension = match (dim, arrayed) {
+        (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    };
+
+    match class {
+        naga::ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+            sample_type: match kind {
+                naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                _ => wgpu::TextureSampleType::Float { filterable: false },
+            },
+            view_dimension,
+            multisampled: multi,
+        },
+        naga::ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Depth,
+            view_dimension,
+            multisampled: multi,
+        },
+        naga::ImageClass::Storage { format, access } => wgpu::BindingType::StorageTexture {
+            access: if !access.contains(naga::StorageAccess::LOAD) {
+                wgpu::StorageTextureAccess::WriteOnly
+            } else if !access.contains(naga::StorageAccess::STORE) {
+                wgpu::StorageTextureAccess::ReadOnly
+            } else {
+                wgpu::StorageTextureAccess::ReadWrite
+            },
+            format: storage_format(format),
+            view_dimension,
+        },
+    }
+}
+
+fn storage_format(format: naga::StorageFormat) -> wgpu::TextureFormat {
+    use naga::StorageFormat as Sf;
+    use wgpu::TextureFormat as Tf;
+
+    match format {
+        Sf::R8Unorm => Tf::R8Unorm,
+        Sf::R8Snorm => Tf::R8Snorm,
+        Sf::R8Uint => Tf::R8Uint,
+        Sf::R8Sint => Tf::R8Sint,
+        Sf::R16Uint => Tf::R16Uint,
+        Sf::R16Sint => Tf::R16Sint,
+        Sf::R16Float => Tf::R16Float,
+        Sf::Rg8Unorm => Tf::Rg8Unorm,
+        Sf::Rg8Snorm => Tf::Rg8Snorm,
+        Sf::Rg8Uint => Tf::Rg8Uint,
+        Sf::Rg8Sint => Tf::Rg8Sint,
+        Sf::R32Uint => Tf::R32Uint,
+        Sf::R32Sint => Tf::R32Sint,
+        Sf::R32Float => Tf::R32Float,
+        Sf::Rg16Uint => Tf::Rg16Uint,
+        Sf::Rg16Sint => Tf::Rg16Sint,
+        Sf::Rg16Float => Tf::Rg16Float,
+        Sf::Rgba8Unorm => Tf::Rgba8Unorm,
+        Sf::Rgba8Snorm => Tf::Rgba8Snorm,
+        Sf::Rgba8Uint => Tf::Rgba8Uint,
+        Sf::Rgba8Sint => Tf::Rgba8Sint,
+        Sf::Rgb10a2Unorm => Tf::Rgb10a2Unorm,
+        Sf::Rg11b10Float => Tf::Rg11b10Float,
+        Sf::Rg32Uint => Tf::Rg32Uint,
+        Sf::Rg32Sint => Tf::Rg32Sint,
+        Sf::Rg32Float => Tf::Rg32Float,
+        Sf::Rgba16Uint => Tf::Rgba16Uint,
+        Sf::Rgba16Sint => Tf::Rgba16Sint,
+        Sf::Rgba16Float => Tf::Rgba16Float,
+        Sf::Rgba32Uint => Tf::Rgba32Uint,
+        Sf::Rgba32Sint => Tf::Rgba32Sint,
+        Sf::Rgba32Float => Tf::Rgba32Float,
+        Sf::R16Unorm => Tf::R16Unorm,
+        Sf::R16Snorm => Tf::R16Snorm,
+        Sf::Rg16Unorm => Tf::Rg16Unorm,
+        Sf::Rg16Snorm => Tf::Rg16Snorm,
+        Sf::Rgba16Unorm => Tf::Rgba16Unorm,
+        Sf::Rgba16Snorm => Tf::Rgba16Snorm,
+    }
+}
+
+/// What the binding declares, for mismatch messages.
+fn describe_entry(ty: &wgpu::BindingType) -> &'static str {
+    match ty {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            ..
+        } => "a uniform buffer",
+        wgpu::BindingType::Buffer { .. } => "a storage buffer",
+        wgpu::BindingType::Sampler(_) => "a sampler",
+        wgpu::BindingType::Texture { .. } => "a texture view",
+        wgpu::BindingType::StorageTexture { .. } => "a storage texture view",
+    }
+}
+
+fn describe_resource(resource: &wgpu::BindingResource<'_>) -> &'static str {
+    match resource {
+        wgpu::BindingResource::Buffer(_) => "a buffer",
+        wgpu::BindingResource::BufferArray(_) => "a buffer array",
+        wgpu::BindingResource::Sampler(_) => "a sampler",
+        wgpu::BindingResource::SamplerArray(_) => "a sampler array",
+        wgpu::BindingResource::TextureView(_) => "a texture view",
+        wgpu::BindingResource::TextureViewArray(_) => "a texture view array",
+        _ => "another resource",
+    }
+}
+
+/// Check the kind of the resource, and the usage and size of buffers.
+fn check_resource(
+    binding: &ShaderBinding,
+    resource: &wgpu::BindingResource<'_>,
+) -> error::Result<()> {
+    let expected = describe_entry(&binding.entry.ty);
+    match (&binding.entry.ty, resource) {
+        (
+            wgpu::BindingType::Buffer {
+                ty,
+                min_binding_size,
+                ..
+            },
+            wgpu::BindingResource::Buffer(buffer_binding),
+        ) => {
+            let usage = match ty {
+                wgpu::BufferBindingType::Uniform => wgpu::BufferUsages::UNIFORM,
+                wgpu::BufferBindingType::Storage { .. } => wgpu::BufferUsages::STORAGE,
+            };
+            let buffer = buffer_binding.buffer;
+            if !buffer.usage().contains(usage) {
+                return Err(binding.mismatch(format!(
+                    "needs {expected}, the buffer has usage {:?}",
+                    buffer.usage()
+                )));
+            }
+            let size = buffer_binding.size.map_or(
+                buffer.size().saturating_sub(buffer_binding.offset),
+                |size| size.get(),
+            );
+            if let Some(min_size) = min_binding_size.filter(|min_size| size < min_size.get()) {
+                return Err(binding.mismatch(format!(
+                    "binds {size} bytes, the shader reads at least {min_size}"
+                )));
+            }
+            Ok(())
+        }
+        (wgpu::BindingType::Sampler(_), wgpu::BindingResource::Sampler(_))
+        | (
+            wgpu::BindingType::Texture { .. } | wgpu::BindingType::StorageTexture { .. },
+            wgpu::BindingResource::TextureView(_),
+        ) if binding.entry.count.is_none() => Ok(()),
+        (_, wgpu::BindingResource::BufferArray(_))
+        | (_, wgpu::BindingResource::SamplerArray(_))
+        | (_, wgpu::BindingResource::TextureViewArray(_))
+            if binding.entry.count.is_some() =>
+        {
+            Ok(())
+        }
+        _ => Err(binding.mismatch(format!(
+            "needs {expected}, got {}",
+            describe_resource(resource)
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShaderLayout;
+    use crate::preprocess::Defines;
+    use crate::shaders::{preprocessor, rotation};
+
+    #[test]
+    pub fn test_reflect_rotation() {
+        let source = preprocessor()
+            .run("rotation.wgsl", rotation::SOURCE, &Defines::new())
+            .unwrap();
+        let layout = ShaderLayout::from_wgsl("rotation.wgsl", &source).unwrap();
+        assert_eq!(layout.groups(), 1);
+        let names: Vec<_> = layout.bindings().iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, ["input_img", "output_img", "img_size", "theta"]);
+
+        let entries = layout.entries(0);
+        assert_eq!(
+            entries[1].ty,
+            wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: wgpu::TextureFormat::Rgba32Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            }
+        );
+        // a runtime-sized array needs one element, a uniform f32 four bytes
+        assert_eq!(
+            entries[2].ty,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: std::num::NonZeroU64::new(4),
+            }
+        );
+        assert_eq!(
+            entries[3].ty,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: std::num::NonZeroU64::new(4),
+            }
+        );
+        assert_eq!(entries[3].visibility, wgpu::ShaderStages::COMPUTE);
+    }
+}
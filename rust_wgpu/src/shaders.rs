@@ -0,0 +1,70 @@
+//! Typed bindings of the shaders in `wgsl/`, generated by `build.rs`.
+//!
+//! There is one module per shader, e.g. [`rotation`]. Each has the shader `SOURCE`, the
+//! `@workgroup_size` of its entry points (`WORKGROUP_SIZE` for `main`), a `#[repr(C)]`
+//! Pod struct per WGSL struct with a fixed host layout (explicit padding fields, so `new`
+//! builds the padded ones), and a `BindGroup<n>` struct per bind group with one field per
+//! binding. Renaming or retyping a binding in the shader breaks the host code at compile time.
+//!
+//! The bindings are generated from the shaders preprocessed with their default defines; the
+//! `SOURCE`s are not preprocessed, see [`preprocessor`].
+
+use crate::preprocess::Preprocessor;
+
+include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+
+/// A preprocessor that resolves the includes of `wgsl/include/`, read from `include/` of
+/// the source directory if there is one, see [`watch::source`](crate::watch::source).
+pub fn preprocessor() -> Preprocessor {
+    if let Some(dir) = crate::watch::SOURCE_DIR.get() {
+        return Preprocessor::new().include_dir(dir.join("include"));
+    }
+    INCLUDES
+        .iter()
+        .fold(Preprocessor::new(), |preprocessor, (name, source)| {
+            preprocessor.source(name, source)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convolution, histogram, preprocessor, rotation, transpose, vectoradd};
+    use crate::element::with_element;
+    use crate::preprocess::Defines;
+    use crate::reflect::ShaderLayout;
+
+    #[test]
+    pub fn test_bindings_match_reflection() {
+        let reflect = |source: &str| {
+            let source = preprocessor()
+                .run("shader", source, &Defines::new())
+                .unwrap();
+            ShaderLayout::from_wgsl("shader", &source)
+                .unwrap()
+                .entries(0)
+        };
+        assert_eq!(
+            reflect(convolution::SOURCE),
+            convolution::BindGroup0::layout_entries()
+        );
+        assert_eq!(
+            reflect(rotation::SOURCE),
+            rotation::BindGroup0::layout_entries()
+        );
+        assert_eq!(
+            reflect(histogram::SOURCE),
+            histogram::BindGroup0::layout_entries()
+        );
+        assert_eq!(
+            reflect(vectoradd::SOURCE),
+            vectoradd::BindGroup0::layout_entries()
+        );
+        assert_eq!(
+            reflect(&with_element::<f32>(transpose::SOURCE).unwrap()),
+            transpose::BindGroup0::layout_entries()
+        );
+
+        assert_eq!(rotation::WORKGROUP_SIZE, [16, 16, 1]);
+        assert_eq!(std::mem::size_of::<transpose::Dims>(), 8);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use crate::blas::Blas;
+use crate::buffer::GpuBuffer;
+use crate::element::{Element, Float};
+use crate::error::{check_len, log_error, Result};
+use crate::map::{check_arity, MapKernels};
+use crate::WgpuState;
+
+/// Chunks of a reduction queued on the device before the host waits for the oldest one.
+const CHUNKS_IN_FLIGHT: usize = 2;
+
+/// Streams host vectors that do not fit on the device through it chunk by chunk.
+///
+/// Each chunk is uploaded, processed with [`MapKernels`] or [`Blas`] and released before the
+/// next one. Element-wise results are read back per chunk; the per-chunk results of a
+/// reduction are gathered in a device buffer and reduced there, so only the final scalar is
+/// read back. The reductions run on `f32` vectors unless specified.
+pub struct Streamer<'a, T: Float = f32> {
+    state: &'a WgpuState,
+    blas: Blas<'a, T>,
+    kernels: MapKernels<'a>,
+    chunk_bytes: usize,
+}
+
+impl<'a, F: Float> Streamer<'a, F> {
+    /// Use chunks of the device's `max_buffer_size`; an error when [`Blas::new`] fails.
+    pub fn new(state: &'a WgpuState) -> Result<Self> {
+        let chunk_bytes = state.device.limits().max_buffer_size as usize;
+        Ok(Self {
+            state,
+            blas: Blas::new(state)?,
+            kernels: MapKernels::new(state),
+            chunk_bytes,
+        })
+    }
+
+    /// Override the size of one chunk of one vector in bytes.
+    pub fn with_chunk_bytes(mut self, chunk_bytes: usize) -> Self {
+        self.chunk_bytes = chunk_bytes;
+        self
+    }
+
+    /// `out[i] = expr(inputs[0][i], inputs[1][i], ...)`, see [`MapKernels::zip`]; `None` with the
+    /// error logged when the expression is not valid WGSL.
+    pub async fn zip<T: Element, O: Element>(&self, inputs: &[&[T]], expr: &str) -> Option<Vec<O>> {
+        check_arity(inputs.len()).map_err(log_error).ok()?;
+        let len = inputs[0].len();
+        for input in inputs {
+            check_len("vector length", len, input.len())
+                .map_err(log_error)
+                .ok()?;
+        }
+
+        let elem_size = std::mem::size_of::<T>().max(std::mem::size_of::<O>());
+        let mut out = Vec::with_capacity(len);
+        for range in self.chunks(len, elem_size) {
+            let buffers = self.upload(inputs, range).map_err(log_error).ok()?;
+            let buffers: Vec<&GpuBuffer<T>> = buffers.iter().collect();
+            let result: GpuBuffer<O> = self.kernels.zip(&buffers, expr).map_err(log_error).ok()?;
+            // the read-back waits for the chunk, so one chunk is on the device at a time
+            out.extend(result.read(self.state).await?);
+        }
+        Some(out)
+    }
+
+    /// Dot product of `x` and `y`.
+    pub async fn dot(&self, x: &[F], y: &[F]) -> Option<F> {
+        check_len("vector length", x.len(), y.len())
+            .map_err(log_error)
+            .ok()?;
+        self.reduce(
+            &[x, y],
+            |v| self.blas.dot_device(v[0], v[1]),
+            |results| self.blas.sum_device(results),
+        )
+        .await
+    }
+
+    /// Euclidean norm of `x`, i.e. the norm of the per-chunk norms.
+    pub async fn nrm2(&self, x: &[F]) -> Option<F> {
+        self.reduce(
+            &[x],
+            |v| self.blas.nrm2_device(v[0]),
+            |results| self.blas.nrm2_device(results),
+        )
+        .await
+    }
+
+    /// Sum of the absolute values of `x`.
+    pub async fn asum(&self, x: &[F]) -> Option<F> {
+        self.reduce(
+            &[x],
+            |v| self.blas.asum_device(v[0]),
+            |results| self.blas.sum_device(results),
+        )
+        .await
+    }
+
+    /// Index of the first element with the largest absolute value, `None` for an empty vector.
+    pub async fn iamax(&self, x: &[F]) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        for range in self.chunks(x.len(), std::mem::size_of::<F>()) {
+            let start = range.start;
+            let chunk = &self.upload(&[x], range).map_err(log_error).ok()?[0];
+            let idx = start + self.blas.iamax(chunk).await? as usize;
+            let value = x[idx].to_f64().abs();
+            if best.is_none_or(|(_, best_value)| value > best_value) {
+                best = Some((idx, value));
+            }
+        }
+        best.map(|(idx, _)| idx)
+    }
+
+    async fn reduce(
+        &self,
+        vectors: &[&[F]],
+        chunk_op: impl Fn(&[&GpuBuffer<F>]) -> Result<GpuBuffer<F>>,
+        final_op: impl Fn(&GpuBuffer<F>) -> Result<GpuBuffer<F>>,
+    ) -> Option<F> {
+        let device = &self.state.device;
+        let chunks = self.chunks(vectors[0].len(), std::mem::size_of::<F>());
+        let results = GpuBuffer::<F>::zeros(self.state, chunks.len())
+            .map_err(log_error)
+            .ok()?;
+        let mut submissions = VecDeque::new();
+
+        for (i, range) in chunks.into_iter().enumerate() {
+            let buffers = self.upload(vectors, range).map_err(log_error).ok()?;
+            let buffers: Vec<&GpuBuffer<F>> = buffers.iter().collect();
+            let result = chunk_op(&buffers).map_err(log_error).ok()?;
+
+            // gather the chunk result on the device
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            encoder.copy_buffer_to_buffer(
+                result.buffer(),
+                0,
+                results.buffer(),
+                (i * std::mem::size_of::<F>()) as wgpu::BufferAddress,
+                std::mem::size_of::<F>() as wgpu::BufferAddress,
+            );
+            submissions.push_back(self.state.queue.submit(Some(encoder.finish())));
+
+            // the uploads are only freed once their chunk ran, so wait for the older chunks
+            // instead of queueing the whole vector
+            if submissions.len() > CHUNKS_IN_FLIGHT {
+                let index = submissions.pop_front().unwrap();
+                device.poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+            }
+        }
+
+        let result = final_op(&results).map_err(log_error).ok()?;
+        result.read(self.state).await.map(|v| v[0])
+    }
+
+    fn upload<T: Element>(
+        &self,
+        vectors: &[&[T]],
+        range: Range<usize>,
+    ) -> Result<Vec<GpuBuffer<T>>> {
+        vectors
+            .iter()
+            .map(|v| GpuBuffer::from_slice(self.state, &v[range.clone()]))
+            .collect()
+    }
+
+    fn chunks(&self, len: usize, elem_size: usize) -> Vec<Range<usize>> {
+        let chunk = (self.chunk_bytes / elem_size).max(1);
+        (0..len)
+            .step_by(chunk)
+            .map(|start| start..(start + chunk).min(len))
+            .collect()
+    }
+}
@@ -0,0 +1,273 @@
+use std::time::Instant;
+
+use wgpu::util::DeviceExt;
+
+use crate::element::{with_element, Element};
+use crate::error::{check_len, log_error, Error, Result};
+use crate::shaders;
+use crate::shaders::transpose::{BindGroup0, Dims, WORKGROUP_SIZE};
+use crate::{watch, WgpuState};
+
+/// Tile width and height of the transpose shader.
+pub const TILE_DIM: u32 = WORKGROUP_SIZE[0];
+
+/// Out-of-place transpose of a row-major `rows x cols` matrix.
+///
+/// The shader stages each 16x16 tile in workgroup memory (padded by one column), so both
+/// the global reads and the global writes are coalesced.
+pub struct Transpose {
+    bind_group_layout: wgpu::BindGroupLayout,
+    transpose_pipeline: wgpu::ComputePipeline,
+    copy_pipeline: wgpu::ComputePipeline,
+}
+
+impl Transpose {
+    /// Build the transpose and copy pipelines for elements of type `T`.
+    ///
+    /// A shader that fails to compile, e.g. a broken hot-reloaded `transpose.wgsl`, is an
+    /// error rather than a panic.
+    pub fn new<T: Element>(state: &WgpuState) -> Result<Self> {
+        if !state.supports::<T>() {
+            return Err(Error::Argument(format!(
+                "the device does not support {} in shaders",
+                T::WGSL_TYPE
+            )));
+        }
+        let device = &state.device;
+        // the copy shader declares the same bindings
+        let bind_group_layout = BindGroup0::create_layout(device);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, source: &str| {
+            let shader = state.create_shader_module(label, source)?;
+            state.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "main",
+            })
+        };
+
+        let transpose_pipeline = create_pipeline(
+            "Transpose Shader",
+            &with_element::<T>(&watch::source("transpose.wgsl", shaders::transpose::SOURCE))?,
+        )?;
+        let copy_pipeline = create_pipeline(
+            "Copy Shader",
+            &with_element::<T>(&watch::source("copy.wgsl", shaders::copy::SOURCE))?,
+        )?;
+
+        Ok(Self {
+            bind_group_layout,
+            transpose_pipeline,
+            copy_pipeline,
+        })
+    }
+
+    /// Record the transpose of `input` (rows x cols) into `output` (cols x rows).
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::Buffer,
+        output: &wgpu::Buffer,
+        rows: u32,
+        cols: u32,
+    ) {
+        self.encode_pass(
+            device,
+            encoder,
+            &self.transpose_pipeline,
+            input,
+            output,
+            rows,
+            cols,
+        );
+    }
+
+    /// Record a plain copy of `input` into `output` with the same dispatch shape.
+    pub fn encode_copy(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::Buffer,
+        output: &wgpu::Buffer,
+        rows: u32,
+        cols: u32,
+    ) {
+        self.encode_pass(
+            device,
+            encoder,
+            &self.copy_pipeline,
+            input,
+            output,
+            rows,
+            cols,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn encode_pass(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::ComputePipeline,
+        input: &wgpu::Buffer,
+        output: &wgpu::Buffer,
+        rows: u32,
+        cols: u32,
+    ) {
+        let dims_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::bytes_of(&Dims { rows, cols }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = BindGroup0 {
+            input: input.as_entire_buffer_binding(),
+            output: output.as_entire_buffer_binding(),
+            dims: dims_buffer.as_entire_buffer_binding(),
+        }
+        .create(device, &self.bind_group_layout);
+
+        let mut compute_pass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(cols.div_ceil(TILE_DIM), rows.div_ceil(TILE_DIM), 1);
+    }
+}
+
+/// Transpose a row-major `rows x cols` matrix on the device.
+pub async fn transpose<T: Element>(
+    state: &WgpuState,
+    input: &[T],
+    rows: u32,
+    cols: u32,
+) -> Option<Vec<T>> {
+    check_len("matrix size", rows as usize * cols as usize, input.len())
+        .map_err(log_error)
+        .ok()?;
+    let transpose = Transpose::new::<T>(state).map_err(log_error).ok()?;
+    let device = &state.device;
+    let (input_buffer, output_buffer) = create_buffers(device, input);
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    transpose.encode(
+        device,
+        &mut encoder,
+        &input_buffer,
+        &output_buffer,
+        rows,
+        cols,
+    );
+    state.queue.submit(Some(encoder.finish()));
+
+    state
+        .read_buffer(&output_buffer, output_buffer.size())
+        .await
+}
+
+/// Effective bandwidth of the transpose compared with a plain copy kernel.
+#[derive(Debug, Clone, Copy)]
+pub struct BandwidthReport {
+    /// Bytes read plus bytes written by one launch.
+    pub bytes: u64,
+    /// Average time of one copy launch in seconds.
+    pub copy_secs: f64,
+    /// Average time of one transpose launch in seconds.
+    pub transpose_secs: f64,
+}
+
+impl BandwidthReport {
+    pub fn copy_gbps(&self) -> f64 {
+        self.bytes as f64 / self.copy_secs / 1e9
+    }
+
+    pub fn transpose_gbps(&self) -> f64 {
+        self.bytes as f64 / self.transpose_secs / 1e9
+    }
+}
+
+/// Time `iterations` launches of the copy and transpose kernels on a `rows x cols` matrix.
+pub async fn measure_bandwidth<T: Element>(
+    state: &WgpuState,
+    rows: u32,
+    cols: u32,
+    iterations: u32,
+) -> Result<BandwidthReport> {
+    let transpose = Transpose::new::<T>(state)?;
+    let device = &state.device;
+    let input = vec![T::zeroed(); rows as usize * cols as usize];
+    let (input_buffer, output_buffer) = create_buffers(device, &input);
+
+    let launch = |copy: bool| {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        if copy {
+            transpose.encode_copy(
+                device,
+                &mut encoder,
+                &input_buffer,
+                &output_buffer,
+                rows,
+                cols,
+            );
+        } else {
+            transpose.encode(
+                device,
+                &mut encoder,
+                &input_buffer,
+                &output_buffer,
+                rows,
+                cols,
+            );
+        }
+        state.queue.submit(Some(encoder.finish()));
+    };
+
+    let time = |copy: bool| {
+        // warm up once before timing
+        launch(copy);
+        device.poll(wgpu::Maintain::Wait);
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            launch(copy);
+        }
+        device.poll(wgpu::Maintain::Wait);
+        start.elapsed().as_secs_f64() / iterations.max(1) as f64
+    };
+
+    let copy_secs = time(true);
+    let transpose_secs = time(false);
+
+    Ok(BandwidthReport {
+        bytes: 2 * input_buffer.size(),
+        copy_secs,
+        transpose_secs,
+    })
+}
+
+fn create_buffers<T: Element>(device: &wgpu::Device, input: &[T]) -> (wgpu::Buffer, wgpu::Buffer) {
+    let input_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Transpose Input Buffer"),
+        contents: bytemuck::cast_slice(input),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+
+    let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Transpose Output Buffer"),
+        size: std::mem::size_of_val(input) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+
+    (input_buffer, output_buffer)
+}
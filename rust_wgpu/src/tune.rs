@@ -0,0 +1,98 @@
+//! Workgroup size auto-tuning.
+//!
+//! The tuner times candidate workgroup sizes of a shader on the current adapter and caches
+//! the fastest one on disk, keyed by adapter name, shader and input shape. The candidates
+//! are shader variants built with other `WORKGROUP_X`/`WORKGROUP_Y` defines.
+
+use std::io;
+use std::time::Duration;
+
+pub use lab_common::tune::{cache_key, CACHE_ENV};
+
+/// The smallest workgroup worth trying.
+const MIN_INVOCATIONS: u32 = 16;
+
+/// Tuned workgroup sizes, shared with the other backend.
+pub type TuneCache = lab_common::tune::TuneCache<u32>;
+
+/// Power-of-two 2D workgroup sizes within the device limits.
+pub fn workgroup_candidates(limits: &wgpu::Limits) -> Vec<[u32; 2]> {
+    let powers = |max: u32| (0..).map(|i| 1u32 << i).take_while(move |&n| n <= max);
+    let mut candidates = Vec::new();
+    for x in powers(limits.max_compute_workgroup_size_x) {
+        for y in powers(limits.max_compute_workgroup_size_y) {
+            let invocations = x * y;
+            if (MIN_INVOCATIONS..=limits.max_compute_invocations_per_workgroup)
+                .contains(&invocations)
+            {
+                candidates.push([x, y]);
+            }
+        }
+    }
+    candidates
+}
+
+/// Picks workgroup sizes from the cache, or by timing the candidates.
+pub struct Tuner {
+    cache: TuneCache,
+    iterations: u32,
+}
+
+impl Tuner {
+    pub fn new(cache: TuneCache) -> Self {
+        Self {
+            cache,
+            iterations: 10,
+        }
+    }
+
+    /// Dispatches timed per candidate.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// The cached size for `key`, else the candidate with the smallest `time(candidate,
+    /// iterations)`, which is then cached. `None` when there are no candidates.
+    pub fn tune(
+        &mut self,
+        key: String,
+        candidates: &[[u32; 2]],
+        mut time: impl FnMut([u32; 2], u32) -> Duration,
+    ) -> io::Result<Option<[u32; 2]>> {
+        if let Some(size) = self.cache.get(&key) {
+            return Ok(Some(size));
+        }
+
+        let mut best: Option<([u32; 2], Duration)> = None;
+        for &candidate in candidates {
+            let elapsed = time(candidate, self.iterations);
+            log::debug!("{key}: {candidate:?} took {elapsed:?}");
+            if best.is_none_or(|(_, fastest)| elapsed < fastest) {
+                best = Some((candidate, elapsed));
+            }
+        }
+
+        let Some((size, elapsed)) = best else {
+            return Ok(None);
+        };
+        log::info!("{key}: tuned to {size:?} ({elapsed:?})");
+        self.cache.insert(key, size);
+        self.cache.save()?;
+        Ok(Some(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::workgroup_candidates;
+
+    #[test]
+    pub fn test_tune_helpers() {
+        let candidates = workgroup_candidates(&wgpu::Limits::default());
+        assert!(candidates.contains(&[16, 16]));
+        assert!(candidates.contains(&[256, 1]));
+        assert!(!candidates.contains(&[32, 16]));
+        assert!(!candidates.contains(&[2, 2]));
+    }
+}
@@ -0,0 +1,60 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::error::Result;
+use crate::preprocess::{Defines, Preprocessor};
+use crate::WgpuState;
+
+/// A shader compiled with one set of defines.
+pub struct Variant {
+    /// The preprocessed source.
+    pub source: String,
+    pub module: wgpu::ShaderModule,
+}
+
+/// Compiled variants of preprocessed shaders, cached by shader name and defines.
+///
+/// Like the builds of an OpenCL program with different `-D` options, e.g. one variant per
+/// filter size or workgroup size.
+pub struct ShaderVariants<'a> {
+    state: &'a WgpuState,
+    preprocessor: Preprocessor,
+    cache: RefCell<HashMap<(String, Defines), Rc<Variant>>>,
+}
+
+impl<'a> ShaderVariants<'a> {
+    pub fn new(state: &'a WgpuState, preprocessor: Preprocessor) -> Self {
+        Self {
+            state,
+            preprocessor,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The variant of `source` (named `name`) with `defines`, compiled on first use.
+    pub fn get(&self, name: &str, source: &str, defines: &Defines) -> Result<Rc<Variant>> {
+        let key = (name.to_string(), defines.clone());
+        if let Some(variant) = self.cache.borrow().get(&key) {
+            return Ok(variant.clone());
+        }
+
+        let (source, lines) = self.preprocessor.run_mapped(name, source, defines)?;
+        let label = match defines.to_options() {
+            options if options.is_empty() => name.to_string(),
+            options => format!("{name} {options}"),
+        };
+        let module = self
+            .state
+            .create_shader_module(&label, &source)
+            .map_err(|err| err.locate(&lines))?;
+        let variant = Rc::new(Variant { source, module });
+        self.cache.borrow_mut().insert(key, variant.clone());
+        Ok(variant)
+    }
+
+    /// Number of compiled variants in the cache.
+    pub fn cached(&self) -> usize {
+        self.cache.borrow().len()
+    }
+}
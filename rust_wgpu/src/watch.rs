@@ -0,0 +1,49 @@
+//! Hot reload of the shaders during development.
+//!
+//! The shaders are embedded at compile time. With a source directory set, by
+//! [`SOURCE_DIR`] or the `WGPU_SHADER_DIR` environment variable, [`source`] reads them from
+//! that directory instead, so a shader created again picks up the edits. `SOURCE_DIR.run`
+//! sets the directory and reruns a job each time a file in it changes, printing the compile
+//! errors instead of exiting:
+//!
+//! ```no_run
+//! # let state = pollster::block_on(rust_wgpu::WgpuState::init()).unwrap();
+//! # let image = vec![0.0; 64 * 64 * 4];
+//! rust_wgpu::watch::SOURCE_DIR.run("wgsl", || {
+//!     let op = rust_wgpu::ops::ImageShader::rotation(&state, 64, 64, 0.5)?;
+//!     let out = pollster::block_on(op.run(&image));
+//!     println!("{:?}", out.map(|out| out.iter().sum::<f32>()));
+//!     Ok::<_, rust_wgpu::error::Error>(())
+//! });
+//! ```
+//!
+//! The typed bindings of `shaders` stay the ones of the embedded shaders; changing a binding
+//! needs a rebuild, the reloaded shader fails validation until then.
+
+use std::borrow::Cow;
+
+pub use lab_common::watch::{SourceDir, Watcher};
+
+/// Environment variable of the source directory.
+pub const SOURCE_DIR_ENV: &str = "WGPU_SHADER_DIR";
+
+/// The directory the shaders are read from, if any.
+pub static SOURCE_DIR: SourceDir = SourceDir::new(SOURCE_DIR_ENV);
+
+/// The source of the shader file `name`, e.g. `rotation.wgsl`, from the source directory if
+/// there is one, else `embedded`.
+///
+/// A file that can not be read falls back to `embedded` with a warning.
+pub fn source(name: &str, embedded: &'static str) -> Cow<'static, str> {
+    let Some(dir) = SOURCE_DIR.get() else {
+        return Cow::Borrowed(embedded);
+    };
+    let path = dir.join(name);
+    match std::fs::read_to_string(&path) {
+        Ok(source) => Cow::Owned(source),
+        Err(err) => {
+            log::warn!("using the embedded {name} - {}: {err}", path.display());
+            Cow::Borrowed(embedded)
+        }
+    }
+}
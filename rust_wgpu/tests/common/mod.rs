@@ -0,0 +1,38 @@
+//! Device setup shared by the integration tests.
+
+use rust_wgpu::WgpuState;
+
+/// The device state (a GPU, else a software adapter such as lavapipe or llvmpipe), `None` to
+/// skip the test when there is no adapter. Logs the adapter so CI output shows what was tested.
+pub fn state() -> Option<WgpuState> {
+    match pollster::block_on(WgpuState::try_init()) {
+        Some(state) => {
+            eprintln!("using wgpu adapter: {}", state.describe());
+            Some(state)
+        }
+        None => {
+            eprintln!("skipped: no wgpu adapter available");
+            None
+        }
+    }
+}
+
+/// Like [`state`], with storage bindings of 16 KiB, so that vectors of more than 4096 `f32`s
+/// are bound window by window.
+#[allow(dead_code)]
+pub fn windowed_state() -> Option<WgpuState> {
+    let limits = wgpu::Limits {
+        max_storage_buffer_binding_size: 16 << 10,
+        ..wgpu::Limits::default()
+    };
+    match pollster::block_on(WgpuState::try_init_with_limits(limits)) {
+        Some(state) => {
+            eprintln!("using wgpu adapter: {}", state.describe());
+            Some(state)
+        }
+        None => {
+            eprintln!("skipped: no wgpu adapter available");
+            None
+        }
+    }
+}
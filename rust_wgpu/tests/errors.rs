@@ -0,0 +1,191 @@
+//! Invalid shaders, pipelines and inputs are errors, not panics.
+
+mod common;
+
+use rust_wgpu::buffer::GpuBuffer;
+use rust_wgpu::error::Error;
+use rust_wgpu::map::MapKernels;
+use rust_wgpu::ops::ImagePass;
+use rust_wgpu::pipeline::ImagePipeline;
+use rust_wgpu::preprocess::{Defines, Preprocessor};
+use rust_wgpu::reflect::ShaderLayout;
+use rust_wgpu::shaders;
+use rust_wgpu::uniform_buffer_entry;
+use rust_wgpu::variants::ShaderVariants;
+
+#[test]
+fn invalid_expression_is_a_shader_error() {
+    let Some(state) = common::state() else { return };
+    let kernels = MapKernels::new(&state);
+    let x = GpuBuffer::from_slice(&state, &[1.0f32, 2.0, 3.0]).unwrap();
+
+    let result = kernels.map::<f32, f32>(&x, "a * 2.0 +");
+    let Err(Error::Shader(err)) = result else {
+        panic!("expected a shader error");
+    };
+    assert!(err.line.is_some() && err.column.is_some(), "{err}");
+    assert_eq!(kernels.cached(), 0);
+
+    // the state is still usable
+    let y: GpuBuffer<f32> = kernels.map(&x, "a * 2.0").unwrap();
+    let y = pollster::block_on(y.read(&state)).unwrap();
+    assert_eq!(y, [2.0, 4.0, 6.0]);
+}
+
+#[test]
+fn mismatched_layout_is_a_validation_error() {
+    let Some(state) = common::state() else { return };
+    let shader = state
+        .create_shader_module(
+            "double.wgsl",
+            "@group(0) @binding(0) var<storage, read_write> v: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    v[gid.x] = v[gid.x] * 2.0;
+}
+",
+        )
+        .unwrap();
+
+    // the layout has a uniform buffer where the shader has a storage buffer
+    let bind_group_layout =
+        state
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[uniform_buffer_entry(0)],
+            });
+    let layout = state
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+    let result = state.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: None,
+        layout: Some(&layout),
+        module: &shader,
+        entry_point: "main",
+    });
+    assert!(matches!(result, Err(Error::Validation(_))));
+    assert!(state.check_device().is_ok());
+}
+
+#[test]
+fn mismatched_resources_name_the_binding() {
+    let Some(state) = common::state() else { return };
+    let source = shaders::preprocessor()
+        .run("rotation.wgsl", shaders::rotation::SOURCE, &Defines::new())
+        .unwrap();
+    let layout = ShaderLayout::from_wgsl("rotation.wgsl", &source).unwrap();
+    let bind_group_layout = layout.bind_group_layout(&state.device, 0);
+    let img_size = GpuBuffer::from_slice(&state, &[4u32, 4]).unwrap();
+    // storage usage, where the shader declares `theta` as a uniform
+    let theta = GpuBuffer::from_slice(&state, &[0.5f32]).unwrap();
+
+    let err = layout
+        .bind_group(
+            &state,
+            &bind_group_layout,
+            0,
+            &[
+                ("img_size", img_size.binding(0..2)),
+                ("theta", theta.binding(0..1)),
+            ],
+        )
+        .unwrap_err();
+    let Error::Binding { name, message } = err else {
+        panic!("expected a binding error, got {err}");
+    };
+    assert_eq!(name, "theta");
+    assert!(message.contains("@binding(3)"), "{message}");
+
+    let err = layout
+        .bind_group(
+            &state,
+            &bind_group_layout,
+            0,
+            &[("angle", theta.binding(0..1))],
+        )
+        .unwrap_err();
+    assert!(matches!(err, Error::Binding { name, .. } if name == "angle"));
+}
+
+#[test]
+fn undefined_define_is_a_shader_error() {
+    let Some(state) = common::state() else { return };
+    let variants = ShaderVariants::new(&state, shaders::preprocessor());
+    let source = shaders::rotation::SOURCE;
+
+    let err = variants
+        .get("rotation.wgsl", "{{ANGLE}}", &Defines::new())
+        .map(|_| ())
+        .unwrap_err();
+    let Error::Shader(err) = err else {
+        panic!("expected a shader error, got {err}");
+    };
+    assert_eq!((err.label.as_str(), err.line), ("rotation.wgsl", Some(1)));
+
+    let defines = Defines::from_options("-D WORKGROUP_X=8 -D WORKGROUP_Y=8");
+    variants.get("rotation.wgsl", source, &defines).unwrap();
+    variants.get("rotation.wgsl", source, &defines).unwrap();
+    variants
+        .get("rotation.wgsl", source, &Defines::new())
+        .unwrap();
+    assert_eq!(variants.cached(), 2);
+}
+
+#[test]
+fn shader_error_is_located_after_an_include() {
+    let Some(state) = common::state() else { return };
+    let preprocessor =
+        Preprocessor::new().source("consts.wgsl", "const A: f32 = 1.0;\nconst B: f32 = 2.0;\n");
+    let variants = ShaderVariants::new(&state, preprocessor);
+    let source = "#include \"consts.wgsl\"\nconst C: f32 = A + B;\nconst D: f32 = C +;\n";
+
+    let err = variants
+        .get("main.wgsl", source, &Defines::new())
+        .map(|_| ())
+        .unwrap_err();
+    let Error::Shader(err) = err else {
+        panic!("expected a shader error, got {err}");
+    };
+    // the 4th line of the preprocessed source
+    assert_eq!(
+        (err.label.as_str(), err.line),
+        ("main.wgsl", Some(3)),
+        "{err}"
+    );
+}
+
+#[test]
+fn mismatched_inputs_are_argument_errors() {
+    let Some(state) = common::state() else { return };
+    let x = GpuBuffer::from_slice(&state, &[1.0f32, 2.0, 3.0]).unwrap();
+    let err = x.write(&state.queue, &[1.0, 2.0]).unwrap_err();
+    assert!(matches!(err, Error::Argument(_)), "{err}");
+
+    let mut pipeline = ImagePipeline::<f32>::new(&state, 8, 4);
+    let pass = ImagePass::rotation(&state, 4, 8, 0.5).unwrap();
+    let err = pipeline.pass(pass).map(|_| ()).unwrap_err();
+    assert!(matches!(err, Error::Argument(_)), "{err}");
+
+    pipeline.rotation(0.5).unwrap();
+    let err = pollster::block_on(pipeline.run(&[0.0; 8 * 4])).unwrap_err();
+    assert!(matches!(err, Error::Argument(_)), "{err}");
+    // the pipeline still runs on an image of its size
+    pollster::block_on(pipeline.run(&[0.0; 8 * 4 * 4])).unwrap();
+}
+
+#[test]
+fn oversized_buffer_is_an_argument_error() {
+    let Some(state) = common::state() else { return };
+    let max = state.device.limits().max_buffer_size;
+    let err = GpuBuffer::<f32>::zeros(&state, (max / 4 + 1) as usize)
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(err, Error::Argument(_)), "{err}");
+    assert!(state.check_device().is_ok());
+}
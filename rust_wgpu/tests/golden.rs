@@ -0,0 +1,361 @@
+//! Runs the shaders and compares them with the CPU references.
+
+mod common;
+
+use rust_wgpu::blas::Blas;
+use rust_wgpu::buffer::GpuBuffer;
+use rust_wgpu::map::MapKernels;
+use rust_wgpu::reference::{self, ErrorStats};
+use rust_wgpu::stream::Streamer;
+use rust_wgpu::transpose::transpose;
+use rust_wgpu::{generate_gaussian_kernel, ops};
+
+const COLS: u32 = 45;
+const ROWS: u32 = 31;
+
+/// A deterministic RGBA test image with values in [0, 1].
+fn test_image() -> Vec<f32> {
+    (0..COLS * ROWS * ops::CHANNELS as u32)
+        .map(|i| ((i * 7919) % 256) as f32 / 255.0)
+        .collect()
+}
+
+fn assert_within(op: &str, stats: ErrorStats, max_abs: f32, mean_abs: f32) {
+    assert!(
+        stats.within(max_abs, mean_abs),
+        "{op}: {stats:?} exceeds max_abs {max_abs}, mean_abs {mean_abs}"
+    );
+}
+
+#[test]
+fn vector_add_matches_reference() {
+    let Some(state) = common::state() else { return };
+    let a: Vec<f32> = (0..1000).map(|v| v as f32 * 0.5).collect();
+    let b: Vec<f32> = (0..1000).map(|v| (v as f32).sin()).collect();
+
+    let out = pollster::block_on(ops::vector_add(&state, &a, &b)).unwrap();
+    let stats = ErrorStats::compare(&out, &reference::vector_add(&a, &b));
+    assert_within("vector_add", stats, 0.0, 0.0);
+}
+
+#[test]
+fn vector_add_beyond_one_dispatch() {
+    let Some(state) = common::state() else { return };
+    // more elements than 65535 workgroups of 256 invocations cover
+    let len = 65535 * 256 + 1000;
+    let a: Vec<f32> = (0..len).map(|v| (v % 1024) as f32).collect();
+    let b = vec![1.0; len];
+
+    let out = pollster::block_on(ops::vector_add(&state, &a, &b)).unwrap();
+    assert_eq!(out, reference::vector_add(&a, &b));
+}
+
+#[test]
+fn transpose_matches_reference() {
+    let Some(state) = common::state() else { return };
+    // square and not, with sides that are not a multiple of the 16x16 tile
+    for (rows, cols) in [(16, 16), (45, 31), (16, 48), (1, 33), (100, 7)] {
+        let input: Vec<f32> = (0..rows * cols).map(|v| v as f32).collect();
+        let out = pollster::block_on(transpose(&state, &input, rows, cols)).unwrap();
+        let expected = reference::transpose(&input, rows as usize, cols as usize);
+        assert_eq!(out, expected, "{rows}x{cols}");
+    }
+
+    let input: Vec<i32> = (0..45 * 31).map(|v| v - 700).collect();
+    let out = pollster::block_on(transpose(&state, &input, 45, 31)).unwrap();
+    assert_eq!(out, reference::transpose(&input, 45, 31));
+    // f16 has no transpose shader
+    let input = vec![half::f16::ZERO; 4];
+    assert!(pollster::block_on(transpose(&state, &input, 2, 2)).is_none());
+}
+
+/// `actual` must be within a relative `tolerance` of `expected`.
+fn assert_close(op: &str, actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+        (actual - expected).abs() <= tolerance * expected.abs().max(1.0),
+        "{op}: {actual} differs from {expected}"
+    );
+}
+
+#[test]
+fn blas_matches_reference() {
+    let Some(state) = common::state() else { return };
+    let blas = Blas::new(&state).unwrap();
+    let upload = |v: &[f32]| GpuBuffer::from_slice(&state, v).unwrap();
+
+    // more elements than one workgroup per partial, and none
+    for len in [0, 1, 1000, 100_003] {
+        let x: Vec<f32> = (0..len)
+            .map(|v| ((v * 37) % 101) as f32 * 0.01 - 0.5)
+            .collect();
+        let y: Vec<f32> = (0..len).map(|v| (v as f32 * 0.1).sin()).collect();
+        let (gx, gy) = (upload(&x), upload(&y));
+
+        let dot = pollster::block_on(blas.dot(&gx, &gy)).unwrap();
+        assert_close("dot", dot, reference::dot(&x, &y), 1e-4);
+        let nrm2 = pollster::block_on(blas.nrm2(&gx)).unwrap();
+        assert_close("nrm2", nrm2, reference::nrm2(&x), 1e-4);
+        let asum = pollster::block_on(blas.asum(&gx)).unwrap();
+        assert_close("asum", asum, reference::asum(&x), 1e-4);
+        let iamax = pollster::block_on(blas.iamax(&gx));
+        assert_eq!(
+            iamax.map(|i| i as usize),
+            reference::iamax(&x),
+            "iamax of {len}"
+        );
+    }
+
+    // ties go to the first index, also across the partials of different workgroups
+    let mut x = vec![0.5f32; 100_000];
+    x[70_000] = -2.0;
+    x[99_999] = 2.0;
+    x[40_000] = 2.0;
+    let iamax = pollster::block_on(blas.iamax(&upload(&x)));
+    assert_eq!(iamax, Some(40_000));
+    assert_eq!(reference::iamax(&x), Some(40_000));
+}
+
+#[test]
+fn map_and_zip_match_reference() {
+    let Some(state) = common::state() else { return };
+    let kernels = MapKernels::new(&state);
+    let read = |buffer: GpuBuffer<f32>| pollster::block_on(buffer.read(&state)).unwrap();
+    let len = 10_001;
+    let a: Vec<f32> = (0..len).map(|v| v as f32 * 0.25 - 100.0).collect();
+    let b: Vec<f32> = (0..len).map(|v| (v % 17) as f32).collect();
+    let c: Vec<f32> = (0..len).map(|v| (v % 5) as f32 * 0.5).collect();
+    let (ga, gb, gc) = (
+        GpuBuffer::from_slice(&state, &a).unwrap(),
+        GpuBuffer::from_slice(&state, &b).unwrap(),
+        GpuBuffer::from_slice(&state, &c).unwrap(),
+    );
+
+    let out = read(kernels.map(&ga, "a * 2.0 + f32(i)").unwrap());
+    let expected: Vec<f32> = a
+        .iter()
+        .enumerate()
+        .map(|(i, a)| a * 2.0 + i as f32)
+        .collect();
+    assert_eq!(out, expected);
+
+    let out = read(kernels.map2(&ga, &gb, "max(a, b)").unwrap());
+    let expected: Vec<f32> = a.iter().zip(&b).map(|(a, b)| a.max(*b)).collect();
+    assert_eq!(out, expected);
+
+    let out = read(kernels.zip(&[&ga, &gb, &gc], "a * b + c").unwrap());
+    let expected: Vec<f32> = (0..len).map(|i| a[i] * b[i] + c[i]).collect();
+    assert_within("zip", ErrorStats::compare(&out, &expected), 1e-4, 1e-6);
+
+    // conversions between element types, and packed f16 pairs with an odd length
+    let x: Vec<i32> = (-500..501).collect();
+    let out: GpuBuffer<u32> = kernels
+        .map(&GpuBuffer::from_slice(&state, &x).unwrap(), "a * a")
+        .unwrap();
+    let expected: Vec<u32> = x.iter().map(|x| (x * x) as u32).collect();
+    assert_eq!(pollster::block_on(out.read(&state)).unwrap(), expected);
+
+    let x: Vec<half::f16> = (0..1001)
+        .map(|v| half::f16::from_f32(v as f32 / 8.0))
+        .collect();
+    let out: GpuBuffer<half::f16> = kernels
+        .map(&GpuBuffer::from_slice(&state, &x).unwrap(), "a * 0.5")
+        .unwrap();
+    let expected: Vec<half::f16> = x.iter().map(|x| *x * half::f16::from_f32(0.5)).collect();
+    assert_eq!(pollster::block_on(out.read(&state)).unwrap(), expected);
+
+    // an empty arity and inputs of different lengths
+    let short = GpuBuffer::from_slice(&state, &c[1..]).unwrap();
+    assert!(kernels.zip::<f32, f32>(&[], "1.0").is_err());
+    assert!(kernels.zip::<f32, f32>(&[&ga, &short], "a").is_err());
+}
+
+#[test]
+fn windows_match_reference() {
+    let Some(state) = common::windowed_state() else {
+        return;
+    };
+    let blas = Blas::new(&state).unwrap();
+    let kernels = MapKernels::new(&state);
+    let upload = |v: &[f32]| GpuBuffer::from_slice(&state, v).unwrap();
+    let read = |buffer: &GpuBuffer<f32>| pollster::block_on(buffer.read(&state)).unwrap();
+
+    // three windows, the last one partial
+    let len = 10_001;
+    let x: Vec<f32> = (0..len)
+        .map(|v| ((v * 37) % 101) as f32 * 0.01 - 0.5)
+        .collect();
+    let y: Vec<f32> = (0..len).map(|v| (v % 7) as f32).collect();
+    let (gx, gy) = (upload(&x), upload(&y));
+    assert_eq!(gx.windows(&state.device.limits()).len(), 3);
+
+    let out = pollster::block_on(ops::vector_add(&state, &x, &y)).unwrap();
+    assert_eq!(out, reference::vector_add(&x, &y));
+
+    let out: GpuBuffer<f32> = kernels.map2(&gx, &gy, "a * b + f32(i)").unwrap();
+    let expected: Vec<f32> = (0..len).map(|i| x[i] * y[i] + i as f32).collect();
+    assert_within(
+        "map2",
+        ErrorStats::compare(&read(&out), &expected),
+        1e-3,
+        1e-6,
+    );
+
+    let gz = upload(&y);
+    blas.axpy(2.0, &gx, &gz).unwrap();
+    let expected: Vec<f32> = x.iter().zip(&y).map(|(x, y)| 2.0 * x + y).collect();
+    assert_within(
+        "axpy",
+        ErrorStats::compare(&read(&gz), &expected),
+        1e-6,
+        1e-7,
+    );
+
+    let dot = pollster::block_on(blas.dot(&gx, &gy)).unwrap();
+    assert_close("dot", dot, reference::dot(&x, &y), 1e-4);
+    let nrm2 = pollster::block_on(blas.nrm2(&gx)).unwrap();
+    assert_close("nrm2", nrm2, reference::nrm2(&x), 1e-4);
+    let asum = pollster::block_on(blas.asum(&gx)).unwrap();
+    assert_close("asum", asum, reference::asum(&x), 1e-4);
+
+    // a tie between the first and the last window
+    let mut x = vec![0.5f32; len];
+    x[len - 1] = 3.0;
+    x[100] = -3.0;
+    assert_eq!(pollster::block_on(blas.iamax(&upload(&x))), Some(100));
+}
+
+#[test]
+fn streaming_matches_reference() {
+    let Some(state) = common::state() else { return };
+    // chunks of 1000 f32s, more of them than the reductions keep in flight
+    let streamer = Streamer::new(&state).unwrap().with_chunk_bytes(4000);
+    let len = 10_001;
+    let x: Vec<f32> = (0..len)
+        .map(|v| ((v * 37) % 101) as f32 * 0.01 - 0.5)
+        .collect();
+    let y: Vec<f32> = (0..len).map(|v| (v % 7) as f32).collect();
+
+    let out: Vec<f32> = pollster::block_on(streamer.zip(&[&x, &y], "a - b")).unwrap();
+    let expected: Vec<f32> = x.iter().zip(&y).map(|(x, y)| x - y).collect();
+    assert_eq!(out, expected);
+
+    let dot = pollster::block_on(streamer.dot(&x, &y)).unwrap();
+    assert_close("dot", dot, reference::dot(&x, &y), 1e-4);
+    let nrm2 = pollster::block_on(streamer.nrm2(&x)).unwrap();
+    assert_close("nrm2", nrm2, reference::nrm2(&x), 1e-4);
+    let asum = pollster::block_on(streamer.asum(&x)).unwrap();
+    assert_close("asum", asum, reference::asum(&x), 1e-4);
+    assert_eq!(pollster::block_on(streamer.iamax(&x)), reference::iamax(&x));
+
+    // a tie between two chunks
+    let mut x = vec![0.5f32; len];
+    x[9_500] = 3.0;
+    x[1_500] = -3.0;
+    assert_eq!(pollster::block_on(streamer.iamax(&x)), Some(1_500));
+
+    // empty vectors, an empty arity and vectors of different lengths
+    assert_eq!(pollster::block_on(streamer.dot(&[], &[])), Some(0.0));
+    assert_eq!(pollster::block_on(streamer.iamax(&[])), None);
+    let out: Option<Vec<f32>> = pollster::block_on(streamer.zip::<f32, f32>(&[], "1.0"));
+    assert!(out.is_none());
+    let out: Option<Vec<f32>> = pollster::block_on(streamer.zip(&[&x, &y[1..]], "a"));
+    assert!(out.is_none());
+}
+
+#[test]
+fn convolution_matches_reference() {
+    let Some(state) = common::state() else { return };
+    let image = test_image();
+    let filter = generate_gaussian_kernel(2, 1.0);
+
+    let out = pollster::block_on(ops::convolution(&state, &image, COLS, ROWS, &filter)).unwrap();
+    let expected =
+        reference::convolution(&image, COLS as usize, ROWS as usize, ops::CHANNELS, &filter);
+    assert_within(
+        "convolution",
+        ErrorStats::compare(&out, &expected),
+        1e-5,
+        1e-6,
+    );
+}
+
+#[test]
+fn rotation_matches_reference() {
+    let Some(state) = common::state() else { return };
+    let image = test_image();
+    let theta = 30f32.to_radians();
+
+    let out = pollster::block_on(ops::rotation(&state, &image, COLS, ROWS, theta)).unwrap();
+    let expected = reference::rotation(&image, COLS as usize, ROWS as usize, ops::CHANNELS, theta);
+    assert_within("rotation", ErrorStats::compare(&out, &expected), 1e-4, 1e-5);
+}
+
+#[test]
+fn bicubic_rotation_matches_reference() {
+    let Some(state) = common::state() else { return };
+    let image = test_image();
+    let theta = 30f32.to_radians();
+
+    let shader = ops::ImageShader::bicubic_rotation(&state, COLS, ROWS, theta).unwrap();
+    let out = pollster::block_on(shader.run(&image)).unwrap();
+    let expected =
+        reference::bicubic_rotation(&image, COLS as usize, ROWS as usize, ops::CHANNELS, theta);
+    assert_within(
+        "bicubic rotation",
+        ErrorStats::compare(&out, &expected),
+        1e-4,
+        1e-5,
+    );
+}
+
+#[test]
+fn workgroup_size_does_not_change_the_output() {
+    let Some(state) = common::state() else { return };
+    let image = test_image();
+    let theta = 30f32.to_radians();
+
+    // 45x31 is not a multiple of the workgroup size, the overhanging invocations must not write
+    let mut shader = ops::ImageShader::rotation(&state, COLS, ROWS, theta).unwrap();
+    shader.set_workgroup_size([8, 4]).unwrap();
+    let out = pollster::block_on(shader.run(&image)).unwrap();
+    let expected = reference::rotation(&image, COLS as usize, ROWS as usize, ops::CHANNELS, theta);
+    assert_within("rotation", ErrorStats::compare(&out, &expected), 1e-4, 1e-5);
+}
+
+#[test]
+fn integer_and_half_pixels_match_reference() {
+    let Some(state) = common::state() else { return };
+    let image = test_image();
+    let filter = generate_gaussian_kernel(2, 1.0);
+    let theta = 30f32.to_radians();
+
+    // 8-bit values, rounded to the nearest integer on the device
+    let scaled: Vec<f32> = image.iter().map(|v| (v * 255.0).round()).collect();
+    let pixels: Vec<u32> = scaled.iter().map(|&v| v as u32).collect();
+    let out = pollster::block_on(ops::convolution(&state, &pixels, COLS, ROWS, &filter)).unwrap();
+    let out: Vec<f32> = out.into_iter().map(|v| v as f32).collect();
+    let expected = reference::convolution(
+        &scaled,
+        COLS as usize,
+        ROWS as usize,
+        ops::CHANNELS,
+        &filter,
+    );
+    assert_within(
+        "u32 convolution",
+        ErrorStats::compare(&out, &expected),
+        1.0,
+        0.25,
+    );
+
+    let pixels: Vec<half::f16> = image.iter().map(|&v| half::f16::from_f32(v)).collect();
+    let out = pollster::block_on(ops::rotation(&state, &pixels, COLS, ROWS, theta)).unwrap();
+    let out: Vec<f32> = out.into_iter().map(half::f16::to_f32).collect();
+    let expected = reference::rotation(&image, COLS as usize, ROWS as usize, ops::CHANNELS, theta);
+    assert_within(
+        "f16 rotation",
+        ErrorStats::compare(&out, &expected),
+        2e-3,
+        5e-4,
+    );
+}
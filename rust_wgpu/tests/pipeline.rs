@@ -0,0 +1,191 @@
+//! Chains of image passes, job files and batches give the same results as the separate ops.
+
+mod common;
+
+use rust_wgpu::batch::{self, BatchOptions};
+use rust_wgpu::job::{self, Job};
+use rust_wgpu::pipeline::{ImagePipeline, HIST_BINS};
+use rust_wgpu::{generate_gaussian_kernel, ops, reference};
+
+const COLS: u32 = 45;
+const ROWS: u32 = 31;
+
+#[test]
+fn chain_matches_separate_ops() {
+    let Some(state) = common::state() else { return };
+    let image: Vec<f32> = (0..COLS * ROWS * ops::CHANNELS as u32)
+        .map(|i| ((i * 7919) % 256) as f32 / 255.0)
+        .collect();
+    let filter = generate_gaussian_kernel(2, 1.0);
+    let theta = 30f32.to_radians();
+
+    let mut pipeline = ImagePipeline::new(&state, COLS, ROWS);
+    pipeline.convolution(&filter).unwrap();
+    let blurred = pipeline.output();
+    pipeline.rotation(theta).unwrap();
+    let rotated = pipeline.output();
+    let histogram = pipeline.histogram().unwrap();
+    let outputs = pollster::block_on(pipeline.run(&image)).unwrap();
+
+    let expected_blurred =
+        pollster::block_on(ops::convolution(&state, &image, COLS, ROWS, &filter)).unwrap();
+    let expected_rotated =
+        pollster::block_on(ops::rotation(&state, &expected_blurred, COLS, ROWS, theta)).unwrap();
+    assert_eq!(outputs.image(blurred), expected_blurred);
+    assert_eq!(outputs.image(rotated), expected_rotated);
+
+    // the luma may round into the neighboring bin on the device
+    let expected = reference::histogram(&expected_rotated, ops::CHANNELS, HIST_BINS);
+    let counts = outputs.histogram(histogram);
+    assert_eq!(counts.iter().sum::<u32>(), COLS * ROWS);
+    let differences: u32 = counts
+        .iter()
+        .zip(&expected)
+        .map(|(a, b)| a.abs_diff(*b))
+        .sum();
+    assert!(
+        differences <= COLS * ROWS / 100,
+        "{counts:?} vs {expected:?}"
+    );
+
+    // the intermediates are reused across passes and runs
+    assert_eq!(pipeline.textures(), 2);
+    let again = pollster::block_on(pipeline.run(&image)).unwrap();
+    assert_eq!(again, outputs);
+    assert_eq!(pipeline.textures(), 2);
+}
+
+#[test]
+fn integer_pipeline_counts_8_bit_values() {
+    let Some(state) = common::state() else { return };
+    let image: Vec<u32> = (0..COLS * ROWS * ops::CHANNELS as u32)
+        .map(|i| (i * 7919) % 256)
+        .collect();
+
+    let mut pipeline = ImagePipeline::<u32>::new(&state, COLS, ROWS);
+    pipeline.rotation(30f32.to_radians()).unwrap();
+    let rotated = pipeline.output();
+    let histogram = pipeline.histogram().unwrap();
+    let outputs = pollster::block_on(pipeline.run(&image)).unwrap();
+
+    let expected_rotated = pollster::block_on(ops::rotation(
+        &state,
+        &image,
+        COLS,
+        ROWS,
+        30f32.to_radians(),
+    ))
+    .unwrap();
+    assert_eq!(outputs.image(rotated), expected_rotated);
+
+    // the integer bins are the values themselves
+    let scaled: Vec<f32> = expected_rotated.iter().map(|&v| v as f32 / 256.0).collect();
+    let expected = reference::histogram(&scaled, ops::CHANNELS, HIST_BINS);
+    let differences: u32 = outputs
+        .histogram(histogram)
+        .iter()
+        .zip(&expected)
+        .map(|(a, b)| a.abs_diff(*b))
+        .sum();
+    assert!(differences <= COLS * ROWS / 100);
+}
+
+#[test]
+fn job_runs_as_a_pipeline() {
+    let Some(state) = common::state() else { return };
+    let input = std::env::temp_dir().join(format!("job-{}.png", std::process::id()));
+    let pixels: Vec<u8> = (0..COLS * ROWS * 4)
+        .map(|i| (i * 7919 % 256) as u8)
+        .collect();
+    image::save_buffer(&input, &pixels, COLS, ROWS, image::ColorType::Rgba8).unwrap();
+
+    let job = Job::from_json(&format!(
+        r#"{{"input": {input:?}, "ops": [
+            {{"op": "rotate", "degrees": 30}},
+            {{"op": "histogram", "bins": 16}}
+        ]}}"#
+    ))
+    .unwrap();
+    let output = pollster::block_on(job::run(&job, &state));
+    std::fs::remove_file(&input).unwrap();
+    let output = output.unwrap();
+
+    let image: Vec<f32> = pixels.iter().map(|&v| v as f32 / 255.0).collect();
+    let expected = pollster::block_on(ops::rotation(
+        &state,
+        &image,
+        COLS,
+        ROWS,
+        30f32.to_radians(),
+    ))
+    .unwrap();
+    assert_eq!((output.cols, output.rows), (COLS, ROWS));
+    assert_eq!(output.image, expected);
+    assert_eq!(output.histograms.len(), 1);
+    assert_eq!(output.histograms[0].len(), 16);
+    assert_eq!(output.histograms[0].iter().sum::<u32>(), COLS * ROWS);
+}
+
+#[test]
+fn batch_matches_single_jobs() {
+    let Some(state) = common::state() else { return };
+    let dir = std::env::temp_dir().join(format!("batch-{}", std::process::id()));
+    let (input_dir, output_dir) = (dir.join("in"), dir.join("out"));
+    std::fs::create_dir_all(input_dir.join("sub")).unwrap();
+    // more sizes than the batch keeps pipelines for, sub/c.png may need a dropped one again
+    let files = [
+        ("a.png", COLS, ROWS),
+        ("b.png", ROWS, COLS),
+        ("d.png", 20, 12),
+        ("e.png", 12, 20),
+        ("f.png", 33, 17),
+        ("sub/c.png", COLS, ROWS),
+    ];
+    for (seed, (file, cols, rows)) in files.iter().enumerate() {
+        let pixels: Vec<u8> = (0..cols * rows * 4)
+            .map(|i| ((i + seed as u32) * 7919 % 256) as u8)
+            .collect();
+        image::save_buffer(
+            input_dir.join(file),
+            &pixels,
+            *cols,
+            *rows,
+            image::ColorType::Rgba8,
+        )
+        .unwrap();
+    }
+    std::fs::write(input_dir.join("broken.png"), "not a png").unwrap();
+
+    let job = |input: &std::path::Path| {
+        Job::from_json(&format!(
+            r#"{{"input": {input:?}, "ops": [
+                {{"op": "gaussian_blur", "radius": 2, "sigma": 1.0}},
+                {{"op": "rotate", "degrees": 30}},
+                {{"op": "histogram", "bins": 16}}
+            ]}}"#
+        ))
+        .unwrap()
+    };
+    let options = BatchOptions {
+        threads: 2,
+        in_flight: 2,
+    };
+    let report = batch::run(&state, &job(&dir).ops, &input_dir, &output_dir, &options).unwrap();
+    assert_eq!(report.processed, files.len());
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, std::path::Path::new("broken.png"));
+
+    for (file, cols, rows) in files {
+        let output = batch::output_path(&output_dir, file.as_ref());
+        let expected = pollster::block_on(job::run(&job(&input_dir.join(file)), &state)).unwrap();
+        let saved = image::open(&output).unwrap().to_rgba8();
+        assert_eq!(saved.dimensions(), (cols, rows));
+        let expected: Vec<u8> = expected.image.iter().map(|v| (v * 255.0) as u8).collect();
+        assert_eq!(saved.into_raw(), expected, "{file}");
+
+        let histograms = std::fs::read_to_string(output.with_extension("histogram.json"));
+        let histograms: Vec<Vec<u32>> = serde_json::from_str(&histograms.unwrap()).unwrap();
+        assert_eq!(histograms[0].iter().sum::<u32>(), cols * rows);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,238 @@
+// BLAS level-1 operations on float vectors of any length.
+// The element type `T` is provided by the host (alias T = f32; or f64).
+//
+// element-wise ops use a grid-stride loop, so the dispatch size is capped by the host.
+// reductions run in two stages: `*_partial` writes one value per workgroup into `partials`,
+// then `*_final` reduces the partials with a single workgroup into `result[0]`.
+//
+// buffers larger than the binding limit are bound window by window; `offset` is the index of
+// the first element of the window within the whole buffer.
+
+struct Params {
+    alpha: T,
+    n: u32,
+    offset: u32,
+};
+
+@group(0) @binding(0) var<storage, read> a: array<T>;
+@group(0) @binding(1) var<storage, read> b: array<T>;
+@group(0) @binding(2) var<storage, read_write> y: array<T>;
+@group(0) @binding(3) var<uniform> params: Params;
+@group(0) @binding(4) var<storage, read_write> partials: array<T>;
+@group(0) @binding(5) var<storage, read_write> partial_idx: array<u32>;
+@group(0) @binding(6) var<storage, read_write> result: array<T>;
+@group(0) @binding(7) var<storage, read_write> result_idx: array<u32>;
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+var<workgroup> scratch: array<T, 256>;
+var<workgroup> scratch_idx: array<u32, 256>;
+
+// y = alpha * a + y
+@compute @workgroup_size(256)
+fn axpy(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    for (var i = gid.x; i < params.n; i += groups.x * WORKGROUP_SIZE) {
+        y[i] = params.alpha * a[i] + y[i];
+    }
+}
+
+// y = alpha * y
+@compute @workgroup_size(256)
+fn scal(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    for (var i = gid.x; i < params.n; i += groups.x * WORKGROUP_SIZE) {
+        y[i] = params.alpha * y[i];
+    }
+}
+
+// y = a * b (element-wise)
+@compute @workgroup_size(256)
+fn mul(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    for (var i = gid.x; i < params.n; i += groups.x * WORKGROUP_SIZE) {
+        y[i] = a[i] * b[i];
+    }
+}
+
+// y = a
+@compute @workgroup_size(256)
+fn copy(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    for (var i = gid.x; i < params.n; i += groups.x * WORKGROUP_SIZE) {
+        y[i] = a[i];
+    }
+}
+
+// tree reduction of `scratch` into scratch[0].
+fn reduce_sum(lid: u32) {
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        workgroupBarrier();
+        if lid < stride {
+            scratch[lid] += scratch[lid + stride];
+        }
+    }
+}
+
+// sum(a * b)
+@compute @workgroup_size(256)
+fn dot_partial(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_id) lid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>
+) {
+    var sum = T(0.0);
+    for (var i = gid.x; i < params.n; i += groups.x * WORKGROUP_SIZE) {
+        sum += a[i] * b[i];
+    }
+    scratch[lid.x] = sum;
+    reduce_sum(lid.x);
+    if lid.x == 0u {
+        partials[wid.x] = scratch[0];
+    }
+}
+
+// sum(a)
+@compute @workgroup_size(256)
+fn sum_partial(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_id) lid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>
+) {
+    var sum = T(0.0);
+    for (var i = gid.x; i < params.n; i += groups.x * WORKGROUP_SIZE) {
+        sum += a[i];
+    }
+    scratch[lid.x] = sum;
+    reduce_sum(lid.x);
+    if lid.x == 0u {
+        partials[wid.x] = scratch[0];
+    }
+}
+
+// sum(|a|)
+@compute @workgroup_size(256)
+fn asum_partial(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_id) lid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>
+) {
+    var sum = T(0.0);
+    for (var i = gid.x; i < params.n; i += groups.x * WORKGROUP_SIZE) {
+        sum += abs(a[i]);
+    }
+    scratch[lid.x] = sum;
+    reduce_sum(lid.x);
+    if lid.x == 0u {
+        partials[wid.x] = scratch[0];
+    }
+}
+
+// sum(a * a)
+@compute @workgroup_size(256)
+fn sumsq_partial(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_id) lid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>
+) {
+    var sum = T(0.0);
+    for (var i = gid.x; i < params.n; i += groups.x * WORKGROUP_SIZE) {
+        sum += a[i] * a[i];
+    }
+    scratch[lid.x] = sum;
+    reduce_sum(lid.x);
+    if lid.x == 0u {
+        partials[wid.x] = scratch[0];
+    }
+}
+
+// result[0] = sum(partials[0..n])
+@compute @workgroup_size(256)
+fn sum_final(@builtin(local_invocation_id) lid: vec3<u32>) {
+    var sum = T(0.0);
+    for (var i = lid.x; i < params.n; i += WORKGROUP_SIZE) {
+        sum += partials[i];
+    }
+    scratch[lid.x] = sum;
+    reduce_sum(lid.x);
+    if lid.x == 0u {
+        result[0] = scratch[0];
+    }
+}
+
+// result[0] = sqrt(sum(partials[0..n]))
+@compute @workgroup_size(256)
+fn sqrt_final(@builtin(local_invocation_id) lid: vec3<u32>) {
+    var sum = T(0.0);
+    for (var i = lid.x; i < params.n; i += WORKGROUP_SIZE) {
+        sum += partials[i];
+    }
+    scratch[lid.x] = sum;
+    reduce_sum(lid.x);
+    if lid.x == 0u {
+        result[0] = sqrt(scratch[0]);
+    }
+}
+
+// tree reduction of (`scratch`, `scratch_idx`) into index 0.
+// keeps the larger value, and the smaller index on a tie.
+fn reduce_max(lid: u32) {
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        workgroupBarrier();
+        if lid < stride {
+            let v = scratch[lid + stride];
+            let idx = scratch_idx[lid + stride];
+            if v > scratch[lid] || (v == scratch[lid] && idx < scratch_idx[lid]) {
+                scratch[lid] = v;
+                scratch_idx[lid] = idx;
+            }
+        }
+    }
+}
+
+// first index of max(|a|)
+@compute @workgroup_size(256)
+fn iamax_partial(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_id) lid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>
+) {
+    var best = T(-1.0);
+    var best_idx = 0xffffffffu;
+    for (var i = gid.x; i < params.n; i += groups.x * WORKGROUP_SIZE) {
+        let v = abs(a[i]);
+        if v > best {
+            best = v;
+            best_idx = i;
+        }
+    }
+    scratch[lid.x] = best;
+    scratch_idx[lid.x] = best_idx;
+    reduce_max(lid.x);
+    if lid.x == 0u {
+        partials[wid.x] = scratch[0];
+        partial_idx[wid.x] = scratch_idx[0] + params.offset;
+    }
+}
+
+// result_idx[0] = index of max(partials[0..n])
+@compute @workgroup_size(256)
+fn iamax_final(@builtin(local_invocation_id) lid: vec3<u32>) {
+    var best = T(-1.0);
+    var best_idx = 0xffffffffu;
+    for (var i = lid.x; i < params.n; i += WORKGROUP_SIZE) {
+        let v = partials[i];
+        let idx = partial_idx[i];
+        if v > best || (v == best && idx < best_idx) {
+            best = v;
+            best_idx = idx;
+        }
+    }
+    scratch[lid.x] = best;
+    scratch_idx[lid.x] = best_idx;
+    reduce_max(lid.x);
+    if lid.x == 0u {
+        result_idx[0] = scratch_idx[0];
+    }
+}
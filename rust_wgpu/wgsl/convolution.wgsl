@@ -1,9 +1,11 @@
-@group(0) @binding(0) var input_img: texture_2d<f32>;
-@group(0) @binding(1) var output_img: texture_storage_2d<rgba32float,write>;
+#include "image.wgsl"
 @group(0) @binding(2) var<storage, read> kernel: array<f32>;
 
+#ifdef FILTER_SIZE
+const FILTER_SIZE: i32 = {{FILTER_SIZE}};
+#endif
 
-@compute @workgroup_size(16,16)
+@compute @workgroup_size({{WORKGROUP_X}},{{WORKGROUP_Y}})
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     // load the grobal id
     let x = f32(global_id.x);
@@ -11,8 +13,17 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     let texture_dim = textureDimensions(input_img, 0);
 
-    let kernel_size = sqrt(f32(arrayLength(&kernel)));
-    let half_kernel_size = i32(kernel_size) / 2;
+    // the workgroups may overhang the image
+    if global_id.x >= texture_dim.x || global_id.y >= texture_dim.y {
+        return;
+    }
+
+#ifdef FILTER_SIZE
+    let half_kernel_size = FILTER_SIZE / 2;
+#else
+    // the filter size follows from the length of the kernel
+    let half_kernel_size = i32(sqrt(f32(arrayLength(&kernel)))) / 2;
+#endif
 
     // Initialize the sum as zero
     var sum: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 0.0);
@@ -31,13 +42,14 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
             // check if the position is within the texture boundaries
             // ignore the outer image.
             if pixel_pos_x >= 0.0 && pixel_pos_x < f32(texture_dim.x) && pixel_pos_y >= 0.0 && pixel_pos_y < f32(texture_dim.y) {
-                let pixel_val: vec4<f32> = textureLoad(input_img, vec2<i32>(i32(pixel_pos_x), i32(pixel_pos_y)), 0);
+                let pixel_val = load_texel(vec2<i32>(i32(pixel_pos_x), i32(pixel_pos_y)));
                 sum = sum + pixel_val * kernel[filt_idx];
-                filt_idx = filt_idx + 1;
             }
+            // the filter index moves on for the skipped pixels too
+            filt_idx = filt_idx + 1;
         }
     }
 
     // write the result to the output texture
-    textureStore(output_img, vec2<i32>(i32(x), i32(y)), sum);
+    store_texel(vec2<i32>(i32(x), i32(y)), sum);
 }
\ No newline at end of file
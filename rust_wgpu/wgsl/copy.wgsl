@@ -0,0 +1,20 @@
+// The element type `T` is provided by the host (alias T = f32; or u32).
+// Plain 2D copy with the same access pattern as transpose. Used as the bandwidth baseline.
+
+struct Dims {
+    rows: u32,
+    cols: u32,
+};
+
+@group(0) @binding(0) var<storage, read> input: array<T>;
+@group(0) @binding(1) var<storage, read_write> output: array<T>;
+@group(0) @binding(2) var<uniform> dims: Dims;
+
+@compute @workgroup_size(16,16)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let x = global_id.x;
+    let y = global_id.y;
+    if x < dims.cols && y < dims.rows {
+        output[y * dims.cols + x] = input[y * dims.cols + x];
+    }
+}
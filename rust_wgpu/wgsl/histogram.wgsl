@@ -0,0 +1,38 @@
+// Histogram of the luma of an RGBA image, in 256 bins over [0, HIST_RANGE].
+// One invocation per pixel; a workgroup has one invocation per bin.
+// the texel type and range are set by the host for the pixel type
+#ifndef TEXEL
+#define TEXEL f32
+#endif
+#ifndef HIST_RANGE
+#define HIST_RANGE 1.0
+#endif
+
+@group(0) @binding(0) var input_img: texture_2d<{{TEXEL}}>;
+@group(0) @binding(1) var<storage, read_write> bins: array<atomic<u32>, 256>;
+
+var<workgroup> local_bins: array<atomic<u32>, 256>;
+
+@compute @workgroup_size(16, 16)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    atomicStore(&local_bins[local_index], 0u);
+    workgroupBarrier();
+
+    // the workgroups may overhang the image, every invocation still reaches the barriers
+    let size = textureDimensions(input_img);
+    if global_id.x < size.x && global_id.y < size.y {
+        let pixel = vec4<f32>(textureLoad(input_img, vec2<i32>(global_id.xy), 0));
+        let luma = dot(pixel.rgb, vec3<f32>(0.299, 0.587, 0.114));
+        let bin = u32(clamp(luma * (256.0 / {{HIST_RANGE}}), 0.0, 255.0));
+        atomicAdd(&local_bins[bin], 1u);
+    }
+    workgroupBarrier();
+
+    let count = atomicLoad(&local_bins[local_index]);
+    if count > 0u {
+        atomicAdd(&bins[local_index], count);
+    }
+}
@@ -0,0 +1,30 @@
+// Bindings, workgroup size and texel access shared by the image shaders.
+#ifndef WORKGROUP_X
+#define WORKGROUP_X 16
+#endif
+#ifndef WORKGROUP_Y
+#define WORKGROUP_Y 16
+#endif
+// the texel type and storage format are set by the host for the pixel type
+#ifndef TEXEL
+#define TEXEL f32
+#endif
+#ifndef STORAGE_FORMAT
+#define STORAGE_FORMAT rgba32float
+#endif
+
+@group(0) @binding(0) var input_img: texture_2d<{{TEXEL}}>;
+@group(0) @binding(1) var output_img: texture_storage_2d<{{STORAGE_FORMAT}},write>;
+
+// the shaders compute in f32
+fn load_texel(coord: vec2<i32>) -> vec4<f32> {
+    return vec4<f32>(textureLoad(input_img, coord, 0));
+}
+
+fn store_texel(coord: vec2<i32>, value: vec4<f32>) {
+#ifdef INTEGER
+    textureStore(output_img, coord, vec4<{{TEXEL}}>(round(value)));
+#else
+    textureStore(output_img, coord, value);
+#endif
+}
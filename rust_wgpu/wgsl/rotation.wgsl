@@ -1,11 +1,35 @@
-@group(0) @binding(0) var input_img: texture_2d<f32>;
-@group(0) @binding(1) var output_img: texture_storage_2d<rgba32float,write>;
+#include "image.wgsl"
 @group(0) @binding(2) var<storage, read> img_size: array<u32>;
 @group(0) @binding(3) var<uniform> theta: f32;
 
+#ifdef BICUBIC
+// Catmull-Rom weights of the four texels around a location t past the second one
+fn cubic_weights(t: f32) -> vec4<f32> {
+    return vec4<f32>(
+        ((-0.5 * t + 1.0) * t - 0.5) * t,
+        (1.5 * t - 2.5) * t * t + 1.0,
+        ((-1.5 * t + 2.0) * t + 0.5) * t,
+        (0.5 * t - 0.5) * t * t
+    );
+}
+
+// texels outside of the image read as zero
+fn texel_or_zero(coord: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(i32(img_size[0]), i32(img_size[1]));
+    if any(coord < vec2<i32>(0)) || any(coord >= size) {
+        return vec4<f32>(0.0);
+    }
+    return load_texel(coord);
+}
+#endif
 
-@compute @workgroup_size(16,16)
+@compute @workgroup_size({{WORKGROUP_X}},{{WORKGROUP_Y}})
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    // the workgroups may overhang the image
+    if global_id.x >= img_size[0] || global_id.y >= img_size[1] {
+        return;
+    }
+
     var x = f32(global_id.x);
     var y = f32(global_id.y);
 
@@ -33,8 +57,25 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     // !! floor the position
     //
     // let coord = vec2<i32>(read_coord);
-    // let value = textureLoad(input_img, coord, 0);
+    // let value = load_texel(coord);
 
+#ifdef BICUBIC
+    //
+    // !! Bicubic interpolation over the 4x4 texels around the location
+    //
+    let base = floor(read_coord);
+    let p = vec2<i32>(base);
+    var wx = cubic_weights(read_coord.x - base.x);
+    var wy = cubic_weights(read_coord.y - base.y);
+    var value = vec4<f32>(0.0);
+    for (var j = 0; j < 4; j++) {
+        var row = vec4<f32>(0.0);
+        for (var i = 0; i < 4; i++) {
+            row += wx[i] * texel_or_zero(p + vec2<i32>(i - 1, j - 1));
+        }
+        value += wy[j] * row;
+    }
+#else
     // 
     // !! Bilinear interpolation
     //
@@ -45,16 +86,17 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let u_frac = fract(read_coord.x);
     let v_frac = fract(read_coord.y);
 
-    let texel_00 = textureLoad(input_img, vec2<i32>(u_int, v_int), 0);
-    let texel_10 = textureLoad(input_img, vec2<i32>(u_int + 1, v_int), 0);
-    let texel_01 = textureLoad(input_img, vec2<i32>(u_int, v_int + 1), 0);
-    let texel_11 = textureLoad(input_img, vec2<i32>(u_int + 1, v_int + 1), 0);
+    let texel_00 = load_texel(vec2<i32>(u_int, v_int));
+    let texel_10 = load_texel(vec2<i32>(u_int + 1, v_int));
+    let texel_01 = load_texel(vec2<i32>(u_int, v_int + 1));
+    let texel_11 = load_texel(vec2<i32>(u_int + 1, v_int + 1));
 
     // compute the bilinear interpolation
     let interp_u0 = texel_00 * (1.0 - u_frac) + texel_10 * u_frac;
     let interp_u1 = texel_01 * (1.0 - u_frac) + texel_11 * u_frac;
     let value = interp_u0 * (1.0 - v_frac) + interp_u1 * v_frac;
+#endif
 
     // Write to the output
-    textureStore(output_img, vec2<i32>(i32(x), i32(y)), value);
+    store_texel(vec2<i32>(i32(x), i32(y)), value);
 }
\ No newline at end of file
@@ -0,0 +1,36 @@
+// The element type `T` is provided by the host (alias T = f32; or u32).
+
+struct Dims {
+    rows: u32,
+    cols: u32,
+};
+
+@group(0) @binding(0) var<storage, read> input: array<T>;
+@group(0) @binding(1) var<storage, read_write> output: array<T>;
+@group(0) @binding(2) var<uniform> dims: Dims;
+
+// padding one column to avoid the bank conflicts on the column access.
+var<workgroup> tile: array<array<T, 17>, 16>;
+
+@compute @workgroup_size(16,16)
+fn main(
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>
+) {
+    // read the tile from the input (row-major, rows x cols)
+    let x = group_id.x * 16u + local_id.x;
+    let y = group_id.y * 16u + local_id.y;
+    if x < dims.cols && y < dims.rows {
+        tile[local_id.y][local_id.x] = input[y * dims.cols + x];
+    }
+
+    // wait until the whole tile is loaded
+    workgroupBarrier();
+
+    // write the transposed tile to the output (cols x rows)
+    let out_x = group_id.y * 16u + local_id.x;
+    let out_y = group_id.x * 16u + local_id.y;
+    if out_x < dims.rows && out_y < dims.cols {
+        output[out_y * dims.rows + out_x] = tile[local_id.x][local_id.y];
+    }
+}
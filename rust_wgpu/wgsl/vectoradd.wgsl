@@ -8,8 +8,10 @@ struct Buffer {
 
 @group(0) @binding(2) var<storage, read_write> out: array<f32>;
 
+// a grid-stride loop, so the host caps the dispatch size
 @compute @workgroup_size(256)
-fn main(@builtin(global_invocation_id) GlobalInvocationID: vec3<u32>) {
-    let gid = GlobalInvocationID.x;
-    out[gid] = in_a[gid] + in_b[gid];
+fn main(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    for (var i = gid.x; i < arrayLength(&out); i += groups.x * 256u) {
+        out[i] = in_a[i] + in_b[i];
+    }
 }